@@ -0,0 +1,316 @@
+use std::{collections::{BTreeMap, HashMap, HashSet}, fs, path::{Path, PathBuf}};
+
+use anyhow::{Context, Result, bail, ensure};
+use aws_sdk_ssm::types::{ParameterMetadata, ParameterStringFilter, ParameterTier, ParameterType, Tag};
+use futures::TryStreamExt;
+use itertools::Itertools;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::copy::DEFAULT_KMS_KEY;
+use crate::files::fetch_files;
+use crate::output::{Event, Redacted};
+use crate::ssm::{self, Client};
+use crate::tags::{add_tags, list_tags, with_change_reason};
+use crate::upload::{UploadOptions, is_skipped, read_local_tree};
+
+const NAME_FILTER_BATCH_SIZE: usize = 50;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SecretsManifest {
+  #[serde(default)]
+  pub prefix: String,
+  #[serde(default)]
+  pub defaults: Defaults,
+  pub parameters: Vec<Declared>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Defaults {
+  pub r#type: Option<String>,
+  pub kms_key_id: Option<String>,
+  pub tier: Option<String>,
+  #[serde(default)]
+  pub tags: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Declared {
+  pub name: String,
+  pub r#type: Option<String>,
+  pub kms_key_id: Option<String>,
+  pub tier: Option<String>,
+  pub description: Option<String>,
+  #[serde(default)]
+  pub tags: BTreeMap<String, String>,
+  pub value: Option<String>,
+  pub value_from_env: Option<String>,
+  pub value_from_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+struct Desired {
+  name: String,
+  r#type: ParameterType,
+  kms_key_id: Option<String>,
+  tier: Option<ParameterTier>,
+  description: Option<String>,
+  tags: BTreeMap<String, String>,
+  value: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Drift {
+  name: String,
+  field: &'static str,
+  expected: String,
+  actual: String,
+}
+
+#[derive(Debug, Default)]
+struct Actual {
+  metadata: Option<ParameterMetadata>,
+  value: Option<String>,
+  tags: BTreeMap<String, String>,
+}
+
+fn load(path: &Path) -> Result<Vec<Desired>> {
+  let text = fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+  let manifest: SecretsManifest = serde_yaml::from_str(&text).context(format!("Failed to parse {}", path.display()))?;
+  let base_dir = path.parent().unwrap_or(Path::new("."));
+
+  let mut seen = HashSet::new();
+  manifest
+    .parameters
+    .iter()
+    .map(|declared| {
+      let desired = resolve(&manifest.prefix, &manifest.defaults, declared, base_dir)?;
+      ensure!(seen.insert(desired.name.clone()), "{} is declared more than once in {}", desired.name, path.display());
+      Ok(desired)
+    })
+    .collect()
+}
+
+fn resolve(prefix: &str, defaults: &Defaults, declared: &Declared, base_dir: &Path) -> Result<Desired> {
+  let name = match declared.name.starts_with('/') || prefix.is_empty() {
+    true => declared.name.clone(),
+    false => format!("{}/{}", prefix.trim_end_matches('/'), declared.name.trim_start_matches('/')),
+  };
+
+  let r#type = match declared.r#type.as_ref().or(defaults.r#type.as_ref()).map(String::as_str) {
+    None | Some("SecureString") => ParameterType::SecureString,
+    Some("String") => ParameterType::String,
+    Some("StringList") => ParameterType::StringList,
+    Some(other) => bail!("{name} has unknown type {other}; expected String, StringList or SecureString"),
+  };
+  let tier = match declared.tier.as_ref().or(defaults.tier.as_ref()).map(String::as_str) {
+    None => None,
+    Some("Standard") => Some(ParameterTier::Standard),
+    Some("Advanced") => Some(ParameterTier::Advanced),
+    Some("Intelligent-Tiering") => Some(ParameterTier::IntelligentTiering),
+    Some(other) => bail!("{name} has unknown tier {other}; expected Standard, Advanced or Intelligent-Tiering"),
+  };
+  let kms_key_id = declared.kms_key_id.clone().or_else(|| defaults.kms_key_id.clone()).filter(|_| r#type == ParameterType::SecureString);
+
+  let value = match (&declared.value, &declared.value_from_env, &declared.value_from_file) {
+    (None, None, None) => None,
+    (Some(value), None, None) => Some(value.clone()),
+    (None, Some(var), None) => Some(std::env::var(var).context(format!("{name} reads its value from ${var}, which is not set"))?),
+    (None, None, Some(file)) => {
+      let path = base_dir.join(file);
+      Some(fs::read_to_string(&path).context(format!("Failed to read the value of {name} from {}", path.display()))?)
+    },
+    _ => bail!("{name} declares more than one of value, value_from_env and value_from_file"),
+  };
+
+  let tags = defaults.tags.iter().chain(&declared.tags).map(|(k, v)| (k.clone(), v.clone())).collect();
+  Ok(Desired { name, r#type, kms_key_id, tier, description: declared.description.clone(), tags, value })
+}
+
+async fn fetch_actual(client: &Client, desired: &[Desired]) -> Result<HashMap<String, Actual>> {
+  let names = desired.iter().map(|d| d.name.clone()).collect::<Vec<_>>();
+  let mut actual: HashMap<String, Actual> = HashMap::new();
+
+  for batch in names.chunks(NAME_FILTER_BATCH_SIZE) {
+    let filter = ParameterStringFilter::builder().key("Name").option("Equals").set_values(Some(batch.to_vec())).build()?;
+    for metadata in ssm::describe_parameters(client, vec![filter]).try_collect::<Vec<_>>().await?.into_iter().flatten() {
+      if let Some(name) = metadata.name.clone() {
+        actual.entry(name).or_default().metadata = Some(metadata);
+      }
+    }
+  }
+
+  let (params, _) = ssm::get_parameters(client, &names).await?;
+  for param in params {
+    if let Some(name) = param.name {
+      actual.entry(name).or_default().value = param.value;
+    }
+  }
+
+  let tagged = desired.iter().filter(|d| !d.tags.is_empty() && actual.contains_key(&d.name)).map(|d| d.name.clone()).collect::<Vec<_>>();
+  for name in tagged {
+    let tags = list_tags(client, &name).await?;
+    actual.entry(name).or_default().tags = tags.into_iter().map(|t| (t.key, t.value)).collect();
+  }
+  Ok(actual)
+}
+
+fn drift(desired: &Desired, actual: Option<&Actual>) -> Vec<Drift> {
+  let mut drifts = vec![];
+  let mut push = |field, expected: String, actual: String| drifts.push(Drift { name: desired.name.clone(), field, expected, actual });
+
+  let Some(actual) = actual.filter(|a| a.metadata.is_some()) else {
+    push("exists", "present".to_string(), "missing".to_string());
+    return drifts;
+  };
+  let metadata = actual.metadata.as_ref().expect("filtered on metadata");
+
+  let actual_type = metadata.r#type().cloned().unwrap_or(ParameterType::String);
+  if actual_type != desired.r#type {
+    push("type", desired.r#type.as_str().to_string(), actual_type.as_str().to_string());
+  }
+  if desired.r#type == ParameterType::SecureString {
+    let expected = desired.kms_key_id.as_deref().unwrap_or(DEFAULT_KMS_KEY);
+    let actual_key = metadata.key_id().unwrap_or(DEFAULT_KMS_KEY);
+    if expected != actual_key {
+      push("kms_key_id", expected.to_string(), actual_key.to_string());
+    }
+  }
+  if let Some(tier) = &desired.tier {
+    let actual_tier = metadata.tier().cloned().unwrap_or(ParameterTier::Standard);
+    if &actual_tier != tier {
+      push("tier", tier.as_str().to_string(), actual_tier.as_str().to_string());
+    }
+  }
+  if let Some(description) = &desired.description
+    && metadata.description() != Some(description.as_str())
+  {
+    push("description", description.clone(), metadata.description().unwrap_or_default().to_string());
+  }
+  for (key, value) in &desired.tags {
+    if actual.tags.get(key) != Some(value) {
+      push("tag", format!("{key}={value}"), actual.tags.get(key).map(|v| format!("{key}={v}")).unwrap_or_default());
+    }
+  }
+  if let Some(value) = &desired.value
+    && actual.value.as_ref() != Some(value)
+  {
+    push("value", Redacted(value).to_string(), Redacted(actual.value.as_deref().unwrap_or_default()).to_string());
+  }
+  drifts
+}
+
+fn report(drifts: &[Drift]) {
+  for d in drifts {
+    Event::new("drift", d.name.as_str())
+      .with("field", d.field)
+      .with("expected", d.expected.as_str())
+      .with("actual", d.actual.as_str())
+      .emit(format!("{}\t{}\texpected {}, found {}", d.name, d.field, d.expected, d.actual));
+  }
+}
+
+pub async fn check(client: &Client, manifest: PathBuf) -> Result<()> {
+  let desired = load(&manifest)?;
+  let actual = fetch_actual(client, &desired).await?;
+  let drifts = desired.iter().flat_map(|d| drift(d, actual.get(&d.name))).collect::<Vec<_>>();
+
+  let drifted = drifts.iter().map(|d| &d.name).collect::<HashSet<_>>().len();
+
+  report(&drifts);
+  Event::new("check", manifest.display().to_string())
+    .with("parameters", desired.len())
+    .with("drifted", drifted)
+    .emit(format!("Checked {} parameters from {}: {drifted} drifted", desired.len(), manifest.display()));
+  ensure!(drifts.is_empty(), "{drifted} parameters drifted from {}", manifest.display());
+  Ok(())
+}
+
+pub async fn check_dir(client: &Client, s3: Option<&aws_sdk_s3::Client>, dir: PathBuf, prefix: String, options: UploadOptions) -> Result<()> {
+  let (local, skipped) = read_local_tree(&dir, &prefix, &options).await?;
+  let remote = fetch_files(client, s3, &prefix)
+    .await?
+    .into_iter()
+    .filter(|(key, _)| !is_skipped(key, &skipped))
+    .collect::<HashMap<_, _>>();
+  let name = |key: &str| format!("{}/{key}", prefix.trim_end_matches('/'));
+
+  let mut drifts = vec![];
+  for key in local.keys().chain(remote.keys()).unique().sorted() {
+    let drift = |field, expected: String, actual: String| Drift { name: name(key), field, expected, actual };
+    match (local.get(key), remote.get(key)) {
+      (Some(_), None) => drifts.push(drift("exists", "present".to_string(), "missing".to_string())),
+      (None, Some(_)) => drifts.push(drift("exists", "absent".to_string(), "present".to_string())),
+      (Some(a), Some(b)) if a != b => drifts.push(drift("value", digest(a), digest(b))),
+      _ => {},
+    }
+  }
+
+  report(&drifts);
+  Event::new("check", prefix.as_str())
+    .with("dir", dir.display().to_string())
+    .with("files", local.len())
+    .with("drifted", drifts.len())
+    .emit(format!("Checked {} files in {} against {prefix}: {} drifted", local.len(), dir.display(), drifts.len()));
+  ensure!(drifts.is_empty(), "{} parameters under {prefix} drifted from {}", drifts.len(), dir.display());
+  Ok(())
+}
+
+fn digest(content: &[u8]) -> String {
+  format!("sha256:{}", hex::encode(Sha256::digest(content)))
+}
+
+pub async fn apply(client: &Client, manifest: PathBuf, dry_run: bool) -> Result<()> {
+  let desired = load(&manifest)?;
+  let actual = fetch_actual(client, &desired).await?;
+
+  let mut changed = 0;
+  for d in &desired {
+    let current = actual.get(&d.name);
+    let drifts = drift(d, current);
+    if drifts.is_empty() {
+      continue;
+    }
+    changed += 1;
+
+    let needs_put = drifts.iter().any(|drift| drift.field != "tag");
+    let value = d.value.clone().or_else(|| current.and_then(|a| a.value.clone()));
+    let fields = drifts.iter().map(|drift| drift.field).collect::<Vec<_>>().join(", ");
+    if dry_run {
+      Event::new("would-apply", d.name.as_str()).with("fields", fields.as_str()).emit(format!("Would update {} ({fields})", d.name));
+      continue;
+    }
+
+    if needs_put {
+      let value = value.context(format!("{} doesn't exist and declares no value source", d.name))?;
+      ssm::ensure_writable("ssm:PutParameter", &d.name)?;
+      ssm::retry_throttled(|| {
+        client
+          .put_parameter()
+          .name(&d.name)
+          .value(&value)
+          .overwrite(true)
+          .r#type(d.r#type.clone())
+          .set_key_id(d.kms_key_id.clone())
+          .set_tier(d.tier.clone())
+          .set_description(d.description.clone())
+          .send()
+      })
+      .await
+      .context(format!("Failed to apply {}", d.name))?;
+    }
+
+    let tags = d.tags.iter().map(|(key, value)| Tag::builder().key(key).value(value).build()).collect::<Result<Vec<_>, _>>()?;
+    add_tags(client, &d.name, &with_change_reason(&tags)).await?;
+    Event::new("apply", d.name.as_str()).with("fields", fields.as_str()).emit(format!("Updated {} ({fields})", d.name));
+  }
+
+  Event::new("applied", manifest.display().to_string())
+    .with("parameters", desired.len())
+    .with("changed", changed)
+    .emit(format!("Applied {}: {changed} of {} parameters changed", manifest.display(), desired.len()));
+  Ok(())
+}
@@ -0,0 +1,147 @@
+use std::{collections::{BTreeMap, BTreeSet}, sync::LazyLock, time::{SystemTime, UNIX_EPOCH}};
+
+use anyhow::{Result, ensure};
+use aws_sdk_ssm::types::{ParameterMetadata, ParameterType};
+use futures::TryStreamExt;
+use regex::Regex;
+
+use crate::copy::DEFAULT_KMS_KEY;
+use crate::files::{MANIFEST_SUFFIX, Manifest, split_part};
+use crate::output::Event;
+use crate::ssm::{self, Client};
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+static SECRET_NAME: LazyLock<Regex> =
+  LazyLock::new(|| Regex::new(r"(?i)(password|passwd|secret|token|api[_-]?key|private[_-]?key|credential)").expect("valid regex"));
+static SECRET_VALUE: LazyLock<Regex> = LazyLock::new(|| {
+  Regex::new(r"(-----BEGIN [A-Z ]*PRIVATE KEY-----|AKIA[0-9A-Z]{16}|[a-z][a-z0-9+.-]*://[^/\s:@]+:[^/\s@]+@|xox[abposr]-[0-9A-Za-z-]+|gh[pousr]_[0-9A-Za-z]{36})")
+    .expect("valid regex")
+});
+
+#[derive(Debug, Clone)]
+pub struct AuditOptions {
+  pub max_age_days: i64,
+  pub strict: bool,
+}
+
+impl Default for AuditOptions {
+  fn default() -> Self {
+    Self { max_age_days: 90, strict: false }
+  }
+}
+
+#[derive(Debug, Clone)]
+struct Finding {
+  check: &'static str,
+  name: String,
+  detail: String,
+}
+
+pub async fn audit(client: &Client, prefix: String, options: AuditOptions) -> Result<()> {
+  let metadata = ssm::all_parameter_metadata_by_path(client, &prefix).try_collect::<Vec<_>>().await?.into_iter().flatten().collect::<Vec<_>>();
+
+  let mut findings = stale(&metadata, options.max_age_days);
+  findings.extend(default_kms_key(&metadata));
+  findings.extend(plaintext_secrets(client, &metadata).await?);
+  findings.extend(orphaned_chunks(client, &metadata).await?);
+
+  for finding in &findings {
+    Event::new("finding", finding.name.as_str())
+      .with("check", finding.check)
+      .with("detail", finding.detail.as_str())
+      .emit(format!("{}\t{}\t{}", finding.check, finding.name, finding.detail));
+  }
+  Event::new("audit", prefix.as_str())
+    .with("parameters", metadata.len())
+    .with("findings", findings.len())
+    .emit(format!("Audited {} parameters under {prefix}: {} findings", metadata.len(), findings.len()));
+
+  ensure!(!options.strict || findings.is_empty(), "Audit found {} issues under {prefix}", findings.len());
+  Ok(())
+}
+
+fn stale(metadata: &[ParameterMetadata], max_age_days: i64) -> Vec<Finding> {
+  let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or_default();
+
+  metadata
+    .iter()
+    .filter_map(|m| {
+      let days = (now - m.last_modified_date()?.secs()) / SECONDS_PER_DAY;
+      let name = m.name()?.to_string();
+      (days > max_age_days).then(|| Finding { check: "stale", name, detail: format!("last modified {days} days ago") })
+    })
+    .collect()
+}
+
+fn default_kms_key(metadata: &[ParameterMetadata]) -> Vec<Finding> {
+  metadata
+    .iter()
+    .filter(|m| m.r#type() == Some(&ParameterType::SecureString))
+    .filter(|m| m.key_id().is_none_or(|key| key == DEFAULT_KMS_KEY))
+    .filter_map(|m| Some(Finding { check: "default-kms-key", name: m.name()?.to_string(), detail: format!("encrypted with {DEFAULT_KMS_KEY}") }))
+    .collect()
+}
+
+async fn plaintext_secrets(client: &Client, metadata: &[ParameterMetadata]) -> Result<Vec<Finding>> {
+  let names = metadata
+    .iter()
+    .filter(|m| m.r#type() != Some(&ParameterType::SecureString))
+    .filter_map(|m| m.name.clone())
+    .collect::<Vec<_>>();
+  let (params, _) = ssm::get_parameters(client, &names).await?;
+
+  Ok(
+    params
+      .iter()
+      .filter_map(|p| {
+        let name = p.name()?;
+        let detail = if SECRET_VALUE.is_match(p.value().unwrap_or_default()) {
+          "value looks like a credential"
+        } else if SECRET_NAME.is_match(name.rsplit('/').next().unwrap_or(name)) {
+          "name suggests a secret"
+        } else {
+          return None;
+        };
+        Some(Finding { check: "plaintext-secret", name: name.to_string(), detail: format!("{detail} but the type is {}", p.r#type()?.as_str()) })
+      })
+      .collect(),
+  )
+}
+
+async fn orphaned_chunks(client: &Client, metadata: &[ParameterMetadata]) -> Result<Vec<Finding>> {
+  let mut parts: BTreeMap<&str, BTreeSet<usize>> = BTreeMap::new();
+  let mut manifests = vec![];
+  for name in metadata.iter().filter_map(|m| m.name()) {
+    if let Some(base) = name.strip_suffix(MANIFEST_SUFFIX) {
+      manifests.push(name.to_string());
+      parts.entry(base).or_default();
+    } else if let (base, Some(idx)) = split_part(name) {
+      parts.entry(base).or_default().insert(idx);
+    }
+  }
+
+  let (params, _) = ssm::get_parameters(client, &manifests).await?;
+  let expected = params
+    .iter()
+    .filter_map(|p| {
+      let manifest = serde_json::from_str::<Manifest>(p.value()?).ok()?;
+      Some((p.name()?.strip_suffix(MANIFEST_SUFFIX)?.to_string(), manifest.parts))
+    })
+    .collect::<BTreeMap<_, _>>();
+
+  Ok(
+    parts
+      .into_iter()
+      .filter_map(|(base, found)| {
+        let detail = match expected.get(base) {
+          None if manifests.iter().any(|m| m.strip_suffix(MANIFEST_SUFFIX) == Some(base)) => "manifest is unreadable".to_string(),
+          None => format!("{} parts without a manifest", found.len()),
+          Some(&count) if found.iter().copied().ne(0..count) => format!("manifest expects {count} parts but found {found:?}"),
+          Some(_) => return None,
+        };
+        Some(Finding { check: "orphaned-chunk", name: base.to_string(), detail })
+      })
+      .collect(),
+  )
+}
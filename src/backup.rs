@@ -0,0 +1,133 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::{Context, Result, ensure};
+use aws_sdk_ssm::types::{ParameterTier, ParameterType, Tag};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::crypto;
+use crate::download::write_atomic;
+use crate::files::S3_SUFFIX;
+use crate::output::Event;
+use crate::ssm::{self, Client};
+use crate::tags::{add_tags, list_tags, with_change_reason};
+
+const BACKUP_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Backup {
+  version: u32,
+  prefix: String,
+  parameters: Vec<BackupEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BackupEntry {
+  name: String,
+  value: String,
+  r#type: String,
+  description: Option<String>,
+  tier: Option<String>,
+  key_id: Option<String>,
+  data_type: Option<String>,
+  allowed_pattern: Option<String>,
+  tags: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RestoreOptions {
+  pub to_prefix: Option<String>,
+  pub kms_key_id: Option<String>,
+  pub dry_run: bool,
+}
+
+pub async fn backup(client: &Client, prefix: String, out: PathBuf, recipients: Vec<age::x25519::Recipient>) -> Result<()> {
+  let prefix = prefix.trim_end_matches('/').to_string();
+  let params = ssm::all_parameters_by_path(client, &prefix, true).try_collect::<Vec<_>>().await?.into_iter().flatten().collect::<Vec<_>>();
+  let metadata = ssm::all_parameter_metadata_by_path(client, &prefix)
+    .try_collect::<Vec<_>>()
+    .await?
+    .into_iter()
+    .flatten()
+    .filter_map(|m| Some((m.name.clone()?, m)))
+    .collect::<BTreeMap<_, _>>();
+
+  let mut parameters = vec![];
+  for param in params {
+    let name = param.name().unwrap_or_default().to_string();
+    if name.ends_with(S3_SUFFIX) {
+      tracing::warn!("{name} points at an S3 object; only the pointer is backed up");
+    }
+
+    let meta = metadata.get(&name);
+    let tags = list_tags(client, &name).await?.into_iter().map(|t| (t.key, t.value)).collect();
+    parameters.push(BackupEntry {
+      value: param.value().unwrap_or_default().to_string(),
+      r#type: param.r#type().map(|t| t.as_str().to_string()).unwrap_or_default(),
+      description: meta.and_then(|m| m.description()).map(str::to_string),
+      tier: meta.and_then(|m| m.tier()).map(|t| t.as_str().to_string()),
+      key_id: meta.and_then(|m| m.key_id()).map(str::to_string),
+      data_type: param.data_type().map(str::to_string),
+      allowed_pattern: meta.and_then(|m| m.allowed_pattern()).map(str::to_string),
+      tags,
+      name,
+    });
+  }
+
+  let count = parameters.len();
+  let archive = serde_json::to_vec(&Backup { version: BACKUP_VERSION, prefix, parameters })?;
+  write_atomic(&out, &crypto::encrypt(&recipients, &archive)?).context(format!("Failed to write {}", out.display()))?;
+
+  let path = out.display().to_string();
+  Event::new("backup", path.as_str()).with("parameters", count).emit(format!("Backed up {count} parameters to {path}"));
+  Ok(())
+}
+
+pub async fn restore(client: &Client, input: PathBuf, identity: PathBuf, options: RestoreOptions) -> Result<()> {
+  let identities = crypto::load_identities(&identity)?;
+  let ciphertext = std::fs::read(&input).context(format!("Failed to read {}", input.display()))?;
+  let backup: Backup = serde_json::from_slice(&crypto::decrypt(&identities, &ciphertext)?).context("Invalid backup archive")?;
+  ensure!(backup.version == BACKUP_VERSION, "Unsupported backup version {}", backup.version);
+
+  let to_prefix = options.to_prefix.as_deref().map(|p| p.trim_end_matches('/')).unwrap_or(&backup.prefix);
+  for entry in &backup.parameters {
+    let name = format!("{to_prefix}{}", entry.name.strip_prefix(&backup.prefix).unwrap_or(&entry.name));
+    let r#type = ParameterType::from(entry.r#type.as_str());
+
+    if options.dry_run {
+      Event::new("would-restore", name.as_str())
+        .with("bytes", entry.value.len())
+        .with("type", r#type.as_str())
+        .emit(format!("Would restore {name} ({} bytes, {})", entry.value.len(), r#type.as_str()));
+      continue;
+    }
+
+    let key_id = match r#type {
+      ParameterType::SecureString => options.kms_key_id.clone().or_else(|| entry.key_id.clone()),
+      _ => None,
+    };
+    ssm::ensure_writable("ssm:PutParameter", &name)?;
+    let resp = ssm::retry_throttled(|| {
+      client
+        .put_parameter()
+        .name(&name)
+        .value(&entry.value)
+        .overwrite(true)
+        .r#type(r#type.clone())
+        .set_key_id(key_id.clone())
+        .set_description(entry.description.clone())
+        .set_tier(entry.tier.as_deref().map(ParameterTier::from))
+        .set_data_type(entry.data_type.clone())
+        .set_allowed_pattern(entry.allowed_pattern.clone())
+        .send()
+    })
+    .await
+    .context(format!("Failed to restore {name}"))?;
+
+    let tags = entry.tags.iter().map(|(key, value)| Tag::builder().key(key).value(value).build()).collect::<Result<Vec<_>, _>>()?;
+    add_tags(client, &name, &with_change_reason(&tags)).await?;
+    Event::new("restore", name.as_str()).with("version", resp.version()).emit(format!("Restored {name} (v{})", resp.version()));
+  }
+
+  Ok(())
+}
@@ -0,0 +1,399 @@
+use std::{collections::{BTreeMap, HashMap, HashSet}, io::{IsTerminal, Write}};
+
+use anyhow::{Context, Result, bail, ensure};
+use aws_sdk_ssm::types::ParameterType;
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use futures::TryStreamExt;
+use itertools::Itertools;
+use ratatui::{
+  DefaultTerminal, Frame,
+  crossterm::event::{self, Event, KeyCode, KeyEventKind},
+  layout::{Constraint, Layout},
+  style::{Style, Stylize},
+  text::Line,
+  widgets::{Block, List, ListState, Paragraph, Wrap},
+};
+
+use crate::files::{fetch_named, logical_name};
+use crate::s3;
+use crate::ssm::{self, Client};
+use crate::tags::{add_tags, with_change_reason};
+
+const DELETE_BATCH_SIZE: usize = 10;
+const HELP: &str = "↑↓ move  ←→ fold  / search  r reveal  y copy  d delete  m move  q quit";
+
+#[derive(Debug, Clone)]
+struct Leaf {
+  names: Vec<String>,
+  r#type: String,
+  version: i64,
+  modified: String,
+  key_id: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Row {
+  path: String,
+  label: String,
+  dir: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Mode {
+  Normal,
+  Search,
+  Move(String),
+  ConfirmDelete,
+}
+
+struct Browser<'a> {
+  client: &'a Client,
+  s3: Option<&'a aws_sdk_s3::Client>,
+  prefix: String,
+  dry_run: bool,
+  leaves: BTreeMap<String, Leaf>,
+  expanded: HashSet<String>,
+  selected: usize,
+  query: String,
+  mode: Mode,
+  values: HashMap<String, Vec<u8>>,
+  revealed: HashSet<String>,
+  status: String,
+}
+
+pub async fn browse(client: &Client, s3: Option<&aws_sdk_s3::Client>, prefix: String, dry_run: bool) -> Result<()> {
+  ensure!(std::io::stdout().is_terminal(), "browse needs an interactive terminal");
+
+  let prefix = prefix.trim_end_matches('/').to_string();
+  let leaves = load(client, &prefix).await?;
+  let mut browser = Browser {
+    client,
+    s3,
+    prefix,
+    dry_run,
+    leaves,
+    expanded: HashSet::new(),
+    selected: 0,
+    query: String::new(),
+    mode: Mode::Normal,
+    values: HashMap::new(),
+    revealed: HashSet::new(),
+    status: HELP.to_string(),
+  };
+
+  let mut terminal = ratatui::init();
+  let result = browser.run(&mut terminal).await;
+  ratatui::restore();
+  result
+}
+
+async fn load(client: &Client, prefix: &str) -> Result<BTreeMap<String, Leaf>> {
+  let path = if prefix.is_empty() { "/" } else { prefix };
+  let metadata = ssm::all_parameter_metadata_by_path(client, path).try_collect::<Vec<_>>().await?;
+
+  Ok(
+    metadata
+      .into_iter()
+      .flatten()
+      .filter(|m| m.name().is_some())
+      .into_group_map_by(|m| logical_name(m.name().unwrap_or_default()).to_string())
+      .into_iter()
+      .map(|(name, parts)| {
+        let leaf = Leaf {
+          names: parts.iter().filter_map(|m| m.name.clone()).collect(),
+          r#type: parts.first().and_then(|m| m.r#type()).map(|t| t.as_str().to_string()).unwrap_or_default(),
+          version: parts.iter().map(|m| m.version()).max().unwrap_or_default(),
+          modified: parts.iter().filter_map(|m| m.last_modified_date()).max().map(|d| d.to_string()).unwrap_or_default(),
+          key_id: parts.first().and_then(|m| m.key_id()).map(str::to_string),
+        };
+        (name, leaf)
+      })
+      .collect(),
+  )
+}
+
+impl Browser<'_> {
+  async fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+    loop {
+      let rows = self.rows();
+      self.selected = self.selected.min(rows.len().saturating_sub(1));
+      terminal.draw(|frame| self.draw(frame, &rows))?;
+
+      let Event::Key(key) = tokio::task::spawn_blocking(event::read).await?? else {
+        continue;
+      };
+      if key.kind != KeyEventKind::Press {
+        continue;
+      }
+
+      let selected = rows.get(self.selected).cloned();
+      match self.handle(key.code, selected, rows.len()).await {
+        Ok(true) => return Ok(()),
+        Ok(false) => {},
+        Err(e) => self.status = format!("{e:#}"),
+      }
+    }
+  }
+
+  fn rows(&self) -> Vec<Row> {
+    if !self.query.is_empty() {
+      let query = self.query.to_lowercase();
+      return self
+        .leaves
+        .keys()
+        .filter(|name| name.to_lowercase().contains(&query))
+        .map(|name| Row { path: name.clone(), label: self.relative(name).to_string(), dir: false })
+        .collect();
+    }
+
+    let mut nodes = HashMap::new();
+    for name in self.leaves.keys() {
+      let segments = self.relative(name).split('/').collect::<Vec<_>>();
+      for depth in 1..segments.len() {
+        nodes.insert(format!("{}/{}", self.prefix, segments[..depth].join("/")), true);
+      }
+      nodes.insert(name.clone(), false);
+    }
+
+    let mut rows = vec![];
+    let mut collapsed: Option<String> = None;
+    for (path, dir) in nodes.into_iter().sorted_by_cached_key(|(path, _)| path.split('/').map(str::to_string).collect::<Vec<_>>()) {
+      if collapsed.as_ref().is_some_and(|c| path.starts_with(&format!("{c}/"))) {
+        continue;
+      }
+      collapsed = (dir && !self.expanded.contains(&path)).then(|| path.clone());
+
+      let depth = self.relative(&path).matches('/').count();
+      let marker = match (dir, self.expanded.contains(&path)) {
+        (true, true) => "▾ ",
+        (true, false) => "▸ ",
+        (false, _) => "  ",
+      };
+      let label = format!("{}{marker}{}", "  ".repeat(depth), path.rsplit('/').next().unwrap_or_default());
+      rows.push(Row { path, label, dir });
+    }
+    rows
+  }
+
+  fn relative<'b>(&self, name: &'b str) -> &'b str {
+    name.strip_prefix(&self.prefix).unwrap_or(name).trim_start_matches('/')
+  }
+
+  fn draw(&self, frame: &mut Frame, rows: &[Row]) {
+    let [main, status] = Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+    let [tree, preview] = Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(main);
+
+    let title = if self.query.is_empty() { format!(" {}/ ", self.prefix) } else { format!(" {}/ matching \"{}\" ", self.prefix, self.query) };
+    let list = List::new(rows.iter().map(|row| row.label.as_str()))
+      .block(Block::bordered().title(title))
+      .highlight_style(Style::new().reversed());
+    frame.render_stateful_widget(list, tree, &mut ListState::default().with_selected(Some(self.selected)));
+
+    let lines = rows.get(self.selected).map(|row| self.preview(row)).unwrap_or_default();
+    frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(" preview ")).wrap(Wrap { trim: false }), preview);
+
+    let status_line = match &self.mode {
+      Mode::Normal => Line::from(self.status.as_str()),
+      Mode::Search => Line::from(format!("/{}", self.query)),
+      Mode::Move(to) => Line::from(format!("Move to: {to}")),
+      Mode::ConfirmDelete => Line::from("Delete this parameter? (y/n)").bold(),
+    };
+    frame.render_widget(Paragraph::new(status_line), status);
+  }
+
+  fn preview(&self, row: &Row) -> Vec<Line<'static>> {
+    if row.dir {
+      let count = self.leaves.keys().filter(|name| name.starts_with(&format!("{}/", row.path))).count();
+      return vec![Line::from(row.path.clone()).bold(), Line::from(format!("{count} parameters"))];
+    }
+
+    let Some(leaf) = self.leaves.get(&row.path) else {
+      return vec![];
+    };
+    let mut lines = vec![
+      Line::from(row.path.clone()).bold(),
+      Line::from(format!("type:     {}", leaf.r#type)),
+      Line::from(format!("version:  {}", leaf.version)),
+      Line::from(format!("modified: {}", leaf.modified)),
+      Line::from(format!("parts:    {}", leaf.names.len())),
+      Line::from(""),
+    ];
+    match (self.values.get(&row.path), self.revealed.contains(&row.path)) {
+      (Some(value), true) => lines.extend(String::from_utf8_lossy(value).lines().map(|line| Line::from(line.to_string()))),
+      (Some(value), false) => lines.push(Line::from(format!("<hidden, {} bytes> press r to reveal", value.len())).italic()),
+      (None, _) => lines.push(Line::from("<hidden> press r to reveal").italic()),
+    }
+    lines
+  }
+
+  async fn handle(&mut self, code: KeyCode, row: Option<Row>, count: usize) -> Result<bool> {
+    match (&mut self.mode, code) {
+      (Mode::Search, KeyCode::Enter) => self.mode = Mode::Normal,
+      (Mode::Search, KeyCode::Esc) => {
+        self.query.clear();
+        self.mode = Mode::Normal;
+      },
+      (Mode::Search, KeyCode::Backspace) => {
+        self.query.pop();
+      },
+      (Mode::Search, KeyCode::Char(c)) => {
+        self.query.push(c);
+        self.selected = 0;
+      },
+      (Mode::Move(to), KeyCode::Backspace) => {
+        to.pop();
+      },
+      (Mode::Move(to), KeyCode::Char(c)) => to.push(c),
+      (Mode::Move(to), KeyCode::Enter) => {
+        let to = to.clone();
+        self.mode = Mode::Normal;
+        if let Some(row) = row {
+          self.move_leaf(&row.path, &to).await?;
+        }
+      },
+      (Mode::ConfirmDelete, KeyCode::Char('y')) => {
+        self.mode = Mode::Normal;
+        if let Some(row) = row {
+          self.delete_leaf(&row.path).await?;
+        }
+      },
+      (Mode::Search | Mode::Move(_) | Mode::ConfirmDelete, _) => {
+        self.mode = Mode::Normal;
+        self.status = HELP.to_string();
+      },
+      (Mode::Normal, KeyCode::Char('q') | KeyCode::Esc) => return Ok(true),
+      (Mode::Normal, KeyCode::Up | KeyCode::Char('k')) => self.selected = self.selected.saturating_sub(1),
+      (Mode::Normal, KeyCode::Down | KeyCode::Char('j')) => self.selected = (self.selected + 1).min(count.saturating_sub(1)),
+      (Mode::Normal, KeyCode::Char('/')) => self.mode = Mode::Search,
+      (Mode::Normal, KeyCode::Right | KeyCode::Char('l') | KeyCode::Enter) => {
+        if let Some(row) = row.filter(|row| row.dir) {
+          self.expanded.insert(row.path);
+        }
+      },
+      (Mode::Normal, KeyCode::Left | KeyCode::Char('h')) => {
+        if let Some(row) = row
+          && !self.expanded.remove(&row.path)
+        {
+          let parent = row.path.rsplit_once('/').map(|(parent, _)| parent.to_string()).unwrap_or_default();
+          self.expanded.remove(&parent);
+        }
+      },
+      (Mode::Normal, KeyCode::Char('r')) => {
+        if let Some(row) = row.filter(|row| !row.dir) {
+          self.value(&row.path).await?;
+          if !self.revealed.remove(&row.path) {
+            self.revealed.insert(row.path);
+          }
+        }
+      },
+      (Mode::Normal, KeyCode::Char('y')) => {
+        if let Some(row) = row.filter(|row| !row.dir) {
+          let value = self.value(&row.path).await?;
+          copy_to_clipboard(&value)?;
+          self.status = format!("Copied {} to the clipboard", row.path);
+        }
+      },
+      (Mode::Normal, KeyCode::Char('d')) => {
+        if row.is_some_and(|row| !row.dir) {
+          self.mode = Mode::ConfirmDelete;
+        }
+      },
+      (Mode::Normal, KeyCode::Char('m')) => {
+        if let Some(row) = row.filter(|row| !row.dir) {
+          self.mode = Mode::Move(row.path);
+        }
+      },
+      (Mode::Normal, _) => {},
+    }
+    Ok(false)
+  }
+
+  async fn value(&mut self, name: &str) -> Result<Vec<u8>> {
+    if let Some(value) = self.values.get(name) {
+      return Ok(value.clone());
+    }
+
+    let leaf = self.leaves.get(name).context(format!("Parameter {name} not found"))?;
+    let (parent, file_name) = name.rsplit_once('/').unwrap_or_default();
+    let mut files = s3::resolve_pointers(self.s3, fetch_named(self.client, parent, &leaf.names).await?).await?;
+    let value = files.remove(file_name).context(format!("Parameter {name} not found"))?;
+
+    self.values.insert(name.to_string(), value.clone());
+    Ok(value)
+  }
+
+  async fn delete_leaf(&mut self, name: &str) -> Result<()> {
+    let leaf = self.leaves.get(name).context(format!("Parameter {name} not found"))?;
+    if self.dry_run {
+      self.status = format!("Would delete {name}");
+      return Ok(());
+    }
+
+    ssm::ensure_writable("ssm:DeleteParameters", name)?;
+    for batch in leaf.names.chunks(DELETE_BATCH_SIZE) {
+      self.client.delete_parameters().set_names(Some(batch.to_vec())).send().await?;
+    }
+    self.reload().await?;
+    self.status = format!("Deleted {name}");
+    Ok(())
+  }
+
+  async fn move_leaf(&mut self, name: &str, to: &str) -> Result<()> {
+    let to = to.trim_end_matches('/');
+    if to == name {
+      return Ok(());
+    }
+    ensure!(to.starts_with('/'), "{to} must be an absolute parameter name");
+    if self.leaves.contains_key(to) {
+      bail!("{to} already exists");
+    }
+
+    let leaf = self.leaves.get(name).context(format!("Parameter {name} not found"))?.clone();
+    if self.dry_run {
+      self.status = format!("Would move {name} to {to}");
+      return Ok(());
+    }
+
+    ssm::ensure_writable("ssm:PutParameter", to)?;
+    let (params, _) = ssm::get_parameters(self.client, &leaf.names).await?;
+    for param in &params {
+      let old_name = param.name().unwrap_or_default();
+      let r#type = param.r#type().cloned().unwrap_or(ParameterType::String);
+      let key_id = (r#type == ParameterType::SecureString).then(|| leaf.key_id.clone()).flatten();
+      let new_name = format!("{to}{}", old_name.strip_prefix(name).unwrap_or_default());
+      self
+        .client
+        .put_parameter()
+        .name(&new_name)
+        .value(param.value().unwrap_or_default())
+        .r#type(r#type)
+        .set_key_id(key_id)
+        .set_data_type(param.data_type().map(str::to_string))
+        .overwrite(false)
+        .send()
+        .await?;
+      add_tags(self.client, &new_name, &with_change_reason(&[])).await?;
+    }
+    for batch in leaf.names.chunks(DELETE_BATCH_SIZE) {
+      self.client.delete_parameters().set_names(Some(batch.to_vec())).send().await?;
+    }
+
+    self.values.remove(name);
+    self.revealed.remove(name);
+    self.reload().await?;
+    self.status = format!("Moved {name} to {to}");
+    Ok(())
+  }
+
+  async fn reload(&mut self) -> Result<()> {
+    self.leaves = load(self.client, &self.prefix).await?;
+    Ok(())
+  }
+}
+
+fn copy_to_clipboard(content: &[u8]) -> Result<()> {
+  let mut stdout = std::io::stdout();
+  write!(stdout, "\x1b]52;c;{}\x07", BASE64.encode(content))?;
+  stdout.flush()?;
+  Ok(())
+}
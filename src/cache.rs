@@ -0,0 +1,149 @@
+use std::{fs, io::ErrorKind, path::{Path, PathBuf}, str::FromStr, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use age::{Identity, secrecy::ExposeSecret, x25519};
+use anyhow::{Context, Result, anyhow, ensure};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use sha2::{Digest, Sha256};
+
+use crate::crypto;
+use crate::download::write_atomic;
+use crate::pin::Pin;
+use crate::store::{Entry, SecretStore};
+
+const KEY_FILE: &str = "cache.key";
+const ENTRY_SUFFIX: &str = ".age";
+
+#[derive(Debug, Clone)]
+pub struct CacheOptions {
+  pub dir: PathBuf,
+  pub ttl: Duration,
+  pub namespace: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Cached<T> {
+  created: u64,
+  value: T,
+}
+
+pub struct CachedStore {
+  inner: Box<dyn SecretStore>,
+  options: CacheOptions,
+  identity: x25519::Identity,
+}
+
+pub fn default_dir() -> Option<PathBuf> {
+  let cache_home = std::env::var_os("XDG_CACHE_HOME").map(PathBuf::from).or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")));
+  cache_home.map(|dir| dir.join("ops"))
+}
+
+pub fn parse_ttl(value: &str) -> Result<Duration> {
+  let (number, unit) = value.split_at(value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len()));
+  let number = number.parse::<u64>().context(format!("Invalid duration {value}; expected e.g. 60s, 5m or 1h"))?;
+  let seconds = match unit {
+    "" | "s" => number,
+    "m" => number * 60,
+    "h" => number * 3600,
+    _ => anyhow::bail!("Invalid duration unit in {value}; expected s, m or h"),
+  };
+  ensure!(seconds > 0, "The cache TTL must be positive");
+  Ok(Duration::from_secs(seconds))
+}
+
+pub fn clear(dir: &Path) -> Result<usize> {
+  let entries = match fs::read_dir(dir) {
+    Ok(entries) => entries,
+    Err(e) if e.kind() == ErrorKind::NotFound => return Ok(0),
+    Err(e) => return Err(e).context(format!("Failed to read {}", dir.display())),
+  };
+
+  let mut removed = 0;
+  for entry in entries {
+    let path = entry?.path();
+    if path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.ends_with(ENTRY_SUFFIX) || n == KEY_FILE) {
+      fs::remove_file(&path).context(format!("Failed to remove {}", path.display()))?;
+      removed += 1;
+    }
+  }
+  Ok(removed)
+}
+
+impl CachedStore {
+  pub fn new(inner: Box<dyn SecretStore>, options: CacheOptions) -> Result<Self> {
+    let identity = load_or_create_key(&options.dir.join(KEY_FILE))?;
+    Ok(Self { inner, options, identity })
+  }
+
+  fn path(&self, kind: &str, names: &[&str], pin: Option<&Pin>) -> PathBuf {
+    let pin = pin.map(|pin| pin.to_string()).unwrap_or_default();
+    let key = format!("{}\0{kind}\0{}\0{pin}", self.options.namespace, names.join("\0"));
+    self.options.dir.join(format!("{}{ENTRY_SUFFIX}", hex::encode(Sha256::digest(key))))
+  }
+
+  fn read<T: DeserializeOwned>(&self, path: &Path) -> Option<T> {
+    let ciphertext = fs::read(path).ok()?;
+    let identities: [Box<dyn Identity + Send + Sync>; 1] = [Box::new(self.identity.clone())];
+    let cached = crypto::decrypt(&identities, &ciphertext)
+      .and_then(|plaintext| Ok(serde_json::from_slice::<Cached<T>>(&plaintext)?))
+      .inspect_err(|err| tracing::debug!("Ignoring unreadable cache entry {}: {err:#}", path.display()))
+      .ok()?;
+
+    let age = now().saturating_sub(cached.created);
+    (age < self.options.ttl.as_secs()).then(|| {
+      tracing::debug!("Using cached entry {} ({age}s old)", path.display());
+      cached.value
+    })
+  }
+
+  fn write<T: Serialize>(&self, path: &Path, value: T) {
+    let result = serde_json::to_vec(&Cached { created: now(), value })
+      .map_err(anyhow::Error::from)
+      .and_then(|plaintext| crypto::encrypt(&[self.identity.to_public()], &plaintext))
+      .and_then(|ciphertext| write_atomic(path, &ciphertext));
+    if let Err(err) = result {
+      tracing::warn!("Failed to write cache entry {}: {err:#}", path.display());
+    }
+  }
+}
+
+#[async_trait]
+impl SecretStore for CachedStore {
+  async fn fetch_prefix(&self, prefix: &str, pin: Option<&Pin>) -> Result<Vec<Entry>> {
+    let path = self.path("prefix", &[prefix], pin);
+    if let Some(entries) = self.read(&path) {
+      return Ok(entries);
+    }
+
+    let entries = self.inner.fetch_prefix(prefix, pin).await?;
+    self.write(&path, &entries);
+    Ok(entries)
+  }
+
+  async fn fetch_names(&self, names: &[String], pin: Option<&Pin>) -> Result<(Vec<Entry>, Vec<String>)> {
+    let path = self.path("names", &names.iter().map(String::as_str).collect::<Vec<_>>(), pin);
+    if let Some(result) = self.read(&path) {
+      return Ok(result);
+    }
+
+    let result = self.inner.fetch_names(names, pin).await?;
+    self.write(&path, &result);
+    Ok(result)
+  }
+}
+
+fn load_or_create_key(path: &Path) -> Result<x25519::Identity> {
+  match fs::read_to_string(path) {
+    Ok(key) => x25519::Identity::from_str(key.trim()).map_err(|e| anyhow!("Invalid cache key {}: {e}", path.display())),
+    Err(e) if e.kind() == ErrorKind::NotFound => {
+      let identity = x25519::Identity::generate();
+      write_atomic(path, identity.to_string().expose_secret().as_bytes()).context(format!("Failed to write {}", path.display()))?;
+      Ok(identity)
+    },
+    Err(e) => Err(e).context(format!("Failed to read {}", path.display())),
+  }
+}
+
+fn now() -> u64 {
+  SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
@@ -0,0 +1,108 @@
+use std::{fmt, path::{Path, PathBuf}, sync::{Mutex, atomic::{AtomicI32, Ordering}}};
+
+use anyhow::Result;
+use tokio::sync::Notify;
+
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+pub const TERMINATED_EXIT_CODE: i32 = 143;
+
+static EXIT_CODE: AtomicI32 = AtomicI32::new(0);
+static TEMP_PATHS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+static NOTIFY: Notify = Notify::const_new();
+
+#[derive(Debug, Clone, Copy)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Cancelled; in-flight work was finished and nothing new was started")
+  }
+}
+
+impl std::error::Error for Cancelled {}
+
+pub fn install() {
+  tokio::spawn(async {
+    loop {
+      let code = wait_for_signal().await;
+      if EXIT_CODE.swap(code, Ordering::SeqCst) != 0 {
+        remove_temp_paths();
+        std::process::exit(code);
+      }
+      NOTIFY.notify_waiters();
+      tracing::warn!("Cancelling: finishing in-flight work; press Ctrl-C again to exit now");
+    }
+  });
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() -> i32 {
+  use tokio::signal::unix::{SignalKind, signal};
+
+  let mut terminate = signal(SignalKind::terminate()).expect("SIGTERM handler can be installed");
+  tokio::select! {
+    _ = tokio::signal::ctrl_c() => INTERRUPTED_EXIT_CODE,
+    _ = terminate.recv() => TERMINATED_EXIT_CODE,
+  }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() -> i32 {
+  let _ = tokio::signal::ctrl_c().await;
+  INTERRUPTED_EXIT_CODE
+}
+
+#[cfg(unix)]
+pub fn forward_termination(pid: u32) {
+  use nix::sys::signal::{Signal, kill};
+  use nix::unistd::Pid;
+
+  if EXIT_CODE.load(Ordering::SeqCst) == TERMINATED_EXIT_CODE {
+    let _ = kill(Pid::from_raw(pid as i32), Signal::SIGTERM);
+  }
+}
+
+#[cfg(not(unix))]
+pub fn forward_termination(_pid: u32) {}
+
+pub fn is_cancelled() -> bool {
+  EXIT_CODE.load(Ordering::SeqCst) != 0
+}
+
+pub fn exit_code() -> Option<i32> {
+  Some(EXIT_CODE.load(Ordering::SeqCst)).filter(|code| *code != 0)
+}
+
+pub async fn cancelled() {
+  let notified = NOTIFY.notified();
+  if !is_cancelled() {
+    notified.await;
+  }
+}
+
+pub fn check() -> Result<()> {
+  if is_cancelled() {
+    return Err(Cancelled.into());
+  }
+  Ok(())
+}
+
+#[derive(Debug)]
+pub struct TempPath(PathBuf);
+
+pub fn track_temp(path: &Path) -> TempPath {
+  TEMP_PATHS.lock().unwrap().push(path.to_path_buf());
+  TempPath(path.to_path_buf())
+}
+
+impl Drop for TempPath {
+  fn drop(&mut self) {
+    TEMP_PATHS.lock().unwrap().retain(|path| *path != self.0);
+  }
+}
+
+pub fn remove_temp_paths() {
+  for path in TEMP_PATHS.lock().unwrap().drain(..) {
+    let _ = if path.is_dir() { std::fs::remove_dir_all(&path) } else { std::fs::remove_file(&path) };
+  }
+}
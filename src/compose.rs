@@ -1,22 +1,50 @@
-use std::{collections::HashMap, io::Write};
+use std::{collections::HashMap, fs, io::{ErrorKind, Write}, process::{Command, ExitStatus, Stdio}};
 
-use anyhow::Result;
-use futures::{future::try_join_all, TryStreamExt};
+use anyhow::{Context, Result, ensure};
+use clap::ValueEnum;
+use futures::future::try_join_all;
 use serde::{Serialize, Deserialize};
 use itertools::Itertools;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
 use tempfile::NamedTempFile;
 
-use crate::ssm;
+use crate::cancel;
+use crate::config;
+use crate::download::{PRIVATE_MODE, set_mode, write_atomic, write_atomic_with_mode};
+use crate::output::Redacted;
+use crate::store::SecretStore;
+
+const SWARM_VERSION_LENGTH: usize = 12;
+const SWARM_KEY_FILE: &str = "swarm.key";
+const DEFAULT_PATH_TEMPLATE: &str = "/apps/{namespace}/{service}/secrets/{name}";
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ComposeFile {
   pub services: HashMap<String, Service>,
   pub secrets: Option<HashMap<String, SecretDefinition>>,
+  #[serde(rename = "x-ops", default, skip_serializing_if = "Option::is_none")]
+  pub x_ops: Option<OpsExtension>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Service {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
   pub secrets: Option<Vec<ServiceSecret>>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub environment: Option<serde_yaml::Value>,
+  #[serde(rename = "x-ops", default, skip_serializing_if = "Option::is_none")]
+  pub x_ops: Option<OpsExtension>,
+  #[serde(rename = "x-ops-env", default, skip_serializing_if = "HashMap::is_empty")]
+  pub x_ops_env: HashMap<String, String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct OpsExtension {
+  pub path_template: Option<String>,
+  #[serde(default)]
+  pub secrets: HashMap<String, String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -40,84 +68,324 @@ pub struct ServiceSecretDetail {
 pub enum SecretDefinition {
   File { file: String },
   Environment { environment: String },
+  Named { name: String, external: bool },
   External { external: Option<bool> },
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum SecretDelivery {
+  #[default]
+  Env,
+  Files,
+  Swarm,
+  References,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum Runtime {
+  #[default]
+  Docker,
+  Podman,
+  Nerdctl,
+}
+
+impl Runtime {
+  fn compose_command(self) -> Command {
+    let (program, subcommand) = match self {
+      Runtime::Docker => ("docker", Some("compose")),
+      Runtime::Podman => ("podman-compose", None),
+      Runtime::Nerdctl => ("nerdctl", Some("compose")),
+    };
+    let mut command = Command::new(program);
+    command.args(subcommand);
+    command
+  }
+
+  fn supports_environment_secrets(self) -> bool {
+    self == Runtime::Docker
+  }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ComposeOptions {
+  pub runtime: Runtime,
+  pub delivery: SecretDelivery,
+  pub path_template: Option<String>,
+  pub allow_missing: bool,
+  pub profiles: Vec<String>,
+  pub project_name: Option<String>,
+}
+
 fn parse(path: &str) -> Result<ComposeFile> {
-  let yaml = std::fs::read_to_string(path)?;
-  let compose: ComposeFile = serde_yaml::from_str(&yaml)?;
+  let yaml = std::fs::read_to_string(path).context(format!("Failed to read {path}"))?;
+  let compose: ComposeFile = serde_yaml::from_str(&yaml).context(format!("Failed to parse {path}"))?;
   Ok(compose)
 }
 
-pub async fn exec_compose(client: &ssm::Client, path: &str, namespace: &str, args: Vec<String>) -> Result<()> {
-  let compose = parse(path)?;
+fn parse_all(paths: &[String]) -> Result<ComposeFile> {
+  let mut merged = ComposeFile { services: HashMap::new(), secrets: None, x_ops: None };
+  for path in paths {
+    let compose = parse(path)?;
+    for (name, service) in compose.services {
+      let entry = merged.services.entry(name).or_default();
+      entry.secrets.get_or_insert_with(Vec::new).extend(service.secrets.unwrap_or_default());
+      entry.x_ops_env.extend(service.x_ops_env);
+      merge_ops(&mut entry.x_ops, service.x_ops);
+    }
+    merge_ops(&mut merged.x_ops, compose.x_ops);
+  }
+  Ok(merged)
+}
+
+fn merge_ops(into: &mut Option<OpsExtension>, from: Option<OpsExtension>) {
+  let Some(from) = from else { return };
+  let into = into.get_or_insert_with(OpsExtension::default);
+  if from.path_template.is_some() {
+    into.path_template = from.path_template;
+  }
+  into.secrets.extend(from.secrets);
+}
+
+pub async fn exec_compose(store: &dyn SecretStore, files: &[String], namespace: &str, args: Vec<String>, mut options: ComposeOptions) -> Result<ExitStatus> {
+  ensure!(options.delivery != SecretDelivery::References, "Compose runtimes can't resolve parameter references themselves; use `ops ecs patch-taskdef` for ECS task definitions or `ops k8s secret --format external-secrets` for Kubernetes");
+  let compose = parse_all(files)?;
+
+  let env_refs = compose
+    .services
+    .iter()
+    .flat_map(|(service_name, service)| service.x_ops_env.iter().map(move |(var, path)| (service_name.clone(), var.clone(), path.clone())))
+    .collect::<Vec<_>>();
+
+  let file_ops = compose.x_ops.unwrap_or_default();
+  let default_template = options.path_template.as_deref().or(file_ops.path_template.as_deref()).unwrap_or(DEFAULT_PATH_TEMPLATE);
 
   let secret_names = compose
     .services
     .into_iter()
-    .flat_map(|(service_name, service)|
+    .flat_map(|(service_name, service)| {
+      let service_ops = service.x_ops.unwrap_or_default();
+      let file_ops = &file_ops;
       service.secrets.unwrap_or(vec![]).into_iter().map(move |secret| {
         let secret_name = match secret {
           ServiceSecret::NameOnly(name) => name,
           ServiceSecret::Detailed(detail) => detail.source,
         };
 
-        format!("/apps/{namespace}/{service_name}/secrets/{secret_name}")
+        let path = match service_ops.secrets.get(&secret_name).or(file_ops.secrets.get(&secret_name)) {
+          Some(path) => path.clone(),
+          None => {
+            let template = service_ops.path_template.as_deref().unwrap_or(default_template);
+            render_path(template, namespace, &service_name, &secret_name)
+          },
+        };
+        ensure!(path.starts_with('/'), "SSM path {path} for secret {secret_name} must start with '/'");
+        Ok((secret_name, path))
       })
-    )
-    .collect::<Vec<_>>();
+    })
+    .collect::<Result<Vec<_>>>()?;
 
-  let paths = secret_names.iter().into_group_map_by(|n|n.rsplit_once('/').map(|(p, _)|p).unwrap_or(n).to_owned());
+  let paths = secret_names.iter().map(|(_, n)| n).into_group_map_by(|n|n.rsplit_once('/').map(|(p, _)|p).unwrap_or(n).to_owned());
 
   let path_secrets = try_join_all(
     paths
       .keys()
       .map(|p| async move {
-        dbg!(&p);
-        let params = ssm::all_parameters_by_path(client, p).try_collect::<Vec<_>>().await?.into_iter().flatten().collect::<Vec<_>>();
-        anyhow::Ok(params.into_iter().map(|p| (p.name().expect("missing name").to_string(), p.value().unwrap_or("").to_string())).collect::<Vec<_>>())
+        tracing::debug!("Fetching secrets under {p}");
+        let entries = store.fetch_prefix(p, None).await?;
+        anyhow::Ok(entries.into_iter().map(|e| (format!("{p}/{}", e.name), String::from_utf8_lossy(&e.value).into_owned())).collect::<Vec<_>>())
       })
   )
     .await?.into_iter().flatten().collect::<HashMap<_,_>>();
 
+  let env_paths = env_refs.iter().map(|(_, _, path)| path.clone()).unique().collect::<Vec<_>>();
+  let (env_entries, _) = store.fetch_names(&env_paths, None).await.context("Failed to fetch x-ops-env parameters")?;
+  let env_values = env_entries.into_iter().map(|e| (e.name, String::from_utf8_lossy(&e.value).into_owned())).collect::<HashMap<_, _>>();
+
+  let missing = secret_names
+    .iter()
+    .map(|(_, path)| path)
+    .filter(|path| !path_secrets.contains_key(*path))
+    .chain(env_paths.iter().filter(|path| !env_values.contains_key(*path)))
+    .unique()
+    .collect::<Vec<_>>();
+  if !missing.is_empty() {
+    let list = missing.iter().map(|path| format!("  {path}")).join("\n");
+    ensure!(options.allow_missing, "Missing {} secret(s) in SSM:\n{list}", missing.len());
+    tracing::warn!("Missing {} secret(s) in SSM:\n{list}", missing.len());
+  }
+
+  if options.delivery == SecretDelivery::Env && !options.runtime.supports_environment_secrets() {
+    tracing::info!("{:?} doesn't support environment secrets, delivering them as files", options.runtime);
+    options.delivery = SecretDelivery::Files;
+  }
+
+  let swarm_key = match options.delivery {
+    SecretDelivery::Swarm => {
+      ensure!(options.runtime == Runtime::Docker, "Swarm mode requires the docker runtime");
+      ensure!(args.first().is_some_and(|a| a == "deploy"), "Swarm mode only supports `deploy`, e.g. `ops compose --swarm deploy <stack>`");
+      swarm_key()?
+    },
+    _ => vec![],
+  };
+
+  let secrets_dir = match options.delivery {
+    SecretDelivery::Files => Some(tempfile::Builder::new().prefix("ops-secrets-").tempdir().context("Failed to create secrets directory")?),
+    SecretDelivery::Env | SecretDelivery::Swarm | SecretDelivery::References => None,
+  };
+  let _tracked_dir = secrets_dir.as_ref().map(|dir| cancel::track_temp(dir.path()));
+
+  let mut envs = vec![];
+  let mut services = HashMap::<String, Service>::new();
+  for (service_name, var, path) in &env_refs {
+    let Some(value) = env_values.get(path) else { continue };
+    let host_var = host_env_name(service_name, var);
+    let environment = services.entry(service_name.clone()).or_default().environment.get_or_insert_with(|| serde_yaml::Value::Mapping(Default::default()));
+    if let serde_yaml::Value::Mapping(mapping) = environment {
+      mapping.insert(var.clone().into(), format!("${{{host_var}}}").into());
+    }
+    envs.push((host_var, value.clone()));
+  }
+
   let secrets = ComposeFile{
-    services: [].into(),
+    services,
+    x_ops: None,
     secrets: Some(
       secret_names
         .iter()
-        .map(|name| {
-          let secret_name = name.rsplit_once("/").map(|(_, name)|name).unwrap_or(name).to_owned();
-          let environment = name.replace('/', "_").to_uppercase();
-          (secret_name, SecretDefinition::Environment { environment })
+        .map(|(secret_name, name)| {
+          let secret_name = secret_name.clone();
+          let value = path_secrets.get(name).map(String::as_str).unwrap_or_default();
+          let definition = match (options.delivery, &secrets_dir) {
+            (SecretDelivery::Swarm, _) => {
+              let versioned = swarm_secret_name(&secret_name, value, &swarm_key)?;
+              ensure_swarm_secret(&versioned, value)?;
+              SecretDefinition::Named { name: versioned, external: true }
+            },
+            (_, Some(dir)) => {
+              let file = dir.path().join(&secret_name);
+              write_atomic_with_mode(&file, value.as_bytes(), PRIVATE_MODE).context(format!("Failed to write secret {secret_name}"))?;
+              SecretDefinition::File { file: file.display().to_string() }
+            },
+            (_, None) => SecretDefinition::Environment { environment: name.replace('/', "_").to_uppercase() },
+          };
+          anyhow::Ok((secret_name, definition))
         })
-        .collect(),
+        .collect::<Result<_>>()?,
     ),
   };
 
-  let envs = path_secrets.iter().map(|(name, value)| {
-    let env_name = name.replace('/', "_").to_uppercase();
-    (env_name, value.clone())
-  }).collect::<Vec<_>>();
+  if options.delivery == SecretDelivery::Env {
+    envs.extend(path_secrets.iter().map(|(name, value)| {
+      let env_name = name.replace('/', "_").to_uppercase();
+      (env_name, value.clone())
+    }));
+  }
 
-  println!("{}", serde_yaml::to_string(&secrets)?);
+  for (name, value) in &envs {
+    tracing::debug!("Passing {name}={}", Redacted(value));
+  }
+  tracing::debug!("Rendered secrets override:\n{}", serde_yaml::to_string(&secrets)?);
   let compose_file = write_compose_to_temp_file(&secrets)?;
-  dbg!(&compose_file.path());
-
-  std::process::Command::new("docker")
-    .envs(envs)
-    .arg("compose")
-    .arg("-f")
-    .arg(path)
-    .arg("-f")
-    .arg(compose_file.path())
-    .args(args)
-    .status()?;
+  let _tracked_file = cancel::track_temp(compose_file.path());
+  tracing::debug!("Wrote secrets override to {}", compose_file.path().display());
+
+  let mut command = match options.delivery {
+    SecretDelivery::Swarm => Command::new("docker"),
+    _ => options.runtime.compose_command(),
+  };
+  command.envs(envs);
+  match options.delivery {
+    SecretDelivery::Swarm => {
+      command.args(["stack", "deploy"]);
+      files.iter().for_each(|file| { command.args(["-c", file]); });
+      command.arg("-c").arg(compose_file.path()).args(&args[1..])
+    },
+    _ => {
+      files.iter().for_each(|file| { command.args(["-f", file]); });
+      command.arg("-f").arg(compose_file.path());
+      options.profiles.iter().for_each(|profile| { command.args(["--profile", profile]); });
+      command.args(options.project_name.iter().flat_map(|name| ["--project-name", name])).args(args)
+    },
+  };
+  let program = command.get_program().to_string_lossy().into_owned();
+  let mut child = tokio::process::Command::from(command).spawn().context(format!("Failed to run {program}"))?;
+  let mut forwarded = false;
+  let status = loop {
+    tokio::select! {
+      status = child.wait() => break status.context(format!("Failed to wait for {program}"))?,
+      _ = cancel::cancelled(), if !forwarded => {
+        forwarded = true;
+        if let Some(pid) = child.id() {
+          cancel::forward_termination(pid);
+        }
+        cancel::remove_temp_paths();
+      },
+    }
+  };
+
+  if let Some(dir) = secrets_dir.filter(|_| !forwarded) {
+    dir.close().context("Failed to remove secrets directory")?;
+  }
+
+  Ok(status)
+}
+
+fn host_env_name(service: &str, var: &str) -> String {
+  format!("OPS_ENV_{service}_{var}").chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' }).collect()
+}
+
+fn render_path(template: &str, namespace: &str, service: &str, name: &str) -> String {
+  template.replace("{namespace}", namespace).replace("{service}", service).replace("{name}", name)
+}
+
+fn swarm_secret_name(secret_name: &str, value: &str, key: &[u8]) -> Result<String> {
+  let mut mac = Hmac::<Sha256>::new_from_slice(key).context("Invalid swarm secret key")?;
+  mac.update(value.as_bytes());
+  let hash = hex::encode(mac.finalize().into_bytes());
+  Ok(format!("{secret_name}_{}", &hash[..SWARM_VERSION_LENGTH]))
+}
+
+fn swarm_key() -> Result<Vec<u8>> {
+  let path = config::config_dir().context("Can't find a config directory for the swarm secret key; set XDG_CONFIG_HOME")?.join(SWARM_KEY_FILE);
+  match fs::read(&path) {
+    Ok(key) => return Ok(key),
+    Err(err) if err.kind() == ErrorKind::NotFound => {},
+    Err(err) => return Err(err).context(format!("Failed to read {}", path.display())),
+  }
+  tracing::info!("Creating the swarm secret key {}", path.display());
+  write_atomic(&path, &rand::random::<[u8; 32]>()).context(format!("Failed to write {}", path.display()))?;
+  fs::read(&path).context(format!("Failed to read {}", path.display()))
+}
+
+fn ensure_swarm_secret(name: &str, value: &str) -> Result<()> {
+  let exists = Command::new("docker")
+    .args(["secret", "inspect", name])
+    .stdout(Stdio::null())
+    .stderr(Stdio::null())
+    .status()
+    .context("Failed to run docker secret inspect")?
+    .success();
+  if exists {
+    tracing::debug!("Swarm secret {name} is up to date");
+    return Ok(());
+  }
+
+  let mut child = Command::new("docker")
+    .args(["secret", "create", name, "-"])
+    .stdin(Stdio::piped())
+    .stdout(Stdio::null())
+    .spawn()
+    .context("Failed to run docker secret create")?;
+  child.stdin.take().expect("stdin is piped").write_all(value.as_bytes())?;
+  ensure!(child.wait()?.success(), "Failed to create swarm secret {name}");
 
+  tracing::info!("Created swarm secret {name}");
   Ok(())
 }
 
 fn write_compose_to_temp_file(compose: &ComposeFile) -> Result<NamedTempFile> {
   let mut file = NamedTempFile::new()?;
+  set_mode(file.as_file(), PRIVATE_MODE)?;
 
   serde_yaml::to_writer(&file, compose)?;
   file.flush()?;
@@ -0,0 +1,39 @@
+use std::io::{Read, Write};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use flate2::{Compression as GzipLevel, read::GzDecoder, write::GzEncoder};
+use serde::{Deserialize, Serialize};
+
+const ZSTD_LEVEL: i32 = 19;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+  Zstd,
+  Gzip,
+}
+
+impl Compression {
+  pub fn compress(self, content: &[u8]) -> Result<Vec<u8>> {
+    match self {
+      Compression::Zstd => Ok(zstd::encode_all(content, ZSTD_LEVEL)?),
+      Compression::Gzip => {
+        let mut encoder = GzEncoder::new(vec![], GzipLevel::best());
+        encoder.write_all(content)?;
+        Ok(encoder.finish()?)
+      },
+    }
+  }
+
+  pub fn decompress(self, content: &[u8]) -> Result<Vec<u8>> {
+    match self {
+      Compression::Zstd => Ok(zstd::decode_all(content)?),
+      Compression::Gzip => {
+        let mut output = vec![];
+        GzDecoder::new(content).read_to_end(&mut output)?;
+        Ok(output)
+      },
+    }
+  }
+}
@@ -0,0 +1,85 @@
+use std::{collections::HashMap, env, fs, path::PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+const LOCAL_CONFIG: &str = "ops.toml";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+  #[serde(default)]
+  pub profiles: HashMap<String, Profile>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+  pub region: Option<String>,
+  pub aws_profile: Option<String>,
+  pub role_arn: Option<String>,
+  pub external_id: Option<String>,
+  pub mfa_serial: Option<String>,
+  pub endpoint_url: Option<String>,
+  pub max_tps: Option<f64>,
+  pub user_agent_suffix: Option<String>,
+  pub backend: Option<String>,
+  pub bucket: Option<String>,
+  pub root: Option<PathBuf>,
+  pub prefix: Option<String>,
+  pub path_template: Option<String>,
+  pub kms_key_id: Option<String>,
+  pub concurrency: Option<usize>,
+  pub namespace: Option<String>,
+}
+
+impl Profile {
+  pub fn defaults(&self) -> Vec<(&'static str, String)> {
+    [
+      ("region", self.region.clone()),
+      ("profile", self.aws_profile.clone()),
+      ("role_arn", self.role_arn.clone()),
+      ("external_id", self.external_id.clone()),
+      ("mfa_serial", self.mfa_serial.clone()),
+      ("endpoint_url", self.endpoint_url.clone()),
+      ("max_tps", self.max_tps.map(|tps| tps.to_string())),
+      ("user_agent_suffix", self.user_agent_suffix.clone()),
+      ("backend", self.backend.clone()),
+      ("bucket", self.bucket.clone()),
+      ("root", self.root.as_ref().map(|root| root.display().to_string())),
+      ("prefix", self.prefix.clone()),
+      ("base", self.prefix.clone()),
+      ("path_template", self.path_template.clone()),
+      ("kms_key_id", self.kms_key_id.clone()),
+      ("concurrency", self.concurrency.map(|c| c.to_string())),
+      ("namespace", self.namespace.clone()),
+    ]
+    .into_iter()
+    .filter_map(|(id, value)| Some((id, value?)))
+    .collect()
+  }
+}
+
+pub fn config_dir() -> Option<PathBuf> {
+  let config_home = env::var_os("XDG_CONFIG_HOME").map(PathBuf::from).or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+  config_home.map(|dir| dir.join("ops"))
+}
+
+pub fn config_paths() -> Vec<PathBuf> {
+  [Some(PathBuf::from(LOCAL_CONFIG)), config_dir().map(|dir| dir.join("config.toml"))].into_iter().flatten().collect()
+}
+
+pub fn load_profile(name: &str) -> Result<Profile> {
+  for path in config_paths() {
+    if !path.is_file() {
+      continue;
+    }
+    let text = fs::read_to_string(&path).context(format!("Failed to read {}", path.display()))?;
+    let mut config: Config = toml::from_str(&text).context(format!("Failed to parse {}", path.display()))?;
+    if let Some(profile) = config.profiles.remove(name) {
+      tracing::debug!("Using profile {name} from {}", path.display());
+      return Ok(profile);
+    }
+  }
+
+  anyhow::bail!("Profile {name} not found in {}", config_paths().iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(" or "))
+}
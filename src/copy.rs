@@ -0,0 +1,138 @@
+use std::{collections::{HashMap, HashSet}, path::Path};
+
+use anyhow::{Context, Result, bail};
+use aws_sdk_ssm::types::{ParameterStringFilter, ParameterType, Tag};
+use futures::TryStreamExt;
+
+use crate::cancel;
+use crate::files::{S3_SUFFIX, is_selected, logical_name, matching_names, within_depth};
+use crate::filter::PathFilter;
+use crate::output::Event;
+use crate::progress::Progress;
+use crate::prompt::confirm;
+use crate::s3::{self, S3Target};
+use crate::ssm::{self, Client};
+use crate::tags::{add_tags, list_tags, with_change_reason};
+
+pub const DEFAULT_KMS_KEY: &str = "alias/aws/ssm";
+
+#[derive(Debug, Clone, Default)]
+pub struct CopyOptions {
+  pub r#type: Option<ParameterType>,
+  pub kms_key_id: Option<String>,
+  pub tags: Vec<Tag>,
+  pub preserve_tags: bool,
+  pub filter: PathFilter,
+  pub filters: Vec<ParameterStringFilter>,
+  pub max_depth: Option<usize>,
+  pub s3: Option<S3Target>,
+  pub cross_region_or_account: bool,
+  pub yes: bool,
+  pub dry_run: bool,
+}
+
+pub fn destination(name: &str, prefix: &str, to_prefix: &str) -> Result<String> {
+  let rest = name.strip_prefix(prefix.trim_end_matches('/')).filter(|rest| rest.starts_with('/')).context(format!("{name} is not under {prefix}"))?;
+  Ok(format!("{}{rest}", to_prefix.trim_end_matches('/')))
+}
+
+pub async fn copy(client: &Client, to_client: &Client, prefix: String, to_prefix: String, options: CopyOptions) -> Result<()> {
+  let CopyOptions { r#type, kms_key_id, tags, preserve_tags, filter, filters, max_depth, s3, cross_region_or_account, yes, dry_run } = options;
+  ssm::ensure_not_root(&prefix)?;
+  ssm::ensure_not_root(&to_prefix)?;
+  let selected = matching_names(client, &prefix, &filters).await?;
+  let params = ssm::all_parameters_by_path(client, &prefix, max_depth != Some(1)).try_collect::<Vec<_>>().await?;
+  let metadata = ssm::all_parameter_metadata_by_path(client, &prefix)
+    .try_collect::<Vec<_>>()
+    .await?
+    .into_iter()
+    .flatten()
+    .filter_map(|m| Some((m.name.clone()?, m)))
+    .collect::<HashMap<_, _>>();
+
+  let params = params
+    .into_iter()
+    .flatten()
+    .filter(|p| p.name().is_some_and(|name| filter.matches(Path::new(logical_name(name))) && is_selected(&selected, name) && within_depth(&prefix, name, max_depth)))
+    .collect::<Vec<_>>();
+
+  if !dry_run && !yes && !params.is_empty() {
+    let existing = ssm::all_parameter_metadata_by_path(to_client, &to_prefix).try_collect::<Vec<_>>().await?.into_iter().flatten().filter_map(|m| m.name).collect::<HashSet<_>>();
+    let destinations = params.iter().filter_map(|p| p.name()).map(|name| destination(name, &prefix, &to_prefix)).collect::<Result<Vec<_>>>()?;
+    let overwritten = destinations.iter().filter(|name| existing.contains(*name)).count();
+    if !confirm(&format!("Copy {} parameters from {prefix} to {to_prefix}, overwriting {overwritten}?", params.len()))? {
+      bail!("Aborted");
+    }
+  }
+
+  let progress = (!dry_run).then(|| Progress::new("copy", params.len()));
+
+  for param in &params {
+    cancel::check()?;
+    let name = param.name().unwrap();
+    let value = param.value().unwrap();
+
+    let new_name = destination(name, &prefix, &to_prefix)?;
+    let r#type = r#type.clone().unwrap_or_else(|| param.r#type().unwrap().clone());
+    let meta = metadata.get(name);
+    let key_id = match r#type {
+      ParameterType::SecureString => kms_key_id.clone().or_else(|| meta.and_then(|m| m.key_id()).filter(|k| *k != DEFAULT_KMS_KEY && !cross_region_or_account).map(str::to_string)),
+      _ => None,
+    };
+
+    let Some(progress) = &progress else {
+      Event::new("would-copy", name)
+        .with("to", new_name.as_str())
+        .with("bytes", value.len())
+        .with("type", r#type.as_str())
+        .emit(format!("Would copy {name} to {new_name} ({} bytes, {})", value.len(), r#type.as_str()));
+      continue;
+    };
+
+    progress.start(name);
+    let value = match new_name.strip_suffix(S3_SUFFIX) {
+      Some(new_logical) => {
+        let target = s3.as_ref().context(format!("{name} is stored in S3; use --backend s3 to copy it"))?;
+        progress.api_calls(1);
+        s3::copy_object(target, value, &s3::object_key(new_logical), kms_key_id.as_deref()).await?
+      },
+      None => value.to_string(),
+    };
+    ssm::ensure_writable("ssm:PutParameter", &new_name)?;
+    let resp = ssm::retry_throttled(|| {
+      to_client
+        .put_parameter()
+        .name(&new_name)
+        .value(&value)
+        .overwrite(true)
+        .r#type(r#type.clone())
+        .set_key_id(key_id.clone())
+        .set_description(meta.and_then(|m| m.description()).map(str::to_string))
+        .set_tier(meta.and_then(|m| m.tier()).cloned())
+        .set_allowed_pattern(meta.and_then(|m| m.allowed_pattern()).map(str::to_string))
+        .set_data_type(meta.and_then(|m| m.data_type()).map(str::to_string))
+        .send()
+    })
+    .await
+    .context(format!("Failed to copy {name} to {new_name}"))?;
+
+    let mut new_tags = if preserve_tags { list_tags(client, name).await? } else { vec![] };
+    new_tags.extend(tags.iter().cloned());
+    let new_tags = with_change_reason(&new_tags);
+    add_tags(to_client, &new_name, &new_tags).await?;
+
+    progress.api_calls(1 + usize::from(preserve_tags) + usize::from(!new_tags.is_empty()));
+    progress.transferred(1, value.len());
+    progress.emit(
+      Event::new("copy", name).with("to", new_name.as_str()).with("version", resp.version()),
+      format!("Copied {name} to {new_name} (v{})", resp.version()),
+    );
+    progress.file_done();
+  }
+
+  if let Some(progress) = progress {
+    progress.finish();
+  }
+
+  Ok(())
+}
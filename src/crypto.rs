@@ -0,0 +1,92 @@
+use std::{io::{Read, Write}, path::Path, sync::OnceLock};
+
+use age::{Decryptor, Encryptor, Identity, IdentityFile, Recipient, armor::{ArmoredReader, ArmoredWriter, Format}, x25519};
+use anyhow::{Context, Result, anyhow, bail, ensure};
+use serde::{Deserialize, Serialize};
+
+static IDENTITIES: OnceLock<Vec<Box<dyn Identity + Send + Sync>>> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encryption {
+  Age,
+}
+
+impl Encryption {
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Encryption::Age => "age",
+    }
+  }
+
+  pub fn parse(value: &str) -> Option<Self> {
+    match value {
+      "age" => Some(Encryption::Age),
+      _ => None,
+    }
+  }
+}
+
+pub fn set_identities(path: &Path) -> Result<()> {
+  let identities = load_identities(path)?;
+  IDENTITIES.set(identities).map_err(|_| anyhow!("Identities are already loaded"))
+}
+
+pub fn parse_encrypt_with(value: &str) -> Result<x25519::Recipient> {
+  match value.split_once(':') {
+    Some(("age", recipient)) => parse_recipient(recipient),
+    _ => bail!("Invalid --encrypt-with {value}; expected age:<recipient>"),
+  }
+}
+
+pub fn decrypt_as(encryption: Option<Encryption>, content: Vec<u8>) -> Result<Vec<u8>> {
+  match encryption {
+    None => Ok(content),
+    Some(Encryption::Age) => {
+      let identities = IDENTITIES.get().context("Value is age-encrypted; pass --age-identity to decrypt it")?;
+      decrypt(identities, &content)
+    },
+  }
+}
+
+pub fn parse_recipient(value: &str) -> Result<x25519::Recipient> {
+  value.parse().map_err(|e| anyhow!("Invalid age recipient {value}: {e}"))
+}
+
+pub fn load_identities(path: &Path) -> Result<Vec<Box<dyn Identity + Send + Sync>>> {
+  let file = IdentityFile::from_file(path.display().to_string()).context(format!("Failed to read identity file {}", path.display()))?;
+  let identities = file.into_identities().context(format!("Invalid identity file {}", path.display()))?;
+  ensure!(!identities.is_empty(), "No identities found in {}", path.display());
+  Ok(identities)
+}
+
+pub fn encrypt(recipients: &[x25519::Recipient], plaintext: &[u8]) -> Result<Vec<u8>> {
+  ensure!(!recipients.is_empty(), "At least one recipient is required to encrypt");
+  let encryptor = Encryptor::with_recipients(recipients.iter().map(|r| r as &dyn Recipient))?;
+
+  let mut ciphertext = Vec::with_capacity(plaintext.len());
+  let mut writer = encryptor.wrap_output(&mut ciphertext)?;
+  writer.write_all(plaintext)?;
+  writer.finish()?;
+  Ok(ciphertext)
+}
+
+pub fn encrypt_armored(recipients: &[x25519::Recipient], plaintext: &[u8]) -> Result<Vec<u8>> {
+  ensure!(!recipients.is_empty(), "At least one recipient is required to encrypt");
+  let encryptor = Encryptor::with_recipients(recipients.iter().map(|r| r as &dyn Recipient))?;
+
+  let mut ciphertext = Vec::with_capacity(plaintext.len() * 2);
+  let mut writer = encryptor.wrap_output(ArmoredWriter::wrap_output(&mut ciphertext, Format::AsciiArmor)?)?;
+  writer.write_all(plaintext)?;
+  writer.finish()?.finish()?;
+  Ok(ciphertext)
+}
+
+pub fn decrypt(identities: &[Box<dyn Identity + Send + Sync>], ciphertext: &[u8]) -> Result<Vec<u8>> {
+  let decryptor = Decryptor::new_buffered(ArmoredReader::new(ciphertext)).context("Not an age-encrypted file")?;
+  let mut reader = decryptor.decrypt(identities.iter().map(|i| i.as_ref() as &dyn Identity)).context("Failed to decrypt")?;
+
+  let mut plaintext = vec![];
+  reader.read_to_end(&mut plaintext)?;
+  Ok(plaintext)
+}
@@ -0,0 +1,51 @@
+use anyhow::{Context, Result, bail};
+use aws_sdk_ssm::types::ParameterStringFilter;
+use futures::TryStreamExt;
+
+use crate::cancel;
+use crate::files::{is_selected, matching_names, within_depth};
+use crate::output::Event;
+use crate::prompt::confirm;
+use crate::ssm::{self, Client};
+
+pub const DELETE_BATCH_SIZE: usize = 10;
+
+pub async fn delete(client: &Client, prefix: String, filters: Vec<ParameterStringFilter>, max_depth: Option<usize>, yes: bool, dry_run: bool) -> Result<()> {
+  ssm::ensure_not_root(&prefix)?;
+  let selected = matching_names(client, &prefix, &filters).await?;
+  let params = ssm::all_parameters_by_path(client, &prefix, max_depth != Some(1)).try_collect::<Vec<_>>().await?;
+  let names = params.into_iter().flatten().filter_map(|p| p.name).filter(|name| is_selected(&selected, name) && within_depth(&prefix, name, max_depth)).collect::<Vec<_>>();
+
+  if names.is_empty() {
+    tracing::info!("Nothing to delete under {prefix}");
+    return Ok(());
+  }
+
+  if !dry_run && !yes && !confirm(&format!("Delete {} parameters under {prefix}?", names.len()))? {
+    bail!("Aborted");
+  }
+
+  delete_names(client, &names, dry_run).await.context(format!("Failed to delete parameters under {prefix}"))
+}
+
+pub async fn delete_names(client: &Client, names: &[String], dry_run: bool) -> Result<()> {
+  for batch in names.chunks(DELETE_BATCH_SIZE) {
+    cancel::check()?;
+    if dry_run {
+      batch.iter().for_each(|name| Event::new("would-delete", name).emit(format!("Would delete {name}")));
+      continue;
+    }
+
+    ssm::ensure_writable("ssm:DeleteParameters", &batch.join(", "))?;
+    let resp = client
+      .delete_parameters()
+      .set_names(Some(batch.to_vec()))
+      .send()
+      .await?;
+
+    resp.deleted_parameters().iter().for_each(|name| Event::new("delete", name).emit(format!("Deleted {name}")));
+    resp.invalid_parameters().iter().for_each(|name| tracing::warn!("Could not delete {name}"));
+  }
+
+  Ok(())
+}
@@ -0,0 +1,44 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use anyhow::Result;
+use itertools::Itertools;
+use similar::TextDiff;
+
+use crate::files::fetch_files;
+use crate::output::Event;
+use crate::ssm::Client;
+use crate::upload::{UploadOptions, is_skipped, read_local_tree};
+
+pub async fn diff(client: &Client, s3: Option<&aws_sdk_s3::Client>, prefix: String, to_prefix: Option<String>, dir: Option<PathBuf>, options: UploadOptions, show_values: bool) -> Result<()> {
+  let mut from = fetch_files(client, s3, &prefix).await?;
+  let to = match (to_prefix, dir) {
+    (Some(to_prefix), _) => fetch_files(client, s3, &to_prefix).await?,
+    (_, Some(dir)) => {
+      let (local, skipped) = read_local_tree(&dir, &prefix, &options).await?;
+      from.retain(|key, _| !is_skipped(key, &skipped));
+      local
+    },
+    _ => HashMap::new(),
+  };
+
+  for key in from.keys().chain(to.keys()).unique().sorted() {
+    match (from.get(key), to.get(key)) {
+      (Some(_), None) => Event::new("removed", key).emit(format!("- {key}")),
+      (None, Some(_)) => Event::new("added", key).emit(format!("+ {key}")),
+      (Some(a), Some(b)) if a != b => {
+        let mut event = Event::new("changed", key);
+        let mut message = format!("~ {key}");
+        if show_values {
+          let (a, b) = (String::from_utf8_lossy(a), String::from_utf8_lossy(b));
+          let diff = TextDiff::from_lines(&a, &b).unified_diff().header(key, key).to_string();
+          message = format!("{message}\n{}", diff.trim_end());
+          event = event.with("diff", diff);
+        }
+        event.emit(message);
+      }
+      _ => {}
+    }
+  }
+
+  Ok(())
+}
@@ -0,0 +1,277 @@
+use std::{fs, io::Write, path::{Component, Path, PathBuf}, sync::Mutex, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use anyhow::{Context, Result, ensure};
+use aws_sdk_ssm::types::ParameterStringFilter;
+use clap::ValueEnum;
+use futures::{StreamExt, TryStreamExt, stream};
+use itertools::Itertools;
+use tempfile::NamedTempFile;
+
+use crate::cancel;
+use crate::files::{FileMetadata, fetch_file_with_metadata, fetch_named_with_metadata, is_selected, logical_name, matching_names, within_depth};
+use crate::output::Event;
+use crate::progress::Progress;
+use crate::prompt::confirm;
+use crate::s3;
+use crate::ssm::{self, Client};
+
+pub const PRIVATE_MODE: u32 = 0o600;
+
+#[derive(Debug, Clone)]
+pub enum DownloadSource {
+  Prefix(String),
+  Name(String),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum Overwrite {
+  Skip,
+  #[default]
+  Replace,
+  Prompt,
+}
+
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+  pub overwrite: Overwrite,
+  pub concurrency: usize,
+  pub s3: Option<aws_sdk_s3::Client>,
+  pub filters: Vec<ParameterStringFilter>,
+  pub max_depth: Option<usize>,
+}
+
+impl Default for DownloadOptions {
+  fn default() -> Self {
+    Self { overwrite: Overwrite::default(), concurrency: 4, s3: None, filters: vec![], max_depth: None }
+  }
+}
+
+pub async fn download_to_dir(client: &Client, source: DownloadSource, output_dir: PathBuf, options: DownloadOptions) -> Result<()> {
+  let prefix = match source {
+    DownloadSource::Prefix(prefix) => prefix.trim_end_matches('/').to_string(),
+    DownloadSource::Name(name) => {
+      let file_name = name.rsplit('/').next().unwrap_or(&name).to_string();
+      let (content, metadata) = fetch_file_with_metadata(client, options.s3.as_ref(), &name).await?;
+      return write_file(&output_dir, &file_name, &content, metadata, options.overwrite);
+    },
+  };
+
+  let selected = matching_names(client, &prefix, &options.filters).await?;
+  let groups = ssm::all_parameter_metadata_by_path(client, &prefix)
+    .try_collect::<Vec<_>>()
+    .await?
+    .into_iter()
+    .flatten()
+    .filter_map(|p| p.name)
+    .filter(|name| is_selected(&selected, name) && within_depth(&prefix, name, options.max_depth))
+    .into_group_map_by(|name| logical_name(name).to_string());
+
+  let progress = Progress::new("download", groups.len());
+  let concurrency = if options.overwrite == Overwrite::Prompt { 1 } else { options.concurrency.max(1) };
+  let links = Mutex::new(vec![]);
+  let (prefix, output_dir, progress_ref, options, links_ref) = (&prefix, &output_dir, &progress, &options, &links);
+
+  stream::iter(groups)
+    .map(|(logical, names)| async move {
+      if cancel::is_cancelled() {
+        progress_ref.file_skipped();
+        return anyhow::Ok(());
+      }
+      progress_ref.start(&logical);
+      let (files, metadata) = fetch_named_with_metadata(client, prefix, &names, None).await?;
+      let files = s3::resolve_pointers(options.s3.as_ref(), files).await?;
+      progress_ref.api_calls(names.len().div_ceil(ssm::GET_BATCH_SIZE));
+      for (rel_path, content) in files {
+        progress_ref.transferred(names.len(), content.len());
+        let metadata = metadata.get(&rel_path).copied().unwrap_or_default();
+        if metadata.is_symlink() {
+          links_ref.lock().expect("links lock poisoned").push((rel_path, content, metadata));
+          progress_ref.file_done();
+          continue;
+        }
+        progress_ref.suspend(|| {
+          write_file(output_dir, &rel_path, &content, metadata, options.overwrite)?;
+          Event::new("download", rel_path.as_str()).with("bytes", content.len()).emit(format!("Downloaded {rel_path}"));
+          anyhow::Ok(())
+        })?;
+        progress_ref.file_done();
+      }
+      anyhow::Ok(())
+    })
+    .buffer_unordered(concurrency)
+    .try_collect::<Vec<_>>()
+    .await?;
+
+  for (rel_path, content, metadata) in links.into_inner().expect("links lock poisoned").into_iter().sorted_by(|(a, ..), (b, ..)| a.cmp(b)) {
+    progress.suspend(|| {
+      write_file(output_dir, &rel_path, &content, metadata, options.overwrite)?;
+      Event::new("download", rel_path.as_str()).with("bytes", content.len()).emit(format!("Downloaded {rel_path}"));
+      anyhow::Ok(())
+    })?;
+  }
+
+  progress.finish();
+  cancel::check()
+}
+
+fn write_file(output_dir: &Path, rel_path: &str, content: &[u8], metadata: FileMetadata, overwrite: Overwrite) -> Result<()> {
+  let full_path = safe_join(output_dir, rel_path)?;
+  ensure_no_symlink_parents(output_dir, rel_path)?;
+  if full_path.symlink_metadata().is_ok() {
+    let replace = match overwrite {
+      Overwrite::Skip => false,
+      Overwrite::Replace => true,
+      Overwrite::Prompt => confirm(&format!("Overwrite {}?", full_path.display()))?,
+    };
+    if !replace {
+      Event::new("skip", rel_path).emit(format!("Skipped {rel_path}"));
+      return Ok(());
+    }
+  }
+
+  if metadata.is_symlink() {
+    let target = std::str::from_utf8(content).context(format!("The symlink target of {rel_path} is not UTF-8"))?;
+    ensure_contained_target(output_dir, rel_path, target)?;
+    return write_symlink(&full_path, target).context(format!("Failed to create the symlink {}", full_path.display()));
+  }
+
+  let modified = metadata.mtime.map(|mtime| UNIX_EPOCH + Duration::from_secs(mtime.max(0) as u64));
+  write_atomic_with(&full_path, content, metadata.mode.unwrap_or(PRIVATE_MODE), modified).context(format!("Failed to write {}", full_path.display()))
+}
+
+pub fn safe_join(dir: &Path, rel_path: &str) -> Result<PathBuf> {
+  let path = Path::new(rel_path);
+  ensure!(!rel_path.is_empty() && path.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)), "Refusing to write {rel_path}, which escapes {}", dir.display());
+  Ok(dir.join(path))
+}
+
+pub fn ensure_no_symlink_parents(dir: &Path, rel_path: &str) -> Result<()> {
+  let mut path = dir.to_path_buf();
+  let components = Path::new(rel_path).components().collect::<Vec<_>>();
+  for component in components.iter().take(components.len().saturating_sub(1)) {
+    path.push(component);
+    ensure!(!path.symlink_metadata().is_ok_and(|m| m.is_symlink()), "Refusing to write {rel_path} through the symlink {}", path.display());
+  }
+  Ok(())
+}
+
+fn ensure_contained_target(dir: &Path, rel_path: &str, target: &str) -> Result<()> {
+  let mut depth = Path::new(rel_path).components().filter(|c| matches!(c, Component::Normal(_))).count().saturating_sub(1);
+  for component in Path::new(target).components() {
+    match component {
+      Component::Normal(_) => depth += 1,
+      Component::CurDir => {},
+      Component::ParentDir if depth > 0 => depth -= 1,
+      _ => anyhow::bail!("Refusing to create the symlink {rel_path} -> {target}, which points outside {}", dir.display()),
+    }
+  }
+  Ok(())
+}
+
+#[cfg(unix)]
+pub fn write_symlink(path: &Path, target: &str) -> Result<()> {
+  let parent = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+  fs::create_dir_all(parent)?;
+
+  tempfile::Builder::new().prefix(".ops-link-").make_in(parent, |temp| std::os::unix::fs::symlink(target, temp))?.persist(path)?;
+  Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn write_symlink(path: &Path, _target: &str) -> Result<()> {
+  anyhow::bail!("Can't recreate the symlink {} on this platform", path.display())
+}
+
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+  write_atomic_with_mode(path, content, PRIVATE_MODE)
+}
+
+pub fn write_atomic_with_mode(path: &Path, content: &[u8], mode: u32) -> Result<()> {
+  write_atomic_with(path, content, mode, None)
+}
+
+fn write_atomic_with(path: &Path, content: &[u8], mode: u32, modified: Option<SystemTime>) -> Result<()> {
+  let parent = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+  fs::create_dir_all(parent)?;
+
+  let mut file = NamedTempFile::new_in(parent)?;
+  let _tracked = cancel::track_temp(file.path());
+  set_mode(file.as_file(), mode)?;
+  file.write_all(content)?;
+  if let Some(modified) = modified {
+    file.as_file().set_modified(modified).context("Failed to set the mtime")?;
+  }
+  file.as_file().sync_all()?;
+  file.persist(path)?;
+  Ok(())
+}
+
+pub fn parse_mode(value: &str) -> Result<u32> {
+  let mode = u32::from_str_radix(value.trim_start_matches("0o"), 8).context(format!("Invalid octal mode {value}"))?;
+  ensure!(mode <= 0o777, "Mode {value} is out of range");
+  Ok(mode)
+}
+
+#[cfg(unix)]
+pub fn set_mode(file: &fs::File, mode: u32) -> Result<()> {
+  use std::os::unix::fs::PermissionsExt;
+
+  file.set_permissions(fs::Permissions::from_mode(mode))?;
+  Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn set_mode(_file: &fs::File, _mode: u32) -> Result<()> {
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn safe_join_accepts_nested_paths() {
+    assert_eq!(safe_join(Path::new("out"), "a/b.txt").unwrap(), Path::new("out/a/b.txt"));
+    assert_eq!(safe_join(Path::new("out"), "./a").unwrap(), Path::new("out/a"));
+    assert_eq!(safe_join(Path::new("out"), "a..b").unwrap(), Path::new("out/a..b"));
+  }
+
+  #[test]
+  fn safe_join_rejects_parent_segments() {
+    for name in ["..", "../x", "a/../../x", "a/b/../../../etc/passwd"] {
+      assert!(safe_join(Path::new("out"), name).is_err(), "{name} should be rejected");
+    }
+  }
+
+  #[test]
+  fn safe_join_rejects_absolute_and_empty_paths() {
+    for name in ["/etc/passwd", "//x", ""] {
+      assert!(safe_join(Path::new("out"), name).is_err(), "{name:?} should be rejected");
+    }
+  }
+
+  #[test]
+  fn write_file_refuses_to_escape_the_output_dir() {
+    let root = tempfile::tempdir().unwrap();
+    let out = root.path().join("out");
+
+    let err = write_file(&out, "../escaped", b"x", FileMetadata::default(), Overwrite::Replace).unwrap_err();
+    assert!(err.to_string().contains("escapes"));
+    assert!(!root.path().join("escaped").exists());
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn write_file_sets_the_mtime_of_a_read_only_file() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let out = tempfile::tempdir().unwrap();
+    let metadata = FileMetadata { mode: Some(0o400), mtime: Some(1_600_000_000), kind: None };
+
+    write_file(out.path(), "secret", b"x", metadata, Overwrite::Replace).unwrap();
+
+    let written = fs::metadata(out.path().join("secret")).unwrap();
+    assert_eq!(written.permissions().mode() & 0o777, 0o400);
+    assert_eq!(written.modified().unwrap(), UNIX_EPOCH + Duration::from_secs(1_600_000_000));
+  }
+}
@@ -0,0 +1,117 @@
+use std::{collections::BTreeMap, fs, io::Write, path::PathBuf, process::Command};
+
+use anyhow::{Context, Result, ensure};
+use aws_config::SdkConfig;
+use aws_sdk_ssm::config::ProvideCredentials;
+use serde_json::{Value, json};
+use tempfile::NamedTempFile;
+
+use crate::cancel;
+use crate::env::KeyTransform;
+use crate::files::referenceable_parameters;
+use crate::output::Event;
+use crate::ssm::{self, Client};
+
+const READ_ONLY_FIELDS: &[&str] =
+  &["taskDefinitionArn", "revision", "status", "requiresAttributes", "compatibilities", "registeredAt", "registeredBy", "deregisteredAt"];
+
+#[derive(Debug, Clone, Default)]
+pub struct PatchOptions {
+  pub container: Option<String>,
+  pub keys: KeyTransform,
+  pub out: Option<PathBuf>,
+  pub register: bool,
+  pub dry_run: bool,
+}
+
+pub async fn patch_taskdef(client: &Client, config: &SdkConfig, taskdef: PathBuf, base: String, options: PatchOptions) -> Result<()> {
+  let text = fs::read_to_string(&taskdef).context(format!("Failed to read {}", taskdef.display()))?;
+  let mut document: Value = serde_json::from_str(&text).context(format!("Failed to parse {}", taskdef.display()))?;
+  let mut definition = match document.get_mut("taskDefinition") {
+    Some(definition) => definition.take(),
+    None => document,
+  };
+  if let Some(fields) = definition.as_object_mut() {
+    READ_ONLY_FIELDS.iter().for_each(|field| drop(fields.remove(*field)));
+  }
+
+  let base = base.trim_end_matches('/').to_string();
+  let arn_prefix = ssm::parameter_arn_prefix(config).await?;
+  let managed = format!("{arn_prefix}{base}/");
+  let secrets = referenceable_parameters(client, &base, "ECS")
+    .await?
+    .into_iter()
+    .map(|name| (options.keys.apply(&base, &name), format!("{arn_prefix}{name}")))
+    .collect::<BTreeMap<_, _>>();
+
+  let containers = definition.get_mut("containerDefinitions").and_then(Value::as_array_mut).context(format!("{} has no containerDefinitions", taskdef.display()))?;
+  let mut patched = 0;
+  for container in containers.iter_mut() {
+    let name = container.get("name").and_then(Value::as_str).unwrap_or_default().to_string();
+    if options.container.as_ref().is_some_and(|wanted| *wanted != name) {
+      continue;
+    }
+
+    let mut entries = container
+      .get("secrets")
+      .and_then(Value::as_array)
+      .into_iter()
+      .flatten()
+      .filter_map(|secret| Some((secret.get("name")?.as_str()?.to_string(), secret.get("valueFrom")?.as_str()?.to_string())))
+      .filter(|(key, value_from)| !value_from.starts_with(&managed) && !secrets.contains_key(key))
+      .collect::<BTreeMap<_, _>>();
+    entries.extend(secrets.clone());
+    container["secrets"] = entries.into_iter().map(|(name, value_from)| json!({ "name": name, "valueFrom": value_from })).collect();
+
+    tracing::info!("Set {} secrets from {base} on container {name}", secrets.len());
+    patched += 1;
+  }
+  ensure!(patched > 0, "No container named {} in {}", options.container.as_deref().unwrap_or_default(), taskdef.display());
+
+  let output = serde_json::to_string_pretty(&definition)?;
+  match &options.out {
+    Some(out) => fs::write(out, format!("{output}\n")).context(format!("Failed to write {}", out.display()))?,
+    None if !options.register => println!("{output}"),
+    None => {},
+  }
+
+  if options.register {
+    register(config, &definition, &output, options.dry_run).await?;
+  }
+  Ok(())
+}
+
+async fn register(config: &SdkConfig, definition: &Value, output: &str, dry_run: bool) -> Result<()> {
+  let family = definition.get("family").and_then(Value::as_str).context("The task definition has no family")?;
+  if dry_run {
+    Event::new("would-register", family).emit(format!("Would register a new revision of {family}"));
+    return Ok(());
+  }
+
+  ssm::ensure_writable("ecs:RegisterTaskDefinition", family)?;
+  let mut file = NamedTempFile::new()?;
+  let _tracked = cancel::track_temp(file.path());
+  file.write_all(output.as_bytes())?;
+  file.flush()?;
+
+  let mut command = Command::new("aws");
+  command.args(["ecs", "register-task-definition", "--output", "json", "--cli-input-json"]).arg(format!("file://{}", file.path().display()));
+  if let Some(region) = config.region() {
+    command.args(["--region", region.as_ref()]);
+  }
+  if let Some(provider) = config.credentials_provider() {
+    let credentials = provider.provide_credentials().await.context("Failed to load credentials for aws ecs")?;
+    command.env("AWS_ACCESS_KEY_ID", credentials.access_key_id()).env("AWS_SECRET_ACCESS_KEY", credentials.secret_access_key());
+    match credentials.session_token() {
+      Some(token) => command.env("AWS_SESSION_TOKEN", token),
+      None => command.env_remove("AWS_SESSION_TOKEN"),
+    };
+  }
+
+  let result = command.output().context("Failed to run aws ecs register-task-definition")?;
+  ensure!(result.status.success(), "aws ecs register-task-definition failed: {}", String::from_utf8_lossy(&result.stderr).trim());
+  let response: Value = serde_json::from_slice(&result.stdout).context("Failed to parse the register-task-definition response")?;
+  let arn = response.pointer("/taskDefinition/taskDefinitionArn").and_then(Value::as_str).unwrap_or_default();
+  Event::new("register", family).with("arn", arn).emit(format!("Registered {arn}"));
+  Ok(())
+}
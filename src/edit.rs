@@ -0,0 +1,103 @@
+use std::{path::Path, process::Command};
+
+use anyhow::{Context, Result, bail, ensure};
+use aws_sdk_ssm::types::{ParameterMetadata, ParameterTier, ParameterType};
+use futures::TryStreamExt;
+use tempfile::NamedTempFile;
+
+use crate::cancel;
+use crate::copy::DEFAULT_KMS_KEY;
+use crate::files::{MANIFEST_SUFFIX, Manifest, S3_SUFFIX, fetch_file_with, logical_name, split_part};
+use crate::output::Event;
+use crate::prompt::confirm;
+use crate::s3;
+use crate::ssm::{self, Client};
+use crate::tags::list_tags;
+use crate::upload::{UploadOptions, prepare, put_files};
+use crate::validate::{self, Syntax};
+
+const DEFAULT_EDITOR: &str = "vi";
+
+#[derive(Debug, Clone, Default)]
+pub struct EditOptions {
+  pub validate: Option<Syntax>,
+  pub r#type: Option<ParameterType>,
+  pub tier: Option<ParameterTier>,
+  pub upload: UploadOptions,
+}
+
+pub async fn edit(client: &Client, s3: Option<&aws_sdk_s3::Client>, name: String, options: EditOptions) -> Result<()> {
+  let original = fetch_file_with(client, s3, &name).await?;
+  let existing = ssm::describe_parameters(client, vec![ssm::string_filter("Name", "BeginsWith", &name)])
+    .try_collect::<Vec<_>>()
+    .await?
+    .into_iter()
+    .flatten()
+    .filter(|p| p.name().is_some_and(|n| logical_name(n) == name))
+    .collect::<Vec<_>>();
+  let upload = carry_over(client, &existing, options.r#type, options.tier, options.upload).await?;
+  let existing = existing.into_iter().filter_map(|p| p.name).collect::<Vec<_>>();
+  ensure!(
+    !upload.encrypt_with.is_empty() || !is_age_encrypted(client, s3, &existing).await?,
+    "{name} is age-encrypted; pass --encrypt-with so the edited value is encrypted again"
+  );
+
+  let suffix = Path::new(&name).extension().map(|ext| format!(".{}", ext.to_string_lossy())).unwrap_or_default();
+  let file = tempfile::Builder::new().prefix("ops-edit-").suffix(&suffix).tempfile().context("Failed to create a temp file")?;
+  let _tracked = cancel::track_temp(file.path());
+  std::fs::write(file.path(), &original)?;
+
+  let edited = loop {
+    launch_editor(&file)?;
+    let edited = std::fs::read(file.path()).context(format!("Failed to read {}", file.path().display()))?;
+
+    match validate::validate(&name, &edited, options.validate) {
+      Ok(_) => break edited,
+      Err(e) if confirm(&format!("{e:#}. Edit again?"))? => continue,
+      Err(_) => bail!("Aborted; {name} was not changed"),
+    }
+  };
+
+  if edited == original {
+    Event::new("unchanged", name.as_str()).emit(format!("{name} is unchanged"));
+    return Ok(());
+  }
+
+  let prepared = prepare(&name, edited, &upload)?;
+  put_files(client, vec![prepared], &upload).await
+}
+
+async fn carry_over(client: &Client, existing: &[ParameterMetadata], r#type: Option<ParameterType>, tier: Option<ParameterTier>, upload: UploadOptions) -> Result<UploadOptions> {
+  let Some(current) = existing.iter().filter(|p| p.name().is_some_and(|n| !n.ends_with(MANIFEST_SUFFIX))).min_by_key(|p| split_part(p.name().unwrap_or_default()).1) else {
+    return Ok(UploadOptions { r#type: r#type.unwrap_or(upload.r#type), tier: tier.unwrap_or(upload.tier), ..upload });
+  };
+  let r#type = r#type.or_else(|| current.r#type().cloned()).unwrap_or(upload.r#type);
+  let tier = tier.or_else(|| current.tier().cloned()).unwrap_or(upload.tier);
+  let kms_key_id = upload.kms_key_id.or_else(|| current.key_id().filter(|k| *k != DEFAULT_KMS_KEY).map(str::to_string));
+  let mut tags = list_tags(client, current.name().unwrap_or_default()).await?.into_iter().filter(|tag| !upload.tags.iter().any(|t| t.key() == tag.key())).collect::<Vec<_>>();
+  tags.extend(upload.tags);
+  Ok(UploadOptions { r#type, tier, kms_key_id, tags, ..upload })
+}
+
+async fn is_age_encrypted(client: &Client, s3: Option<&aws_sdk_s3::Client>, names: &[String]) -> Result<bool> {
+  let Some(marker) = names.iter().find(|n| n.ends_with(MANIFEST_SUFFIX) || n.ends_with(S3_SUFFIX)) else {
+    return Ok(false);
+  };
+  let (parameters, _) = ssm::get_parameters(client, std::slice::from_ref(marker)).await?;
+  let value = parameters.first().and_then(|p| p.value()).unwrap_or_default();
+  match s3.filter(|_| marker.ends_with(S3_SUFFIX)) {
+    Some(s3) => Ok(s3::get_object(s3, value).await?.1.is_some()),
+    None if marker.ends_with(S3_SUFFIX) => Ok(false),
+    None => Ok(serde_json::from_str::<Manifest>(value).context(format!("Invalid manifest {marker}"))?.encryption.is_some()),
+  }
+}
+
+fn launch_editor(file: &NamedTempFile) -> Result<()> {
+  let editor = std::env::var("VISUAL").or_else(|_| std::env::var("EDITOR")).unwrap_or_else(|_| DEFAULT_EDITOR.to_string());
+  let mut words = editor.split_whitespace();
+  let program = words.next().context("$EDITOR is empty")?;
+
+  let status = Command::new(program).args(words).arg(file.path()).status().context(format!("Failed to launch {program}"))?;
+  ensure!(status.success(), "{program} exited with {status}");
+  Ok(())
+}
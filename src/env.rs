@@ -0,0 +1,254 @@
+use std::{io::Write, path::Path};
+
+use anyhow::{Context, Result, ensure};
+use clap::ValueEnum;
+use itertools::Itertools;
+
+use crate::download::{PRIVATE_MODE, write_atomic_with_mode};
+use crate::env_file::{self, EnvFormat};
+use crate::output::Event;
+use crate::pin::Pin;
+use crate::store::SecretStore;
+use crate::systemd::{self, SystemdOptions};
+use crate::upload::file_mode;
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum StringListMode {
+  #[default]
+  Raw,
+  Indexed,
+  Joined,
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum KeyCase {
+  #[default]
+  Upper,
+  Lower,
+  Preserve,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct KeyTransform {
+  pub case: KeyCase,
+  pub prefix: Option<String>,
+  pub strip_path_components: usize,
+  pub map: Vec<(String, String)>,
+}
+
+impl KeyTransform {
+  pub fn apply(&self, base: &str, name: &str) -> String {
+    let rel_path = name.strip_prefix(base.trim_end_matches('/')).unwrap_or(name).trim_start_matches('/');
+    if let Some((_, key)) = self.map.iter().find(|(from, _)| from == name || from == rel_path) {
+      return key.clone();
+    }
+
+    let segments = rel_path.split(['/', '#']).filter(|s| !s.is_empty()).collect::<Vec<_>>();
+    let key = segments[self.strip_path_components.min(segments.len().saturating_sub(1))..].join("_");
+    let key = match self.case {
+      KeyCase::Upper => key.to_ascii_uppercase(),
+      KeyCase::Lower => key.to_ascii_lowercase(),
+      KeyCase::Preserve => key,
+    };
+    format!("{}{key}", self.prefix.as_deref().unwrap_or_default())
+  }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EnvOptions {
+  pub vars: Vec<String>,
+  pub all: bool,
+  pub format: EnvFormat,
+  pub string_list: StringListMode,
+  pub list_delimiter: Option<String>,
+  pub mode: Option<u32>,
+  pub allow_missing: bool,
+  pub defaults: Vec<(String, String)>,
+  pub keys: KeyTransform,
+  pub merge: bool,
+  pub pin: Option<Pin>,
+  pub systemd: Option<SystemdOptions>,
+}
+
+#[derive(Debug, Clone)]
+struct Var {
+  key: String,
+  value: String,
+  string_list: bool,
+}
+
+pub async fn set_env(store: &dyn SecretStore, file: Option<String>, base: String, options: EnvOptions) -> Result<()> {
+  let (mut vars, missing) = if options.all {
+    tracing::info!("Getting all vars from {base}");
+    (fetch_all_vars(store, &base, &options.keys, options.pin.as_ref()).await?, vec![])
+  } else {
+    tracing::info!("Getting vars {:?} from {base}", options.vars);
+    fetch_vars(store, &base, &options.vars, &options.keys, options.pin.as_ref()).await?
+  };
+
+  let unresolved = apply_defaults(&mut vars, &missing, &options.defaults);
+  if !unresolved.is_empty() {
+    ensure!(options.allow_missing, "Parameters not found under {base}: {}; pass --allow-missing or --default KEY=value", unresolved.iter().join(", "));
+    tracing::warn!("Parameters not found: {}", unresolved.iter().join(", "));
+  }
+
+  let entries = expand_string_lists(vars, options.string_list, options.list_delimiter.as_deref().unwrap_or(","));
+  let mut output = env_file::render(&entries, options.format)?;
+  if let Some(systemd) = &options.systemd {
+    return systemd::install(&output, entries.len(), systemd);
+  }
+  if let Some(var) = options.format.github_file_var() {
+    return write_github(file, var, &entries, &output);
+  }
+  let Some(file) = file else {
+    let mut stdout = std::io::stdout().lock();
+    writeln!(stdout, "{output}")?;
+    stdout.flush()?;
+    return Ok(());
+  };
+
+  let mut mode = options.mode;
+  if options.merge {
+    ensure!(!matches!(options.format, EnvFormat::Json), "--merge can't be used with --format json");
+    match std::fs::read_to_string(&file) {
+      Ok(existing) => {
+        output = env_file::merge(&existing, &output);
+        mode = mode.or_else(|| std::fs::metadata(&file).ok().as_ref().and_then(file_mode));
+      },
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => output = env_file::merge("", &output),
+      Err(e) => return Err(e).context(format!("Failed to read {file}")),
+    }
+  }
+
+  write_atomic_with_mode(Path::new(&file), output.as_bytes(), mode.unwrap_or(PRIVATE_MODE)).context(format!("Failed to write to {file}"))?;
+  Event::new("write", file.as_str()).with("vars", entries.len()).emit(format!("Wrote {} vars to {file}", entries.len()));
+
+  Ok(())
+}
+
+fn write_github(file: Option<String>, var: &str, entries: &[(String, String)], output: &str) -> Result<()> {
+  let file = file.or_else(|| std::env::var(var).ok()).context(format!("${var} is not set; pass --file or run inside GitHub Actions"))?;
+  for mask in env_file::github_masks(entries) {
+    println!("{mask}");
+  }
+
+  let mut handle = std::fs::OpenOptions::new().create(true).append(true).open(&file).context(format!("Failed to open {file}"))?;
+  writeln!(handle, "{output}").context(format!("Failed to write to {file}"))?;
+  Event::new("write", file.as_str()).with("vars", entries.len()).emit(format!("Appended {} vars to {file}", entries.len()));
+  Ok(())
+}
+
+pub fn env_key(rel_path: &str) -> String {
+  rel_path.replace('/', "_").to_ascii_uppercase()
+}
+
+fn expand_string_lists(vars: Vec<Var>, mode: StringListMode, delimiter: &str) -> Vec<(String, String)> {
+  vars
+    .into_iter()
+    .flat_map(|var| match (var.string_list, mode) {
+      (true, StringListMode::Indexed) => var.value.split(',').enumerate().map(|(i, item)| (format!("{}_{i}", var.key), item.to_string())).collect(),
+      (true, StringListMode::Joined) => vec![(var.key, var.value.split(',').join(delimiter))],
+      _ => vec![(var.key, var.value)],
+    })
+    .collect()
+}
+
+async fn fetch_all_vars(store: &dyn SecretStore, base: &str, keys: &KeyTransform, pin: Option<&Pin>) -> Result<Vec<Var>> {
+  let entries = store.fetch_prefix(base, pin).await?;
+
+  Ok(
+    entries
+      .into_iter()
+      .map(|entry| Var {
+        key: keys.apply(base, &entry.name),
+        value: String::from_utf8_lossy(&entry.value).into_owned(),
+        string_list: entry.string_list,
+      })
+      .sorted_by(|a, b| a.key.cmp(&b.key))
+      .collect(),
+  )
+}
+
+fn apply_defaults(vars: &mut Vec<Var>, missing: &[String], defaults: &[(String, String)]) -> Vec<String> {
+  for (key, value) in defaults {
+    if !vars.iter().any(|var| var.key == *key) {
+      vars.push(Var { key: key.clone(), value: value.clone(), string_list: false });
+    }
+  }
+  missing.iter().filter(|key| !defaults.iter().any(|(k, _)| k == *key)).cloned().collect()
+}
+
+async fn fetch_vars(store: &dyn SecretStore, base: &str, vars: &[String], keys: &KeyTransform, pin: Option<&Pin>) -> Result<(Vec<Var>, Vec<String>)> {
+  let names = vars.iter().map(|v| format!("{base}/{v}")).collect::<Vec<_>>();
+
+  let (entries, invalid) = store.fetch_names(&names, pin).await.context("Failed to fetch parameters")?;
+  let vars = entries
+    .into_iter()
+    .map(|entry| Var { key: keys.apply(base, &entry.name), value: String::from_utf8_lossy(&entry.value).into_owned(), string_list: entry.string_list })
+    .collect();
+
+  Ok((vars, invalid.iter().map(|name| keys.apply(base, name)).collect()))
+}
+
+pub fn parse_mapping(value: &str) -> Result<(String, String)> {
+  let (name, key) = value.split_once('=').context(format!("Invalid mapping {value}; expected ssm_name=ENV_NAME"))?;
+  ensure!(!name.is_empty() && !key.is_empty(), "Invalid mapping {value}; expected ssm_name=ENV_NAME");
+  Ok((name.to_string(), key.to_string()))
+}
+
+pub fn parse_default(value: &str) -> Result<(String, String)> {
+  let (key, value) = value.split_once('=').context(format!("Invalid default {value}; expected KEY=value"))?;
+  ensure!(!key.is_empty(), "Invalid default {value}; the key is empty");
+  Ok((key.to_string(), value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn var(key: &str, value: &str) -> Var {
+    Var { key: key.to_string(), value: value.to_string(), string_list: false }
+  }
+
+  #[test]
+  fn apply_strips_the_base_and_joins_segments() {
+    let keys = KeyTransform::default();
+    assert_eq!(keys.apply("/app", "/app/db/url"), "DB_URL");
+    assert_eq!(keys.apply("/app/", "/app/db#primary"), "DB_PRIMARY");
+    assert_eq!(keys.apply("/other", "/app/port"), "APP_PORT");
+  }
+
+  #[test]
+  fn apply_honours_case_prefix_strip_and_map() {
+    let keys = KeyTransform { case: KeyCase::Preserve, prefix: Some("APP_".to_string()), strip_path_components: 1, map: vec![] };
+    assert_eq!(keys.apply("/app", "/app/db/Url"), "APP_Url");
+    assert_eq!(keys.apply("/app", "/app/port"), "APP_port");
+
+    let keys = KeyTransform { case: KeyCase::Lower, map: vec![("db/url".to_string(), "DATABASE_URL".to_string())], ..Default::default() };
+    assert_eq!(keys.apply("/app", "/app/db/url"), "DATABASE_URL");
+    assert_eq!(keys.apply("/app", "/app/DB/Host"), "db_host");
+  }
+
+  #[test]
+  fn defaults_fill_missing_keys_exactly_as_given() {
+    let keys = KeyTransform { case: KeyCase::Lower, prefix: Some("app_".to_string()), ..Default::default() };
+    let missing = vec![keys.apply("/app", "/app/port")];
+    let mut vars = vec![var("app_host", "example.com")];
+
+    let unresolved = apply_defaults(&mut vars, &missing, &[("app_port".to_string(), "80".to_string()), ("app_host".to_string(), "localhost".to_string())]);
+
+    assert!(unresolved.is_empty());
+    assert_eq!(vars.iter().map(|v| (v.key.as_str(), v.value.as_str())).collect::<Vec<_>>(), [("app_host", "example.com"), ("app_port", "80")]);
+  }
+
+  #[test]
+  fn defaults_must_match_the_transformed_key() {
+    let missing = vec!["APP_PORT".to_string()];
+    let mut vars = vec![];
+
+    let unresolved = apply_defaults(&mut vars, &missing, &[("port".to_string(), "80".to_string())]);
+
+    assert_eq!(unresolved, ["APP_PORT"]);
+    assert_eq!(vars.iter().map(|v| v.key.as_str()).collect::<Vec<_>>(), ["port"]);
+  }
+}
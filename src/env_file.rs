@@ -0,0 +1,139 @@
+use std::{collections::hash_map::RandomState, hash::{BuildHasher, Hasher}};
+
+use anyhow::{Context, Result, ensure};
+use clap::ValueEnum;
+use itertools::Itertools;
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum EnvFormat {
+  #[default]
+  Dotenv,
+  ShellExport,
+  Json,
+  Systemd,
+  Docker,
+  GithubActions,
+  GithubOutput,
+}
+
+impl EnvFormat {
+  pub fn github_file_var(self) -> Option<&'static str> {
+    match self {
+      EnvFormat::GithubActions => Some("GITHUB_ENV"),
+      EnvFormat::GithubOutput => Some("GITHUB_OUTPUT"),
+      _ => None,
+    }
+  }
+}
+
+pub const MANAGED_BEGIN: &str = "# BEGIN ops managed";
+pub const MANAGED_END: &str = "# END ops managed";
+
+pub fn merge(existing: &str, managed: &str) -> String {
+  let block = format!("{MANAGED_BEGIN}\n{managed}\n{MANAGED_END}");
+  let lines = existing.lines().collect::<Vec<_>>();
+  let begin = lines.iter().position(|line| line.trim() == MANAGED_BEGIN);
+  let end = begin.and_then(|begin| lines[begin..].iter().position(|line| line.trim() == MANAGED_END).map(|end| begin + end));
+
+  let (before, after) = match (begin, end) {
+    (Some(begin), Some(end)) => (&lines[..begin], &lines[end + 1..]),
+    _ => (&lines[..], &[][..]),
+  };
+  before.iter().copied().chain([block.as_str()]).chain(after.iter().copied()).collect::<Vec<_>>().join("\n") + "\n"
+}
+
+pub fn render(entries: &[(String, String)], format: EnvFormat) -> Result<String> {
+  let output = match format {
+    EnvFormat::Dotenv => entries.iter().map(|(key, value)| format!("{key}=\"{}\"", escape_double_quoted(value))).collect::<Vec<_>>().join("\n"),
+    EnvFormat::ShellExport => entries.iter().map(|(key, value)| format!("export {key}='{}'", value.replace('\'', r"'\''"))).collect::<Vec<_>>().join("\n"),
+    EnvFormat::Json => serde_json::to_string_pretty(&entries.iter().map(|(key, value)| (key.clone(), value.as_str().into())).collect::<serde_json::Map<_, _>>())?,
+    EnvFormat::Systemd => entries.iter().map(|(key, value)| format!("{key}=\"{}\"", escape_systemd(value))).collect::<Vec<_>>().join("\n"),
+    EnvFormat::Docker => entries
+      .iter()
+      .map(|(key, value)| {
+        ensure!(!value.contains(['\n', '\r']), "{key} contains a newline, which docker --env-file can't represent");
+        Ok(format!("{key}={value}"))
+      })
+      .collect::<Result<Vec<_>>>()?
+      .join("\n"),
+    EnvFormat::GithubActions | EnvFormat::GithubOutput => entries.iter().map(|(key, value)| github_entry(key, value)).collect::<Vec<_>>().join("\n"),
+  };
+  Ok(output)
+}
+
+fn github_entry(key: &str, value: &str) -> String {
+  if !value.contains(['\n', '\r']) {
+    return format!("{key}={value}");
+  }
+  let delimiter = format!("ops_{:016x}", RandomState::new().build_hasher().finish());
+  format!("{key}<<{delimiter}\n{value}\n{delimiter}")
+}
+
+pub fn github_masks(entries: &[(String, String)]) -> Vec<String> {
+  entries.iter().flat_map(|(_, value)| value.lines()).map(str::trim).filter(|line| !line.is_empty()).unique().map(|line| format!("::add-mask::{line}")).collect()
+}
+
+fn escape_double_quoted(value: &str) -> String {
+  value
+    .chars()
+    .map(|c| match c {
+      '\\' => r"\\".to_string(),
+      '"' => r#"\""#.to_string(),
+      '$' => r"\$".to_string(),
+      '`' => r"\`".to_string(),
+      '\n' => r"\n".to_string(),
+      '\r' => r"\r".to_string(),
+      c => c.to_string(),
+    })
+    .collect()
+}
+
+fn escape_systemd(value: &str) -> String {
+  value
+    .chars()
+    .map(|c| match c {
+      '\\' | '"' | '$' | '`' => format!("\\{c}"),
+      c => c.to_string(),
+    })
+    .collect()
+}
+
+pub fn parse_dotenv(content: &str) -> Result<Vec<(String, String)>> {
+  content
+    .lines()
+    .enumerate()
+    .map(|(i, line)| (i + 1, line.trim()))
+    .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+    .map(|(number, line)| {
+      let line = line.strip_prefix("export ").unwrap_or(line);
+      let (key, value) = line.split_once('=').context(format!("Line {number} is not KEY=VALUE"))?;
+      let value = value.trim();
+      let value = if let Some(quoted) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        unescape_double_quoted(quoted)
+      } else if let Some(quoted) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+        quoted.replace(r"'\''", "'")
+      } else {
+        value.to_string()
+      };
+      Ok((key.trim().to_string(), value))
+    })
+    .collect()
+}
+
+fn unescape_double_quoted(value: &str) -> String {
+  let mut output = String::with_capacity(value.len());
+  let mut chars = value.chars();
+  while let Some(c) = chars.next() {
+    match (c, chars.clone().next()) {
+      ('\\', Some('n')) => output.push('\n'),
+      ('\\', Some('r')) => output.push('\r'),
+      ('\\', Some(escaped @ ('\\' | '"' | '$' | '`'))) => output.push(escaped),
+      (c, _) => {
+        output.push(c);
+        continue;
+      },
+    }
+    chars.next();
+  }
+  output
+}
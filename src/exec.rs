@@ -0,0 +1,30 @@
+use anyhow::{Context, Result};
+
+use crate::env::KeyTransform;
+use crate::pin::Pin;
+use crate::store::SecretStore;
+
+pub async fn exec(store: &dyn SecretStore, base: String, command: Vec<String>, keys: KeyTransform, pin: Option<Pin>) -> Result<()> {
+  let envs = store
+    .fetch_prefix(&base, pin.as_ref())
+    .await?
+    .into_iter()
+    .map(|entry| (keys.apply(&base, &entry.name), String::from_utf8_lossy(&entry.value).into_owned()))
+    .collect::<Vec<_>>();
+
+  let (program, args) = command.split_first().context("No command given")?;
+  let mut child = std::process::Command::new(program);
+  child.args(args).envs(envs);
+
+  #[cfg(unix)]
+  {
+    use std::os::unix::process::CommandExt;
+    Err(child.exec()).context(format!("Failed to exec {program}"))
+  }
+
+  #[cfg(not(unix))]
+  {
+    let status = child.status().context(format!("Failed to run {program}"))?;
+    std::process::exit(status.code().unwrap_or(1));
+  }
+}
@@ -0,0 +1,121 @@
+use std::{collections::BTreeMap, io::Write, path::{Path, PathBuf}};
+
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use serde_json::Value;
+
+use crate::download::write_atomic;
+use crate::env::env_key;
+use crate::env_file::{self, EnvFormat};
+use crate::files::fetch_files;
+use crate::output::Event;
+use crate::ssm::Client;
+use crate::upload::{UploadOptions, prepare, put_files};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ExportFormat {
+  Json,
+  Yaml,
+  Dotenv,
+  Csv,
+}
+
+impl ExportFormat {
+  fn from_path(path: &Path) -> Option<Self> {
+    match path.extension()?.to_str()? {
+      "json" => Some(Self::Json),
+      "yaml" | "yml" => Some(Self::Yaml),
+      "env" => Some(Self::Dotenv),
+      "csv" => Some(Self::Csv),
+      _ if path.file_name()?.to_str()?.starts_with(".env") => Some(Self::Dotenv),
+      _ => None,
+    }
+  }
+}
+
+pub async fn export(client: &Client, s3: Option<&aws_sdk_s3::Client>, prefix: String, format: ExportFormat, out: Option<PathBuf>) -> Result<()> {
+  let prefix = prefix.trim_end_matches('/');
+  let entries = fetch_files(client, s3, prefix)
+    .await?
+    .into_iter()
+    .map(|(rel_path, content)| {
+      let value = String::from_utf8(content).map_err(|_| anyhow::anyhow!("{prefix}/{rel_path} is binary and can't be exported"))?;
+      Ok((rel_path, value))
+    })
+    .collect::<Result<BTreeMap<_, _>>>()?;
+
+  let output = match format {
+    ExportFormat::Json => serde_json::to_string_pretty(&entries)?,
+    ExportFormat::Yaml => serde_yaml::to_string(&entries)?,
+    ExportFormat::Dotenv => env_file::render(&entries.iter().map(|(k, v)| (env_key(k), v.clone())).collect::<Vec<_>>(), EnvFormat::Dotenv)?,
+    ExportFormat::Csv => {
+      let mut writer = csv::Writer::from_writer(vec![]);
+      writer.write_record(["key", "value"])?;
+      entries.iter().try_for_each(|(key, value)| writer.write_record([key, value]))?;
+      String::from_utf8(writer.into_inner()?)?
+    },
+  };
+
+  match out {
+    Some(path) => {
+      write_atomic(&path, output.as_bytes()).context(format!("Failed to write {}", path.display()))?;
+      let path = path.display().to_string();
+      Event::new("export", path.as_str()).with("parameters", entries.len()).emit(format!("Exported {} parameters to {path}", entries.len()));
+    },
+    None => {
+      let mut stdout = std::io::stdout().lock();
+      writeln!(stdout, "{output}")?;
+      stdout.flush()?;
+    },
+  }
+
+  Ok(())
+}
+
+pub async fn import(client: &Client, file: PathBuf, prefix: String, format: Option<ExportFormat>, options: UploadOptions) -> Result<()> {
+  let format = format.or_else(|| ExportFormat::from_path(&file)).context(format!("Can't tell the format of {}; pass --format", file.display()))?;
+  let content = std::fs::read_to_string(&file).context(format!("Failed to read {}", file.display()))?;
+
+  let entries = match format {
+    ExportFormat::Json => flatten(serde_json::from_str(&content).context("Invalid JSON")?)?,
+    ExportFormat::Yaml => flatten(serde_yaml::from_str(&content).context("Invalid YAML")?)?,
+    ExportFormat::Dotenv => env_file::parse_dotenv(&content)?.into_iter().map(|(key, value)| (key.to_ascii_lowercase(), value)).collect(),
+    ExportFormat::Csv => csv::Reader::from_reader(content.as_bytes())
+      .records()
+      .map(|record| {
+        let record = record?;
+        Ok((record.get(0).context("Missing key column")?.to_string(), record.get(1).context("Missing value column")?.to_string()))
+      })
+      .collect::<Result<_>>()?,
+  };
+
+  let prefix = prefix.trim_end_matches('/');
+  let files = entries
+    .into_iter()
+    .map(|(key, value)| prepare(&format!("{prefix}/{}", key.trim_start_matches('/')), value.into_bytes(), &options))
+    .collect::<Result<Vec<_>>>()?;
+
+  tracing::info!("Importing {} parameters into {prefix}", files.len());
+  put_files(client, files, &options).await
+}
+
+fn flatten(value: Value) -> Result<BTreeMap<String, String>> {
+  let Value::Object(map) = value else {
+    bail!("Expected a top-level object of keys to values");
+  };
+
+  let mut entries = BTreeMap::new();
+  for (key, value) in map {
+    match value {
+      Value::Object(_) => entries.extend(flatten(value)?.into_iter().map(|(k, v)| (format!("{key}/{k}"), v))),
+      Value::String(s) => {
+        entries.insert(key, s);
+      },
+      Value::Null => tracing::warn!("Skipping {key}, which is null"),
+      other => {
+        entries.insert(key, other.to_string());
+      },
+    }
+  }
+  Ok(entries)
+}
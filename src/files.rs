@@ -0,0 +1,394 @@
+use std::{collections::{HashMap, HashSet}, fs, path::{Component, Path, PathBuf}, sync::{Arc, Mutex}};
+
+use anyhow::{Context, Result, ensure};
+use aws_sdk_ssm::types::{ParameterStringFilter, ParameterTier};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use futures::TryStreamExt;
+use ignore::WalkBuilder;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use walkdir::WalkDir;
+
+use crate::compress::Compression;
+use crate::crypto::{self, Encryption};
+use crate::filter::PathFilter;
+use crate::pin::{self, Pin};
+use crate::s3;
+use crate::ssm::{self, Client};
+
+pub const CHUNK_SIZE: usize = 4096;
+pub const ADVANCED_CHUNK_SIZE: usize = 8192;
+pub const STANDARD_PARAMETER_QUOTA: usize = 10_000;
+pub const ADVANCED_PARAMETER_QUOTA: usize = 100_000;
+pub const BASE64_SUFFIX: &str = ".b64";
+pub const HASH_DESCRIPTION_PREFIX: &str = "sha256:";
+pub const MANIFEST_SUFFIX: &str = ".manifest";
+pub const S3_SUFFIX: &str = ".s3";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+  pub parts: usize,
+  pub sha256: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub compression: Option<Compression>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub encryption: Option<Encryption>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub mode: Option<u32>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub mtime: Option<i64>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub kind: Option<FileKind>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileKind {
+  Symlink,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+  #[default]
+  Preserve,
+  Follow,
+  Skip,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WalkOptions {
+  pub gitignore: bool,
+  pub symlinks: SymlinkPolicy,
+  pub include_hidden: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct Walk {
+  pub files: Vec<PathBuf>,
+  pub hidden: Vec<PathBuf>,
+}
+
+pub type Files = HashMap<String, Vec<u8>>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FileMetadata {
+  pub mode: Option<u32>,
+  pub mtime: Option<i64>,
+  pub kind: Option<FileKind>,
+}
+
+impl FileMetadata {
+  pub fn is_empty(&self) -> bool {
+    self.mode.is_none() && self.mtime.is_none() && self.kind.is_none()
+  }
+
+  pub fn is_symlink(&self) -> bool {
+    self.kind == Some(FileKind::Symlink)
+  }
+}
+
+pub fn parameter_quota(tier: &ParameterTier) -> usize {
+  match tier {
+    ParameterTier::Standard => STANDARD_PARAMETER_QUOTA,
+    _ => ADVANCED_PARAMETER_QUOTA,
+  }
+}
+
+pub fn max_chunk_size(tier: &ParameterTier) -> usize {
+  match tier {
+    ParameterTier::Standard => CHUNK_SIZE,
+    _ => ADVANCED_CHUNK_SIZE,
+  }
+}
+
+pub async fn fetch_files(client: &Client, s3: Option<&aws_sdk_s3::Client>, prefix: &str) -> Result<HashMap<String, Vec<u8>>> {
+  Ok(fetch_files_with_metadata(client, s3, prefix).await?.0)
+}
+
+pub async fn fetch_files_with_metadata(client: &Client, s3: Option<&aws_sdk_s3::Client>, prefix: &str) -> Result<(Files, HashMap<String, FileMetadata>)> {
+  let (files, metadata) = fetch_stored_files(client, prefix).await?;
+  Ok((s3::resolve_pointers(s3, files).await?, metadata))
+}
+
+pub async fn fetch_stored_files(client: &Client, prefix: &str) -> Result<(Files, HashMap<String, FileMetadata>)> {
+  let prefix = prefix.trim_end_matches('/');
+  let params = ssm::all_parameters_by_path(client, prefix, true).try_collect::<Vec<_>>().await?.into_iter().flatten();
+
+  assemble_with_metadata(params.map(|param| {
+    let name = param.name().unwrap();
+    let rel_path = name.trim_start_matches(&format!("{prefix}/")).to_string();
+    (rel_path, param.value().unwrap().to_string())
+  }))
+}
+
+pub async fn fetch_file(client: &Client, name: &str) -> Result<Vec<u8>> {
+  fetch_file_with(client, None, name).await
+}
+
+pub async fn fetch_file_with(client: &Client, s3: Option<&aws_sdk_s3::Client>, name: &str) -> Result<Vec<u8>> {
+  fetch_file_at(client, s3, name, None).await
+}
+
+pub async fn fetch_file_at(client: &Client, s3: Option<&aws_sdk_s3::Client>, name: &str, pin: Option<&Pin>) -> Result<Vec<u8>> {
+  Ok(fetch_file_with_metadata_at(client, s3, name, pin).await?.0)
+}
+
+pub async fn fetch_file_with_metadata(client: &Client, s3: Option<&aws_sdk_s3::Client>, name: &str) -> Result<(Vec<u8>, FileMetadata)> {
+  fetch_file_with_metadata_at(client, s3, name, None).await
+}
+
+async fn fetch_file_with_metadata_at(client: &Client, s3: Option<&aws_sdk_s3::Client>, name: &str, pin: Option<&Pin>) -> Result<(Vec<u8>, FileMetadata)> {
+  let parent = name.rsplit_once('/').map(|(parent, _)| parent).unwrap_or_default();
+  let names = physical_names(client, name).await?;
+  ensure!(!names.is_empty(), "Parameter {name} not found");
+  if let Some(Pin::Version(version)) = pin {
+    ensure!(names.len() == 1, "{name} is split across {} parameters, so version {version} is ambiguous; pin it with --as-of or a label", names.len());
+  }
+
+  let (files, metadata) = fetch_named_with_metadata(client, parent, &names, pin).await?;
+  let mut files = s3::resolve_pointers(s3, files).await?;
+
+  let file_name = name.trim_start_matches(&format!("{parent}/"));
+  let content = match pin {
+    Some(pin) => files.remove(file_name).context(format!("Parameter {name} not found at {pin}")),
+    None => files.remove(file_name).context(format!("Parameter {name} not found")),
+  }?;
+  Ok((content, metadata.get(file_name).copied().unwrap_or_default()))
+}
+
+pub async fn matching_names(client: &Client, prefix: &str, filters: &[ParameterStringFilter]) -> Result<Option<HashSet<String>>> {
+  if filters.is_empty() {
+    return Ok(None);
+  }
+
+  let path = match prefix.trim_end_matches('/') {
+    "" => "/",
+    path => path,
+  };
+  let filters = [ssm::string_filter("Path", "Recursive", path)].into_iter().chain(filters.iter().cloned()).collect();
+  let names = ssm::describe_parameters(client, filters).try_collect::<Vec<_>>().await?.into_iter().flatten().filter_map(|p| p.name);
+  Ok(Some(names.map(|name| logical_name(&name).to_string()).collect()))
+}
+
+pub fn is_selected(selected: &Option<HashSet<String>>, name: &str) -> bool {
+  selected.as_ref().is_none_or(|names| names.contains(logical_name(name)))
+}
+
+pub fn within_depth(prefix: &str, name: &str, max_depth: Option<usize>) -> bool {
+  let rel_path = logical_name(name).strip_prefix(prefix.trim_end_matches('/')).unwrap_or(name).trim_start_matches('/');
+  max_depth.is_none_or(|max_depth| rel_path.split('/').count() <= max_depth)
+}
+
+pub async fn referenceable_parameters(client: &Client, prefix: &str, reader: &str) -> Result<Vec<String>> {
+  let groups = ssm::all_parameter_metadata_by_path(client, prefix)
+    .try_collect::<Vec<_>>()
+    .await?
+    .into_iter()
+    .flatten()
+    .filter_map(|p| p.name)
+    .into_group_map_by(|name| logical_name(name).to_string());
+
+  Ok(
+    groups
+      .into_iter()
+      .sorted_by(|a, b| a.0.cmp(&b.0))
+      .filter_map(|(logical, names)| {
+        if names.len() != 1 || names[0] != logical {
+          tracing::warn!("Skipping {logical}: chunked and binary parameters can't be read by {reader}");
+          return None;
+        }
+        Some(logical)
+      })
+      .collect(),
+  )
+}
+
+pub async fn physical_names(client: &Client, name: &str) -> Result<Vec<String>> {
+  Ok(
+    ssm::describe_parameters(client, vec![ssm::string_filter("Name", "BeginsWith", name)])
+      .try_collect::<Vec<_>>()
+      .await?
+      .into_iter()
+      .flatten()
+      .filter_map(|p| p.name)
+      .filter(|n| logical_name(n) == name)
+      .collect(),
+  )
+}
+
+pub async fn fetch_named(client: &Client, prefix: &str, names: &[String]) -> Result<HashMap<String, Vec<u8>>> {
+  Ok(fetch_named_with_metadata(client, prefix, names, None).await?.0)
+}
+
+pub async fn fetch_named_with_metadata(client: &Client, prefix: &str, names: &[String], pin: Option<&Pin>) -> Result<(Files, HashMap<String, FileMetadata>)> {
+  let prefix = prefix.trim_end_matches('/');
+  let (parameters, _) = pin::get_parameters(client, names, pin).await?;
+
+  assemble_with_metadata(parameters.into_iter().map(|p| {
+    let rel_path = p.name().unwrap_or_default().trim_start_matches(&format!("{prefix}/")).to_string();
+    (rel_path, p.value().unwrap_or_default().to_string())
+  }))
+}
+
+pub fn logical_name(name: &str) -> &str {
+  if let Some(name) = name.strip_suffix(S3_SUFFIX) {
+    return name;
+  }
+  let name = name.strip_suffix(MANIFEST_SUFFIX).unwrap_or(name);
+  let name = split_part(name).0;
+  name.strip_suffix(BASE64_SUFFIX).unwrap_or(name)
+}
+
+pub fn assemble(params: impl IntoIterator<Item = (String, String)>) -> Result<HashMap<String, Vec<u8>>> {
+  Ok(assemble_with_metadata(params)?.0)
+}
+
+pub fn assemble_with_metadata(params: impl IntoIterator<Item = (String, String)>) -> Result<(Files, HashMap<String, FileMetadata>)> {
+  let mut parameters: HashMap<String, Vec<(usize, String)>> = HashMap::new();
+  let mut manifests: HashMap<String, Manifest> = HashMap::new();
+  for (rel_path, content) in params {
+    if let Some(base) = rel_path.strip_suffix(MANIFEST_SUFFIX) {
+      let manifest = serde_json::from_str(&content).context(format!("Invalid manifest {rel_path}"))?;
+      manifests.insert(base.to_string(), manifest);
+      continue;
+    }
+
+    let (base, idx) = split_part(&rel_path);
+    parameters.entry(base.to_string()).or_default().push((idx.unwrap_or(0), content));
+  }
+
+  parameters
+    .into_iter()
+    .map(|(rel_path, mut chunks)| {
+      chunks.sort_by_key(|(i, _)| *i);
+      let manifest = manifests.remove(&rel_path);
+      if let Some(manifest) = &manifest {
+        chunks.retain(|(i, _)| *i < manifest.parts);
+        let found = chunks.iter().map(|(i, _)| *i).collect::<Vec<_>>();
+        ensure!(found == (0..manifest.parts).collect::<Vec<_>>(), "{rel_path} expected {} parts but found {found:?}", manifest.parts);
+      }
+
+      let content: String = chunks.into_iter().map(|(_, c)| c).collect();
+      let (rel_path, content) = match rel_path.strip_suffix(BASE64_SUFFIX) {
+        Some(original) => (original.to_string(), BASE64.decode(content).context(format!("Failed to decode {rel_path}"))?),
+        None => (rel_path, content.into_bytes()),
+      };
+
+      if let Some(manifest) = &manifest {
+        ensure!(hex::encode(Sha256::digest(&content)) == manifest.sha256, "{rel_path} does not match its manifest checksum");
+      }
+      let content = crypto::decrypt_as(manifest.as_ref().and_then(|m| m.encryption), content).context(format!("Failed to decrypt {rel_path}"))?;
+      let content = match manifest.as_ref().and_then(|m| m.compression) {
+        Some(compression) => compression.decompress(&content).context(format!("Failed to decompress {rel_path}"))?,
+        None => content,
+      };
+
+      let metadata = manifest.map(|m| FileMetadata { mode: m.mode, mtime: m.mtime, kind: m.kind }).unwrap_or_default();
+      Ok((rel_path, content, metadata))
+    })
+    .process_results(|files| {
+      let mut contents = HashMap::new();
+      let mut metadata = HashMap::new();
+      for (rel_path, content, meta) in files {
+        if !meta.is_empty() {
+          metadata.insert(rel_path.clone(), meta);
+        }
+        ensure!(contents.insert(rel_path.clone(), content).is_none(), "{rel_path} is stored both as text and as {rel_path}{BASE64_SUFFIX}; delete the stale one");
+      }
+      Ok((contents, metadata))
+    })?
+}
+
+pub fn read_local_files(dir: &Path) -> Result<HashMap<String, Vec<u8>>> {
+  WalkDir::new(dir)
+    .into_iter()
+    .filter_map(Result::ok)
+    .filter(|e| e.file_type().is_file())
+    .map(|entry| {
+      let rel_path = entry.path().strip_prefix(dir)?;
+      let key = to_ssm_key(rel_path).trim_start_matches('/').to_string();
+      Ok((key, fs::read(entry.path())?))
+    })
+    .collect()
+}
+
+pub fn walk_files(dir: &Path, filter: &PathFilter, options: WalkOptions) -> Result<Walk> {
+  let WalkOptions { gitignore, symlinks, include_hidden } = options;
+  let hidden = Arc::new(Mutex::new(vec![]));
+  let rel = |path: &Path| path.strip_prefix(dir).unwrap_or(path).to_path_buf();
+  let visible = {
+    let hidden = hidden.clone();
+    move |path: &Path, rel_path: &Path| {
+      if include_hidden || !is_hidden(rel_path) {
+        return true;
+      }
+      hidden.lock().unwrap().push(path.to_path_buf());
+      false
+    }
+  };
+
+  let files = if gitignore {
+    let filter = filter.clone();
+    let root = dir.to_path_buf();
+    WalkBuilder::new(dir)
+      .standard_filters(false)
+      .git_ignore(true)
+      .git_exclude(true)
+      .git_global(true)
+      .parents(true)
+      .require_git(false)
+      .follow_links(symlinks == SymlinkPolicy::Follow)
+      .filter_entry(move |e| {
+        let rel_path = e.path().strip_prefix(&root).unwrap_or(e.path());
+        e.file_name() != ".git" && !filter.is_excluded(rel_path) && visible(e.path(), rel_path)
+      })
+      .build()
+      .filter_map(|e| e.map_err(|err| tracing::warn!("Skipping {err}")).ok())
+      .filter(|e| e.file_type().is_some_and(|t| is_walked(t, e.path(), symlinks)))
+      .map(|e| e.into_path())
+      .collect()
+  } else {
+    WalkDir::new(dir)
+      .follow_links(symlinks == SymlinkPolicy::Follow)
+      .into_iter()
+      .filter_entry(|e| !filter.is_excluded(&rel(e.path())) && visible(e.path(), &rel(e.path())))
+      .filter_map(|e| e.map_err(|err| tracing::warn!("Skipping {err}")).ok())
+      .filter(|e| is_walked(e.file_type(), e.path(), symlinks))
+      .map(|e| e.into_path())
+      .collect()
+  };
+
+  let hidden = std::mem::take(&mut *hidden.lock().unwrap());
+  Ok(Walk { files, hidden })
+}
+
+pub fn is_hidden(rel_path: &Path) -> bool {
+  rel_path.components().any(|c| matches!(c, Component::Normal(name) if name.to_string_lossy().starts_with('.')))
+}
+
+fn is_walked(file_type: fs::FileType, path: &Path, symlinks: SymlinkPolicy) -> bool {
+  if file_type.is_symlink() && symlinks == SymlinkPolicy::Skip {
+    tracing::debug!("Skipping symlink {}", path.display());
+  }
+  file_type.is_file() || (file_type.is_symlink() && symlinks == SymlinkPolicy::Preserve)
+}
+
+pub fn split_part(name: &str) -> (&str, Option<usize>) {
+  match name.rsplit_once(".part") {
+    Some((base, part)) => match part.parse() {
+      Ok(idx) => (base, Some(idx)),
+      Err(_) => (name, None),
+    },
+    None => (name, None),
+  }
+}
+
+pub fn to_ssm_key(path: &Path) -> String {
+  let mut key = String::new();
+  for comp in path.components() {
+    key.push('/');
+    key.push_str(&comp.as_os_str().to_string_lossy());
+  }
+  key
+}
@@ -0,0 +1,73 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::Regex;
+
+pub const IGNORE_FILE: &str = ".opsignore";
+pub const REGEX_PREFIX: &str = "re:";
+
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+  include: Option<Matcher>,
+  exclude: Option<Matcher>,
+}
+
+#[derive(Debug, Clone)]
+struct Matcher {
+  globs: GlobSet,
+  regexes: Vec<Regex>,
+}
+
+impl Matcher {
+  fn is_match(&self, path: &Path) -> bool {
+    self.globs.is_match(path) || self.regexes.iter().any(|r| r.is_match(&path.to_string_lossy()))
+  }
+}
+
+impl PathFilter {
+  pub fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+    Ok(Self { include: build(include)?, exclude: build(exclude)? })
+  }
+
+  pub fn for_dir(dir: &Path, include: &[String], exclude: &[String]) -> Result<Self> {
+    let ignore_file = dir.join(IGNORE_FILE);
+    let mut exclude = exclude.to_vec();
+    if ignore_file.is_file() {
+      let patterns = fs::read_to_string(&ignore_file).context(format!("Failed to read {}", ignore_file.display()))?;
+      exclude.extend(patterns.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')).map(str::to_string));
+      exclude.push(IGNORE_FILE.to_string());
+    }
+    Self::new(include, &exclude)
+  }
+
+  pub fn is_excluded(&self, rel_path: &Path) -> bool {
+    self.exclude.as_ref().is_some_and(|set| rel_path.ancestors().any(|p| !p.as_os_str().is_empty() && set.is_match(p)))
+  }
+
+  pub fn is_included(&self, rel_path: &Path) -> bool {
+    self.include.as_ref().is_none_or(|set| set.is_match(rel_path))
+  }
+
+  pub fn matches(&self, rel_path: &Path) -> bool {
+    self.is_included(rel_path) && !self.is_excluded(rel_path)
+  }
+}
+
+fn build(patterns: &[String]) -> Result<Option<Matcher>> {
+  if patterns.is_empty() {
+    return Ok(None);
+  }
+
+  let mut globs = GlobSetBuilder::new();
+  let mut regexes = vec![];
+  for pattern in patterns {
+    match pattern.strip_prefix(REGEX_PREFIX) {
+      Some(regex) => regexes.push(Regex::new(regex).context(format!("Invalid regex {regex}"))?),
+      None => {
+        globs.add(Glob::new(pattern).context(format!("Invalid glob {pattern}"))?);
+      },
+    }
+  }
+  Ok(Some(Matcher { globs: globs.build()?, regexes }))
+}
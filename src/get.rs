@@ -0,0 +1,17 @@
+use std::io::{self, Write};
+
+use anyhow::Result;
+
+use crate::files::fetch_file_at;
+use crate::pin::{Pin, split_selector};
+use crate::ssm::Client;
+
+pub async fn get(client: &Client, name: String, pin: Option<Pin>) -> Result<()> {
+  let (name, selector) = split_selector(&name)?;
+  let content = fetch_file_at(client, None, name, selector.as_ref().or(pin.as_ref())).await?;
+
+  let mut stdout = io::stdout().lock();
+  stdout.write_all(&content)?;
+  stdout.flush()?;
+  Ok(())
+}
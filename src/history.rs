@@ -0,0 +1,68 @@
+use anyhow::{Context, Result, bail};
+use futures::TryStreamExt;
+
+use crate::files::{logical_name, physical_names};
+use crate::output::Event;
+use crate::ssm::{self, Client};
+use crate::tags::{add_tags, with_change_reason};
+
+pub async fn history(client: &Client, name: String) -> Result<()> {
+  let versions = ssm::parameter_history(client, &name).try_collect::<Vec<_>>().await?;
+
+  for version in versions.into_iter().flatten() {
+    let modified = version.last_modified_date().map(|d| d.to_string()).unwrap_or_default();
+    let user = version.last_modified_user().unwrap_or_default();
+    let labels = version.labels().join(",");
+
+    Event::new("version", name.as_str())
+      .with("version", version.version())
+      .with("last_modified", modified.as_str())
+      .with("user", user)
+      .with("labels", version.labels().to_vec())
+      .emit(format!("v{}\t{modified}\t{user}\t{labels}", version.version()));
+  }
+
+  Ok(())
+}
+
+pub async fn rollback(client: &Client, name: String, version: i64, dry_run: bool) -> Result<()> {
+  let name = match physical_names(client, logical_name(&name)).await?.as_slice() {
+    [] => bail!("Parameter {name} not found"),
+    [physical] => physical.clone(),
+    parts => bail!("{name} is split across {} parameters, whose versions don't line up; roll it back by re-uploading the old content", parts.len()),
+  };
+  let versions = ssm::parameter_history(client, &name).try_collect::<Vec<_>>().await?;
+  let target = versions
+    .into_iter()
+    .flatten()
+    .find(|v| v.version() == version)
+    .context(format!("Version {version} of {name} not found"))?;
+
+  if dry_run {
+    Event::new("would-rollback", name.as_str()).with("version", version).emit(format!("Would restore {name} to v{version}"));
+    return Ok(());
+  }
+
+  ssm::ensure_writable("ssm:PutParameter", &name)?;
+  let resp = ssm::retry_throttled(|| {
+    client
+      .put_parameter()
+      .name(&name)
+      .value(target.value().unwrap_or_default())
+      .overwrite(true)
+      .set_type(target.r#type().cloned())
+      .set_key_id(target.key_id().map(str::to_string))
+      .description(target.description().unwrap_or_default())
+      .set_tier(target.tier().cloned())
+      .send()
+  })
+  .await
+  .context(format!("Failed to restore {name}"))?;
+  add_tags(client, &name, &with_change_reason(&[])).await?;
+
+  Event::new("rollback", name.as_str())
+    .with("from_version", version)
+    .with("version", resp.version())
+    .emit(format!("Restored {name} to v{version} as v{}", resp.version()));
+  Ok(())
+}
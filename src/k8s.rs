@@ -0,0 +1,110 @@
+use std::{collections::BTreeMap, io::{self, Write}};
+
+use anyhow::Result;
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use clap::ValueEnum;
+use serde_json::json;
+
+use crate::files::{fetch_files, referenceable_parameters};
+use crate::ssm::Client;
+
+const EXTERNAL_SECRETS_API_VERSION: &str = "external-secrets.io/v1beta1";
+const DEFAULT_REFRESH_INTERVAL: &str = "1h";
+const SECRETS_STORE_CSI_API_VERSION: &str = "secrets-store.csi.x-k8s.io/v1";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum SecretFormat {
+  #[default]
+  Secret,
+  ExternalSecrets,
+  SecretProviderClass,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SecretOptions {
+  pub name: String,
+  pub namespace: Option<String>,
+  pub format: SecretFormat,
+  pub secret_store: String,
+}
+
+pub async fn secret(client: &Client, s3: Option<&aws_sdk_s3::Client>, prefix: String, options: SecretOptions) -> Result<()> {
+  let prefix = prefix.trim_end_matches('/').to_string();
+  let manifest = match options.format {
+    SecretFormat::Secret => secret_manifest(client, s3, &prefix, &options).await?,
+    SecretFormat::ExternalSecrets => external_secret_manifest(client, &prefix, &options).await?,
+    SecretFormat::SecretProviderClass => secret_provider_class_manifest(client, &prefix, &options).await?,
+  };
+
+  let mut stdout = io::stdout().lock();
+  stdout.write_all(serde_yaml::to_string(&manifest)?.as_bytes())?;
+  stdout.flush()?;
+  Ok(())
+}
+
+pub fn secret_key(rel_path: &str) -> String {
+  rel_path.trim_start_matches('/').replace('/', "_")
+}
+
+async fn secret_manifest(client: &Client, s3: Option<&aws_sdk_s3::Client>, prefix: &str, options: &SecretOptions) -> Result<serde_json::Value> {
+  let data = fetch_files(client, s3, prefix)
+    .await?
+    .into_iter()
+    .map(|(rel_path, content)| (secret_key(&rel_path), BASE64.encode(content)))
+    .collect::<BTreeMap<_, _>>();
+
+  Ok(json!({
+    "apiVersion": "v1",
+    "kind": "Secret",
+    "metadata": metadata(options),
+    "type": "Opaque",
+    "data": data,
+  }))
+}
+
+async fn external_secret_manifest(client: &Client, prefix: &str, options: &SecretOptions) -> Result<serde_json::Value> {
+  let data = referenceable_parameters(client, prefix, "ExternalSecrets")
+    .await?
+    .into_iter()
+    .map(|name| json!({ "secretKey": secret_key(name.trim_start_matches(prefix)), "remoteRef": { "key": name } }))
+    .collect::<Vec<_>>();
+
+  Ok(json!({
+    "apiVersion": EXTERNAL_SECRETS_API_VERSION,
+    "kind": "ExternalSecret",
+    "metadata": metadata(options),
+    "spec": {
+      "refreshInterval": DEFAULT_REFRESH_INTERVAL,
+      "secretStoreRef": { "name": options.secret_store, "kind": "ClusterSecretStore" },
+      "target": { "name": options.name },
+      "data": data,
+    },
+  }))
+}
+
+async fn secret_provider_class_manifest(client: &Client, prefix: &str, options: &SecretOptions) -> Result<serde_json::Value> {
+  let names = referenceable_parameters(client, prefix, "the Secrets Store CSI driver").await?;
+  let objects = names
+    .iter()
+    .map(|name| json!({ "objectName": name, "objectType": "ssmparameter", "objectAlias": secret_key(name.trim_start_matches(prefix)) }))
+    .collect::<Vec<_>>();
+  let data = names.iter().map(|name| secret_key(name.trim_start_matches(prefix))).map(|key| json!({ "key": key, "objectName": key })).collect::<Vec<_>>();
+
+  Ok(json!({
+    "apiVersion": SECRETS_STORE_CSI_API_VERSION,
+    "kind": "SecretProviderClass",
+    "metadata": metadata(options),
+    "spec": {
+      "provider": "aws",
+      "parameters": { "objects": serde_yaml::to_string(&objects)? },
+      "secretObjects": [{ "secretName": options.name, "type": "Opaque", "data": data }],
+    },
+  }))
+}
+
+fn metadata(options: &SecretOptions) -> serde_json::Value {
+  match &options.namespace {
+    Some(namespace) => json!({ "name": options.name, "namespace": namespace }),
+    None => json!({ "name": options.name }),
+  }
+}
@@ -0,0 +1,76 @@
+use anyhow::{Context, Result, ensure};
+use futures::TryStreamExt;
+
+use crate::files::physical_names;
+use crate::output::Event;
+use crate::ssm::{self, Client};
+
+pub async fn add(client: &Client, name: String, label: String, version: Option<i64>, dry_run: bool) -> Result<()> {
+  let names = physical_names(client, &name).await?;
+  ensure!(!names.is_empty(), "Parameter {name} not found");
+  if let Some(version) = version {
+    ensure!(names.len() == 1, "{name} is split across {} parameters, so version {version} is ambiguous; omit --version to label the latest", names.len());
+  }
+
+  for physical in &names {
+    if dry_run {
+      Event::new("would-label", physical.as_str()).with("label", label.as_str()).emit(format!("Would label {physical} as {label}"));
+      continue;
+    }
+
+    ssm::ensure_writable("ssm:LabelParameterVersion", physical)?;
+    let resp = ssm::retry_throttled(|| client.label_parameter_version().name(physical).set_parameter_version(version).labels(&label).send())
+      .await
+      .context(format!("Failed to label {physical}"))?;
+    ensure!(resp.invalid_labels().is_empty(), "Invalid label {label}: labels can't start with a number, \"aws\" or \"ssm\"");
+
+    Event::new("label", physical.as_str())
+      .with("label", label.as_str())
+      .with("version", resp.parameter_version())
+      .emit(format!("Labelled {physical} v{} as {label}", resp.parameter_version()));
+  }
+  Ok(())
+}
+
+pub async fn remove(client: &Client, name: String, label: String, dry_run: bool) -> Result<()> {
+  let names = physical_names(client, &name).await?;
+  ensure!(!names.is_empty(), "Parameter {name} not found");
+
+  for physical in &names {
+    let history = ssm::parameter_history(client, physical).try_collect::<Vec<_>>().await?;
+    let Some(version) = history.into_iter().flatten().find(|v| v.labels().contains(&label)).map(|v| v.version()) else {
+      tracing::warn!("{physical} has no version labelled {label}");
+      continue;
+    };
+
+    if dry_run {
+      Event::new("would-unlabel", physical.as_str()).with("label", label.as_str()).with("version", version).emit(format!("Would remove {label} from {physical} v{version}"));
+      continue;
+    }
+
+    ssm::ensure_writable("ssm:UnlabelParameterVersion", physical)?;
+    ssm::retry_throttled(|| client.unlabel_parameter_version().name(physical).parameter_version(version).labels(&label).send())
+      .await
+      .context(format!("Failed to unlabel {physical}"))?;
+    Event::new("unlabel", physical.as_str()).with("label", label.as_str()).with("version", version).emit(format!("Removed {label} from {physical} v{version}"));
+  }
+  Ok(())
+}
+
+pub async fn list(client: &Client, name: String) -> Result<()> {
+  let names = physical_names(client, &name).await?;
+  ensure!(!names.is_empty(), "Parameter {name} not found");
+
+  for physical in &names {
+    let history = ssm::parameter_history(client, physical).try_collect::<Vec<_>>().await?;
+    for version in history.into_iter().flatten() {
+      for label in version.labels() {
+        Event::new("label", physical.as_str())
+          .with("label", label.as_str())
+          .with("version", version.version())
+          .emit(format!("{physical}\tv{}\t{label}", version.version()));
+      }
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,58 @@
+pub mod apply;
+pub mod audit;
+pub mod backup;
+pub mod browse;
+pub mod cache;
+pub mod cancel;
+pub mod compose;
+pub mod compress;
+pub mod config;
+pub mod copy;
+pub mod crypto;
+pub mod delete;
+pub mod diff;
+pub mod ecs;
+pub mod download;
+pub mod edit;
+pub mod env;
+pub mod env_file;
+pub mod exec;
+pub mod export;
+pub mod files;
+pub mod filter;
+pub mod get;
+pub mod history;
+pub mod k8s;
+pub mod label;
+pub mod list;
+pub mod local;
+pub mod output;
+pub mod pin;
+pub mod policy;
+pub mod progress;
+pub mod promote;
+pub mod prompt;
+pub mod prune;
+pub mod put;
+pub mod rate_limit;
+pub mod render;
+pub mod rename;
+pub mod s3;
+pub mod search;
+pub mod secrets_manager;
+pub mod ssm;
+pub mod stats;
+pub mod store;
+pub mod sync;
+pub mod systemd;
+pub mod tags;
+pub mod terraform;
+pub mod upload;
+pub mod validate;
+pub mod watch;
+
+pub use copy::{CopyOptions, copy};
+pub use download::{DownloadOptions, DownloadSource, download_to_dir};
+pub use env::{EnvOptions, set_env};
+pub use ssm::{all_parameters_by_path, Client};
+pub use upload::{UploadOptions, upload_dir};
@@ -0,0 +1,41 @@
+use anyhow::Result;
+use futures::TryStreamExt;
+use itertools::Itertools;
+
+use aws_sdk_ssm::types::ParameterStringFilter;
+
+use crate::files::{MANIFEST_SUFFIX, is_selected, logical_name, matching_names, within_depth};
+use crate::output::Event;
+use crate::ssm::{self, Client};
+
+pub async fn list(client: &Client, prefix: String, long: bool, filters: Vec<ParameterStringFilter>, max_depth: Option<usize>) -> Result<()> {
+  let selected = matching_names(client, &prefix, &filters).await?;
+  let params = ssm::all_parameters_by_path(client, &prefix, max_depth != Some(1)).try_collect::<Vec<_>>().await?;
+
+  let entries = params
+    .into_iter()
+    .flatten()
+    .filter(|p| !p.name().unwrap_or_default().ends_with(MANIFEST_SUFFIX) && is_selected(&selected, p.name().unwrap_or_default()) && within_depth(&prefix, p.name().unwrap_or_default(), max_depth))
+    .into_group_map_by(|p| logical_name(p.name().unwrap_or_default()).to_string());
+
+  for (name, parts) in entries.into_iter().sorted_by(|(a, _), (b, _)| a.cmp(b)) {
+    if !long {
+      Event::new("list", name.as_str()).emit(&name);
+      continue;
+    }
+
+    let version = parts.iter().map(|p| p.version()).max().unwrap_or_default();
+    let modified = parts.iter().filter_map(|p| p.last_modified_date()).max().map(|d| d.to_string()).unwrap_or_default();
+    let r#type = parts.first().and_then(|p| p.r#type()).map(|t| t.as_str()).unwrap_or_default();
+    let chunks = if parts.len() > 1 { format!(" ({} parts)", parts.len()) } else { String::new() };
+
+    Event::new("list", name.as_str())
+      .with("version", version)
+      .with("type", r#type)
+      .with("last_modified", modified.as_str())
+      .with("parts", parts.len())
+      .emit(format!("{modified}\tv{version}\t{type}\t{name}{chunks}"));
+  }
+
+  Ok(())
+}
@@ -0,0 +1,66 @@
+use std::{fs, io::ErrorKind, path::{Path, PathBuf}};
+
+use anyhow::{Context, Result, ensure};
+use async_trait::async_trait;
+use walkdir::WalkDir;
+
+use crate::files::to_ssm_key;
+use crate::pin::Pin;
+use crate::store::{Entry, SecretStore};
+
+pub struct LocalStore {
+  root: PathBuf,
+}
+
+impl LocalStore {
+  pub fn new(root: PathBuf) -> Self {
+    Self { root }
+  }
+
+  fn path(&self, name: &str) -> PathBuf {
+    self.root.join(name.trim_start_matches('/'))
+  }
+}
+
+#[async_trait]
+impl SecretStore for LocalStore {
+  async fn fetch_prefix(&self, prefix: &str, pin: Option<&Pin>) -> Result<Vec<Entry>> {
+    ensure!(pin.is_none(), "Version pinning is only supported by the ssm backend");
+    let dir = self.path(prefix);
+    if !dir.is_dir() {
+      return Ok(vec![]);
+    }
+
+    WalkDir::new(&dir)
+      .into_iter()
+      .filter_map(Result::ok)
+      .filter(|e| e.file_type().is_file())
+      .map(|entry| {
+        let rel_path = entry.path().strip_prefix(&dir)?;
+        let value = fs::read(entry.path()).context(format!("Failed to read {}", entry.path().display()))?;
+        Ok(Entry { name: to_ssm_key(rel_path).trim_start_matches('/').to_string(), value, string_list: false })
+      })
+      .collect()
+  }
+
+  async fn fetch_names(&self, names: &[String], pin: Option<&Pin>) -> Result<(Vec<Entry>, Vec<String>)> {
+    ensure!(pin.is_none(), "Version pinning is only supported by the ssm backend");
+    let mut entries = vec![];
+    let mut missing = vec![];
+    for name in names {
+      match read_file(&self.path(name))? {
+        Some(value) => entries.push(Entry { name: name.clone(), value, string_list: false }),
+        None => missing.push(name.clone()),
+      }
+    }
+    Ok((entries, missing))
+  }
+}
+
+fn read_file(path: &Path) -> Result<Option<Vec<u8>>> {
+  match fs::read(path) {
+    Ok(value) => Ok(Some(value)),
+    Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+    Err(err) => Err(err).context(format!("Failed to read {}", path.display())),
+  }
+}
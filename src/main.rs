@@ -1,24 +1,62 @@
-use std::{
-  collections::HashMap,
-  fs,
-  path::{Path, PathBuf},
-};
+use std::{path::{Path, PathBuf}, time::Duration};
 
-use anyhow::{Context, Result};
-use aws_sdk_ssm::{Client, types::ParameterType};
-use clap::{Parser, Subcommand};
-use tokio::fs as tokio_fs;
-use walkdir::WalkDir;
-use futures::TryStreamExt;
+use aws_config::AppName;
+use aws_sdk_ssm::types::{ParameterStringFilter, ParameterTier, ParameterType, Tag};
+use tracing_subscriber::{EnvFilter, filter::LevelFilter};
+use anyhow::Context;
+use clap_complete::Shell;
+use clap::{ArgAction, ArgGroup, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 
-mod compose;
-mod ssm;
-
-const CHUNK_SIZE: usize = 4096;
+use ops::{CopyOptions, DownloadOptions, DownloadSource, EnvOptions, UploadOptions, copy, download_to_dir, set_env, upload_dir};
+use ops::config::{self, Profile};
+use ops::{apply, audit, backup, browse, cache, cancel, compress::Compression, compose::{self, ComposeOptions, Runtime, SecretDelivery}, crypto, delete, diff, ecs, download::{Overwrite, parse_mode}, edit, env::{self, KeyCase, KeyTransform, StringListMode}, env_file::EnvFormat, exec, export::{self, ExportFormat}, files::SymlinkPolicy, filter::PathFilter, get, history, k8s::{self, SecretFormat}, label, list, output::{self, OutputFormat}, pin::{self, Pin}, policy::{self, Policy}, promote, prune, put::{self, PutSource}, render, rename, s3::S3Target, search, ssm, stats, store::{self, Backend}, sync, systemd, tags::{self, parse_tag}, terraform, upload, validate::Syntax, watch};
 
 #[derive(Parser)]
 #[command(author, version, about)]
 struct Cli {
+  #[arg(long, global = true)]
+  region: Option<String>,
+  #[arg(long, global = true)]
+  profile: Option<String>,
+  #[arg(long, global = true)]
+  role_arn: Option<String>,
+  #[arg(long, global = true, requires = "role_arn")]
+  external_id: Option<String>,
+  #[arg(long, global = true, requires = "role_arn")]
+  mfa_serial: Option<String>,
+  #[arg(long, global = true, env = "OPS_ENDPOINT_URL")]
+  endpoint_url: Option<String>,
+  #[arg(long, global = true, env = "OPS_MAX_TPS", value_parser = parse_max_tps)]
+  max_tps: Option<f64>,
+  #[arg(long, global = true, env = "OPS_USER_AGENT_SUFFIX", value_parser = ssm::parse_user_agent_suffix)]
+  user_agent_suffix: Option<AppName>,
+  #[arg(long, global = true, env = "OPS_CHANGE_REASON", value_parser = tags::parse_change_reason)]
+  change_reason: Option<String>,
+  #[arg(long, global = true, env = "OPS_CACHE_TTL", value_name = "DURATION", value_parser = cache::parse_ttl)]
+  cache_ttl: Option<Duration>,
+  #[arg(long, global = true)]
+  no_cache: bool,
+  #[arg(long, global = true)]
+  dry_run: bool,
+  #[arg(long, global = true, env = "OPS_READ_ONLY")]
+  read_only: bool,
+  #[arg(long, global = true, value_enum, default_value_t)]
+  output: OutputFormat,
+  #[arg(long, global = true, value_enum, default_value_t)]
+  backend: Backend,
+  #[arg(long, global = true)]
+  bucket: Option<String>,
+  #[arg(long, global = true)]
+  root: Option<PathBuf>,
+  #[arg(long, global = true, env = "OPS_PROFILE")]
+  profile_name: Option<String>,
+  #[arg(long, global = true, env = "OPS_AGE_IDENTITY")]
+  age_identity: Option<PathBuf>,
+  #[arg(long, global = true, action = ArgAction::Count)]
+  verbose: u8,
+  #[arg(long, short, global = true, conflicts_with("verbose"))]
+  quiet: bool,
+
   #[command(subcommand)]
   command: Command,
 }
@@ -31,6 +69,89 @@ enum Command {
 
     #[arg(long)]
     prefix: String,
+
+    #[arg(long, default_value_t = 4)]
+    concurrency: usize,
+
+    #[arg(long)]
+    force: bool,
+
+    #[arg(long)]
+    include: Vec<String>,
+
+    #[arg(long)]
+    exclude: Vec<String>,
+
+    #[arg(long)]
+    gitignore: bool,
+    #[command(flatten)]
+    symlinks: SymlinkArgs,
+    #[arg(long)]
+    include_hidden: bool,
+    #[arg(long, value_name = "SIZE", value_parser = upload::parse_size)]
+    max_file_size: Option<u64>,
+    #[arg(long)]
+    preserve_mode: bool,
+    #[arg(long)]
+    preserve_mtime: bool,
+    #[arg(long)]
+    fail_fast: bool,
+    #[arg(long, value_enum)]
+    validate: Option<Syntax>,
+
+    #[command(flatten)]
+    parameter: ParameterArgs,
+  },
+  Sync {
+    #[arg(long)]
+    dir: PathBuf,
+    #[arg(long)]
+    prefix: String,
+    #[arg(long, conflicts_with("from_ssm"))]
+    delete: bool,
+    #[arg(long)]
+    from_ssm: bool,
+    #[arg(long, default_value_t = 4)]
+    concurrency: usize,
+    #[arg(long)]
+    include: Vec<String>,
+    #[arg(long)]
+    exclude: Vec<String>,
+    #[arg(long)]
+    gitignore: bool,
+    #[command(flatten)]
+    symlinks: SymlinkArgs,
+    #[arg(long)]
+    include_hidden: bool,
+    #[arg(long, value_name = "SIZE", value_parser = upload::parse_size)]
+    max_file_size: Option<u64>,
+    #[arg(long)]
+    preserve_mode: bool,
+    #[arg(long)]
+    preserve_mtime: bool,
+    #[arg(long)]
+    fail_fast: bool,
+    #[arg(long, value_enum)]
+    validate: Option<Syntax>,
+
+    #[command(flatten)]
+    parameter: ParameterArgs,
+  },
+  #[command(group(ArgGroup::new("source").required(true)))]
+  Put {
+    #[arg(long)]
+    name: String,
+    #[arg(long, group = "source")]
+    value: Option<String>,
+    #[arg(long, group = "source")]
+    value_from_stdin: bool,
+    #[arg(long, group = "source")]
+    file: Option<PathBuf>,
+    #[arg(long, value_enum)]
+    validate: Option<Syntax>,
+
+    #[command(flatten)]
+    parameter: ParameterArgs,
   },
   Download {
     #[arg(long, conflicts_with("name"), required_unless_present("name"))]
@@ -40,173 +161,884 @@ enum Command {
 
     #[arg(long)]
     dir: PathBuf,
+
+    #[arg(long, value_enum, default_value_t)]
+    overwrite: Overwrite,
+
+    #[arg(long, default_value_t = 4)]
+    concurrency: usize,
+    #[arg(long = "filter", value_name = "KEY:VALUE", value_parser = ssm::parse_filter, conflicts_with("name"))]
+    filters: Vec<ParameterStringFilter>,
+    #[command(flatten)]
+    depth: DepthArgs,
+  },
+  Get {
+    #[arg(long)]
+    name: String,
+    #[command(flatten)]
+    pin: PinArgs,
+  },
+  Edit {
+    #[arg(long)]
+    name: String,
+    #[arg(long, value_enum)]
+    validate: Option<Syntax>,
+
+    #[command(flatten)]
+    parameter: ParameterArgs,
   },
   Copy {
     #[arg(long)]
     prefix: String,
     #[arg(long)]
     to_prefix: String,
+    #[arg(long, value_enum)]
+    r#type: Option<ParamType>,
+    #[arg(long)]
+    kms_key_id: Option<String>,
+    #[arg(long)]
+    to_region: Option<String>,
+    #[arg(long)]
+    to_profile: Option<String>,
+    #[arg(long)]
+    to_role_arn: Option<String>,
+    #[arg(long = "tag", value_name = "KEY=VALUE", value_parser = parse_tag)]
+    tags: Vec<Tag>,
+    #[arg(long)]
+    preserve_tags: bool,
+    #[arg(long)]
+    include: Vec<String>,
+    #[arg(long)]
+    exclude: Vec<String>,
+    #[arg(long = "filter", value_name = "KEY:VALUE", value_parser = ssm::parse_filter)]
+    filters: Vec<ParameterStringFilter>,
+    #[command(flatten)]
+    depth: DepthArgs,
+    #[arg(long, short)]
+    yes: bool,
+  },
+  Delete {
+    #[arg(long)]
+    prefix: String,
+    #[arg(long = "filter", value_name = "KEY:VALUE", value_parser = ssm::parse_filter)]
+    filters: Vec<ParameterStringFilter>,
+    #[command(flatten)]
+    depth: DepthArgs,
+    #[arg(long, short)]
+    yes: bool,
+  },
+  Promote {
+    #[arg(long)]
+    from: String,
+    #[arg(long)]
+    to: String,
+    #[arg(long)]
+    all: bool,
+    #[arg(long)]
+    delete: bool,
+    #[arg(long)]
+    show_values: bool,
+    #[arg(long, default_value_t = 4)]
+    concurrency: usize,
+
+    #[command(flatten)]
+    parameter: ParameterArgs,
+  },
+  #[command(alias = "rename")]
+  Move {
+    #[arg(long)]
+    prefix: String,
+    #[arg(long)]
+    to_prefix: String,
+    #[arg(long, short)]
+    yes: bool,
+  },
+  Prune {
+    #[arg(long)]
+    prefix: String,
+  },
+  Tag {
+    #[arg(long)]
+    prefix: String,
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_tag)]
+    add: Vec<Tag>,
+    #[arg(long, value_name = "KEY")]
+    remove: Vec<String>,
+  },
+  History {
+    #[arg(long)]
+    name: String,
+  },
+  Rollback {
+    #[arg(long)]
+    name: String,
+    #[arg(long)]
+    version: i64,
+  },
+  Diff {
+    #[arg(long)]
+    prefix: String,
+    #[arg(long, conflicts_with("dir"), required_unless_present("dir"))]
+    to_prefix: Option<String>,
+    #[arg(long, conflicts_with("to_prefix"), required_unless_present("to_prefix"))]
+    dir: Option<PathBuf>,
+    #[command(flatten)]
+    scan: ScanArgs,
+    #[arg(long)]
+    show_values: bool,
+  },
+  List {
+    #[arg(long)]
+    prefix: String,
+    #[arg(long, short)]
+    long: bool,
+    #[arg(long = "filter", value_name = "KEY:VALUE", value_parser = ssm::parse_filter)]
+    filters: Vec<ParameterStringFilter>,
+    #[command(flatten)]
+    depth: DepthArgs,
+  },
+  Browse {
+    #[arg(long)]
+    prefix: String,
+  },
+  Export {
+    #[arg(long)]
+    prefix: String,
+    #[arg(long, value_enum)]
+    format: ExportFormat,
+    #[arg(long)]
+    out: Option<PathBuf>,
+  },
+  Import {
+    #[arg(long)]
+    file: PathBuf,
+    #[arg(long)]
+    prefix: String,
+    #[arg(long, value_enum)]
+    format: Option<ExportFormat>,
+    #[arg(long, default_value_t = 4)]
+    concurrency: usize,
+
+    #[command(flatten)]
+    parameter: ParameterArgs,
+  },
+  Backup {
+    #[arg(long)]
+    prefix: String,
+    #[arg(long)]
+    out: PathBuf,
+    #[arg(long = "recipient", value_name = "AGE_RECIPIENT", required = true, value_parser = crypto::parse_recipient)]
+    recipients: Vec<age::x25519::Recipient>,
+  },
+  Restore {
+    #[arg(long = "in", value_name = "FILE")]
+    input: PathBuf,
+    #[arg(long)]
+    identity: PathBuf,
+    #[arg(long)]
+    to_prefix: Option<String>,
+    #[arg(long)]
+    kms_key_id: Option<String>,
+  },
+  Apply {
+    #[arg(long)]
+    manifest: PathBuf,
+  },
+  Check {
+    #[arg(long, conflicts_with("dir"), required_unless_present("dir"))]
+    manifest: Option<PathBuf>,
+    #[arg(long, requires("prefix"))]
+    dir: Option<PathBuf>,
+    #[arg(long, requires("dir"))]
+    prefix: Option<String>,
+    #[command(flatten)]
+    scan: ScanArgs,
+  },
+  Stats {
+    #[arg(long)]
+    prefix: String,
+    #[arg(long, default_value_t = 10)]
+    top: usize,
+  },
+  Audit {
+    #[arg(long)]
+    prefix: String,
+    #[arg(long, default_value_t = 90)]
+    max_age_days: i64,
+    #[arg(long)]
+    strict: bool,
+  },
+  #[command(alias = "grep")]
+  Search {
+    #[arg(long)]
+    prefix: String,
+    #[arg(long)]
+    pattern: String,
+    #[arg(long)]
+    values: bool,
+    #[arg(long, short)]
+    ignore_case: bool,
   },
   Env {
     #[arg(long, short, env)]
-    file: String,
+    file: Option<String>,
+    #[arg(long, conflicts_with("merge"))]
+    stdout: bool,
+    #[arg(long, value_name = "UNIT", conflicts_with_all(["file", "stdout", "merge", "format"]))]
+    systemd_unit: Option<String>,
+    #[arg(long, default_value = systemd::SYSTEMD_DIR, requires("systemd_unit"))]
+    systemd_dir: PathBuf,
+    #[arg(long, requires("systemd_unit"))]
+    restart: bool,
     #[arg(long, short, env)]
     base: String,
-    #[arg(long, short, env, value_delimiter = ',')]
+    #[arg(long, short, env, value_delimiter = ',', required_unless_present("all"))]
     vars: Vec<String>,
+    #[arg(long, conflicts_with("vars"))]
+    all: bool,
+    #[arg(long, value_enum, default_value_t)]
+    format: EnvFormat,
+    #[arg(long, value_enum, default_value_t)]
+    string_list: StringListMode,
+    #[arg(long)]
+    list_delimiter: Option<String>,
+    #[arg(long, value_parser = parse_mode)]
+    mode: Option<u32>,
+    #[arg(long)]
+    allow_missing: bool,
+    #[arg(long = "default", value_name = "KEY=VALUE", value_parser = env::parse_default)]
+    defaults: Vec<(String, String)>,
+    #[arg(long)]
+    merge: bool,
+
+    #[command(flatten)]
+    keys: KeyArgs,
+    #[command(flatten)]
+    pin: PinArgs,
+  },
+  Exec {
+    #[arg(long, short, env)]
+    base: String,
+    #[command(flatten)]
+    keys: KeyArgs,
+    #[command(flatten)]
+    pin: PinArgs,
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true, required = true)]
+    command: Vec<String>,
+  },
+  Watch {
+    #[arg(long)]
+    prefix: String,
+    #[arg(long, default_value_t = 30, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    interval: u64,
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true, required = true)]
+    command: Vec<String>,
+  },
+  Completions {
+    #[arg(value_enum)]
+    shell: Shell,
+  },
+  Manpages {
+    #[arg(long)]
+    out_dir: PathBuf,
+  },
+  Render {
+    #[arg(long)]
+    template: PathBuf,
+    #[arg(long)]
+    out: Option<PathBuf>,
+    #[arg(long, value_parser = parse_mode)]
+    mode: Option<u32>,
+  },
+  K8s {
+    #[command(subcommand)]
+    command: K8sCommand,
+  },
+  Ecs {
+    #[command(subcommand)]
+    command: EcsCommand,
+  },
+  Label {
+    #[command(subcommand)]
+    command: LabelCommand,
+  },
+  Terraform {
+    #[command(subcommand)]
+    command: TerraformCommand,
+  },
+  Cache {
+    #[command(subcommand)]
+    command: CacheCommand,
   },
   Compose {
-    #[arg(long, short)]
-    file: String,
+    #[arg(long, short, required = true)]
+    file: Vec<String>,
     #[arg(long, short)]
     namespace: String,
-    #[arg(trailing_var_arg = true)]
+    #[arg(long)]
+    show_values: bool,
+    #[arg(long, value_enum, default_value_t)]
+    runtime: Runtime,
+    #[arg(long, value_enum, default_value_t)]
+    secrets_as: SecretDelivery,
+    #[arg(long, conflicts_with("secrets_as"))]
+    swarm: bool,
+    #[arg(long)]
+    path_template: Option<String>,
+    #[arg(long)]
+    allow_missing: bool,
+    #[arg(long, value_name = "PROFILE")]
+    compose_profile: Vec<String>,
+    #[arg(long)]
+    project_name: Option<String>,
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
     args: Vec<String>,
   }
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-  let cli = Cli::parse();
-  let client = ssm::client().await;
-  dbg!(&cli.command);
+impl Command {
+  fn uses_store(&self) -> bool {
+    matches!(self, Command::Env { .. } | Command::Exec { .. } | Command::Compose { .. })
+  }
 
-  match cli.command {
-    Command::Upload { dir, prefix } => upload_dir(&client, dir, prefix).await?,
-    Command::Download { prefix, dir, name } => download_to_dir(&client, prefix, name, dir).await?,
-    Command::Env{ file, base, vars } => set_env(&client, file, base, vars).await?,
-    Command::Copy { prefix, to_prefix } => copy(&client, prefix, to_prefix).await?,
-    Command::Compose { file, namespace, args } => compose::exec_compose(&client, &file, &namespace, args).await?,
+  fn writes(&self) -> bool {
+    match self {
+      Command::Upload { .. }
+      | Command::Put { .. }
+      | Command::Edit { .. }
+      | Command::Copy { .. }
+      | Command::Delete { .. }
+      | Command::Promote { .. }
+      | Command::Move { .. }
+      | Command::Prune { .. }
+      | Command::Rollback { .. }
+      | Command::Import { .. }
+      | Command::Apply { .. }
+      | Command::Restore { .. } => true,
+      Command::Sync { from_ssm, .. } => !from_ssm,
+      Command::Tag { add, remove, .. } => !add.is_empty() || !remove.is_empty(),
+      Command::Label { command } => !matches!(command, LabelCommand::List { .. }),
+      Command::Ecs { command: EcsCommand::PatchTaskdef { register, .. } } => *register,
+      _ => false,
+    }
   }
+}
 
-  Ok(())
+#[derive(Debug, Subcommand)]
+enum CacheCommand {
+  Clear,
 }
 
-async fn upload_dir(client: &Client, dir: PathBuf, prefix: String) -> anyhow::Result<()> {
-  for entry in WalkDir::new(&dir).into_iter().filter_map(Result::ok).filter(|e| e.file_type().is_file()) {
-    let rel_path = entry.path().strip_prefix(&dir)?;
-    let content = tokio_fs::read(entry.path()).await?;
-
-    let param_base = format!("{}{}", prefix.trim_end_matches('/'), to_ssm_key(rel_path));
-
-    if content.len() > CHUNK_SIZE {
-      for (i, chunk) in content.chunks(CHUNK_SIZE).enumerate() {
-        let key = format!("{}.part{}", param_base, i);
-        client
-          .put_parameter()
-          .name(&key)
-          .value(String::from_utf8_lossy(chunk))
-          .overwrite(true)
-          .r#type(ParameterType::SecureString)
-          .send()
-          .await?;
-      }
-    } else {
-      client
-        .put_parameter()
-        .name(&param_base)
-        .value(String::from_utf8_lossy(&content))
-        .overwrite(true)
-        .r#type(ParameterType::SecureString)
-        .send()
-        .await?;
+#[derive(Debug, Subcommand)]
+enum LabelCommand {
+  Add {
+    #[arg(long)]
+    name: String,
+    #[arg(long)]
+    label: String,
+    #[arg(long)]
+    version: Option<i64>,
+  },
+  Remove {
+    #[arg(long)]
+    name: String,
+    #[arg(long)]
+    label: String,
+  },
+  List {
+    #[arg(long)]
+    name: String,
+  },
+}
+
+#[derive(Debug, Subcommand)]
+enum EcsCommand {
+  PatchTaskdef {
+    #[arg(long)]
+    taskdef: PathBuf,
+    #[arg(long)]
+    base: String,
+    #[arg(long)]
+    container: Option<String>,
+    #[arg(long)]
+    out: Option<PathBuf>,
+    #[arg(long)]
+    register: bool,
+    #[command(flatten)]
+    keys: KeyArgs,
+  },
+}
+
+#[derive(Debug, Subcommand)]
+enum TerraformCommand {
+  ImportBlocks {
+    #[arg(long)]
+    prefix: String,
+    #[arg(long)]
+    out: Option<PathBuf>,
+  },
+}
+
+#[derive(Debug, Subcommand)]
+enum K8sCommand {
+  Secret {
+    #[arg(long)]
+    prefix: String,
+    #[arg(long)]
+    name: String,
+    #[arg(long)]
+    namespace: Option<String>,
+    #[arg(long, value_enum, default_value_t)]
+    format: SecretFormat,
+    #[arg(long, default_value = "aws-parameter-store")]
+    secret_store: String,
+  },
+}
+
+#[derive(Debug, Args)]
+#[command(group(ArgGroup::new("pin").multiple(false)))]
+struct PinArgs {
+  #[arg(long, value_name = "VERSION|LABEL", value_parser = pin::parse_selector, group = "pin")]
+  at_version: Option<Pin>,
+  #[arg(long, group = "pin")]
+  label: Option<String>,
+  #[arg(long, value_name = "TIMESTAMP", value_parser = pin::parse_as_of, group = "pin")]
+  as_of: Option<Pin>,
+}
+
+impl From<PinArgs> for Option<Pin> {
+  fn from(args: PinArgs) -> Self {
+    args.at_version.or(args.label.map(Pin::Label)).or(args.as_of)
+  }
+}
+
+#[derive(Debug, Args)]
+struct DepthArgs {
+  #[arg(long, conflicts_with("max_depth"))]
+  no_recursive: bool,
+  #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+  max_depth: Option<u64>,
+}
+
+impl From<DepthArgs> for Option<usize> {
+  fn from(args: DepthArgs) -> Self {
+    if args.no_recursive { Some(1) } else { args.max_depth.map(|depth| depth as usize) }
+  }
+}
+
+#[derive(Debug, Args)]
+struct ScanArgs {
+  #[arg(long, requires("dir"))]
+  include: Vec<String>,
+  #[arg(long, requires("dir"))]
+  exclude: Vec<String>,
+  #[arg(long, requires("dir"))]
+  gitignore: bool,
+  #[command(flatten)]
+  symlinks: SymlinkArgs,
+  #[arg(long, requires("dir"))]
+  include_hidden: bool,
+  #[arg(long, value_name = "SIZE", value_parser = upload::parse_size, requires("dir"))]
+  max_file_size: Option<u64>,
+  #[arg(long, value_enum, requires("dir"))]
+  r#type: Option<ParamType>,
+}
+
+impl From<ScanArgs> for UploadOptions {
+  fn from(args: ScanArgs) -> Self {
+    let ScanArgs { include, exclude, gitignore, symlinks, include_hidden, max_file_size, r#type } = args;
+    let r#type = r#type.unwrap_or(ParamType::SecureString).into();
+    UploadOptions { include, exclude, gitignore, symlinks: symlinks.into(), include_hidden, max_file_size, r#type, ..Default::default() }
+  }
+}
+
+#[derive(Debug, Args)]
+struct SymlinkArgs {
+  #[arg(long, conflicts_with("skip_symlinks"))]
+  follow_symlinks: bool,
+  #[arg(long)]
+  skip_symlinks: bool,
+}
+
+impl From<SymlinkArgs> for SymlinkPolicy {
+  fn from(args: SymlinkArgs) -> Self {
+    match (args.follow_symlinks, args.skip_symlinks) {
+      (true, _) => SymlinkPolicy::Follow,
+      (_, true) => SymlinkPolicy::Skip,
+      _ => SymlinkPolicy::Preserve,
     }
   }
-  Ok(())
 }
 
-async fn download_to_dir(client: &Client, prefix: Option<String>, name: Option<String>, output_dir: PathBuf) -> anyhow::Result<()> {
-  let parameters = match (prefix, name) {
-    (Some(prefix), _) => { 
-      let params = ssm::all_parameters_by_path(client, &prefix).try_collect::<Vec<_>>().await?.into_iter().flatten();
+#[derive(Debug, Args)]
+struct KeyArgs {
+  #[arg(long, value_enum, default_value_t)]
+  case: KeyCase,
+  #[arg(long = "prefix", value_name = "PREFIX")]
+  key_prefix: Option<String>,
+  #[arg(long, default_value_t = 0)]
+  strip_path_components: usize,
+  #[arg(long, value_name = "SSM_NAME=ENV_NAME", value_parser = env::parse_mapping)]
+  map: Vec<(String, String)>,
+}
 
-      let mut parameters: HashMap<String, Vec<(usize, String)>> = HashMap::new();
-      for param in params {
-        let name = param.name().unwrap().to_string();
-        let rel_path = name.trim_start_matches(&format!("{prefix}/"));
-        let content = param.value().unwrap().to_string();
+impl From<KeyArgs> for KeyTransform {
+  fn from(args: KeyArgs) -> Self {
+    KeyTransform { case: args.case, prefix: args.key_prefix, strip_path_components: args.strip_path_components, map: args.map }
+  }
+}
 
-        if let Some((base, part)) = rel_path.rsplit_once(".part") {
-          let idx: usize = part.parse()?;
-          parameters.entry(base.to_string()).or_default().push((idx, content));
-        } else {
-          parameters.entry(rel_path.to_string()).or_default().push((0, content));
-        }
-      }
-      parameters
-    },
-    (_, Some(name)) => {
-      let resp = client.get_parameter().name(name).with_decryption(true).send().await?;
-      resp.parameter().into_iter().map(|p| (p.name().unwrap().rsplit('/').nth(0).unwrap().to_string(), p.value().into_iter().map(|v|(0, v.to_string())).collect())).collect()
-    },
-    _ => { [].into() }
-  };
+#[derive(Debug, Args)]
+struct ParameterArgs {
+  #[arg(long, value_enum)]
+  r#type: Option<ParamType>,
+  #[arg(long)]
+  kms_key_id: Option<String>,
+  #[arg(long, value_enum)]
+  tier: Option<Tier>,
+  #[arg(long)]
+  chunk_size: Option<usize>,
+  #[arg(long)]
+  max_parts: Option<usize>,
+  #[arg(long = "tag", value_name = "KEY=VALUE", value_parser = parse_tag)]
+  tags: Vec<Tag>,
+  #[arg(long = "policy", value_name = "KIND=VALUE", value_parser = policy::parse_policy)]
+  policies: Vec<Policy>,
+  #[arg(long, value_name = "age:RECIPIENT", value_parser = crypto::parse_encrypt_with)]
+  encrypt_with: Vec<age::x25519::Recipient>,
+  #[arg(long, value_enum)]
+  compress: Option<Compression>,
+}
+
+impl ParameterArgs {
+  fn upload_options(self, concurrency: usize, force: bool, dry_run: bool) -> UploadOptions {
+    UploadOptions {
+      concurrency,
+      force,
+      r#type: self.r#type.unwrap_or(ParamType::SecureString).into(),
+      kms_key_id: self.kms_key_id,
+      tier: self.tier.unwrap_or(Tier::Standard).into(),
+      chunk_size: self.chunk_size,
+      max_parts: self.max_parts,
+      tags: self.tags,
+      policies: self.policies,
+      encrypt_with: self.encrypt_with,
+      compress: self.compress,
+      dry_run,
+      ..Default::default()
+    }
+  }
+}
 
-  for (rel_path, mut chunks) in parameters {
-    chunks.sort_by_key(|(i, _)| *i);
-    let content: String = chunks.into_iter().map(|(_, c)| c).collect();
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ParamType {
+  String,
+  #[value(name = "securestring")]
+  SecureString,
+  #[value(name = "stringlist")]
+  StringList,
+}
 
-    let full_path = output_dir.join(rel_path);
-    if let Some(parent) = full_path.parent() {
-      fs::create_dir_all(parent)?;
+impl From<ParamType> for ParameterType {
+  fn from(value: ParamType) -> Self {
+    match value {
+      ParamType::String => ParameterType::String,
+      ParamType::SecureString => ParameterType::SecureString,
+      ParamType::StringList => ParameterType::StringList,
     }
-    fs::write(full_path, content)?;
   }
+}
 
-  Ok(())
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Tier {
+  Standard,
+  Advanced,
+  IntelligentTiering,
 }
 
-fn to_ssm_key(path: &Path) -> String {
-  let mut key = String::new();
-  for comp in path.components() {
-    key.push('/');
-    key.push_str(&comp.as_os_str().to_string_lossy());
+impl From<Tier> for ParameterTier {
+  fn from(value: Tier) -> Self {
+    match value {
+      Tier::Standard => ParameterTier::Standard,
+      Tier::Advanced => ParameterTier::Advanced,
+      Tier::IntelligentTiering => ParameterTier::IntelligentTiering,
+    }
   }
-  key
 }
 
-pub async fn set_env(client: &Client, file: String, base: String, vars: Vec<String>) -> Result<()> {
-  println!("Getting vars {vars:?} from {base}");
-  let resp = client
-    .get_parameters()
-    .set_names(Some(vars.iter().map(|v| format!("{base}/{v}")).collect()))
-    .with_decryption(true)
-    .send()
-    .await
-    .context("Failed to fetch parameters from SSM")?;
+fn init_logging(verbose: u8, quiet: bool) {
+  let level = match (quiet, verbose) {
+    (true, _) => LevelFilter::WARN,
+    (false, 0) => LevelFilter::INFO,
+    (false, 1) => LevelFilter::DEBUG,
+    (false, _) => LevelFilter::TRACE,
+  };
+  let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(format!("warn,ops={level}")));
 
-  let output = resp.parameters().iter().map(|p| {
-    let name = p.name().unwrap_or_default();
-    let value = p.value().unwrap_or_default();
+  tracing_subscriber::fmt()
+    .with_env_filter(filter)
+    .with_writer(std::io::stderr)
+    .with_target(false)
+    .without_time()
+    .init();
+}
 
-    let key = name.rsplit('/').next().unwrap_or(&name).to_ascii_uppercase();
+fn parse_cli() -> anyhow::Result<Cli> {
+  let profile = match profile_name(std::env::args()) {
+    Some(name) => config::load_profile(&name)?,
+    None => Profile::default(),
+  };
 
-    format!("{key}=\"{value}\"")
-  }).collect::<Vec<_>>().join("\n");
+  let command = with_defaults(Cli::command(), &profile.defaults());
+  Ok(Cli::from_arg_matches(&command.get_matches()).unwrap_or_else(|err| err.exit()))
+}
 
-  println!("Writing to file {file}");
-  fs::write(&file, output).context(format!("Failed to write to {file}"))?;
+fn profile_name(args: impl Iterator<Item = String>) -> Option<String> {
+  let mut args = args.skip(1);
+  while let Some(arg) = args.next() {
+    if arg == "--" {
+      break;
+    }
+    if arg == "--profile-name" {
+      return args.next();
+    }
+    if let Some(name) = arg.strip_prefix("--profile-name=") {
+      return Some(name.to_string());
+    }
+  }
+  std::env::var("OPS_PROFILE").ok()
+}
 
+fn clear_cache() -> anyhow::Result<()> {
+  let dir = cache::default_dir().context("Can't find a cache directory; set XDG_CACHE_HOME")?;
+  let removed = cache::clear(&dir)?;
+  output::Event::new("clear", dir.display().to_string()).with("removed", removed).emit(format!("Removed {removed} cache files from {}", dir.display()));
   Ok(())
 }
 
-pub async fn copy(client: &Client, prefix: String, to_prefix: String) -> Result<()> {
-  let params = ssm::all_parameters_by_path(client, &prefix).try_collect::<Vec<_>>().await?;
+fn parse_max_tps(value: &str) -> anyhow::Result<f64> {
+  let max_tps = value.parse::<f64>()?;
+  anyhow::ensure!(max_tps.is_finite() && max_tps > 0.0, "--max-tps must be a positive number");
+  Ok(max_tps)
+}
+
+fn with_defaults(mut command: clap::Command, defaults: &[(&'static str, String)]) -> clap::Command {
+  for (id, value) in defaults {
+    if command.get_arguments().any(|arg| arg.get_id() == id) {
+      command = command.mut_arg(*id, |arg| arg.default_value(value.clone()).required(false));
+    }
+  }
+
+  let subcommands = command.get_subcommands().map(|sub| sub.get_name().to_string()).collect::<Vec<_>>();
+  subcommands.into_iter().fold(command, |command, name| command.mut_subcommand(name, |sub| with_defaults(sub, defaults)))
+}
+
+fn write_completions(shell: Shell) -> anyhow::Result<()> {
+  let mut command = Cli::command();
+  let name = command.get_name().to_string();
+  clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+  Ok(())
+}
+
+fn write_manpages(out_dir: &Path) -> anyhow::Result<()> {
+  std::fs::create_dir_all(out_dir).context(format!("Failed to create {}", out_dir.display()))?;
+  let mut command = Cli::command();
+  command.build();
+  write_manpage(&command, out_dir, command.get_name())
+}
+
+fn write_manpage(command: &clap::Command, out_dir: &Path, name: &str) -> anyhow::Result<()> {
+  let path = out_dir.join(format!("{name}.1"));
+  let mut file = std::fs::File::create(&path).context(format!("Failed to create {}", path.display()))?;
+  clap_mangen::Man::new(command.clone().name(name.to_string())).render(&mut file)?;
+  output::Event::new("write", path.display().to_string()).emit(format!("Wrote {}", path.display()));
+
+  for sub in command.get_subcommands().filter(|sub| !sub.is_hide_set() && sub.get_name() != "help") {
+    write_manpage(sub, out_dir, &format!("{name}-{}", sub.get_name()))?;
+  }
+  Ok(())
+}
 
-  for param in params.into_iter().flatten() {
-    let name = param.name().unwrap();
-    let value = param.value().unwrap();
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+  match run().await.map_err(ssm::explain_access_denied) {
+    Err(err) if let Some(code) = cancel::exit_code() => {
+      eprintln!("Error: {err:#}");
+      std::process::exit(code);
+    },
+    result => result,
+  }
+}
 
-    let new_name = format!("{}{}", to_prefix, name.trim_start_matches(&prefix));
+async fn run() -> anyhow::Result<()> {
+  let cli = parse_cli()?;
+  output::set_format(cli.output);
+  match &cli.command {
+    Command::Completions { shell } => return write_completions(*shell),
+    Command::Manpages { out_dir } => return write_manpages(out_dir),
+    Command::Cache { command: CacheCommand::Clear } => return clear_cache(),
+    _ => {},
+  }
+  output::set_quiet(cli.quiet);
+  init_logging(cli.verbose, cli.quiet);
+  cancel::install();
+  anyhow::ensure!(!cli.read_only || cli.dry_run || !cli.command.writes(), "This command modifies parameters and can't run with --read-only");
+  ssm::set_read_only(cli.read_only);
+  if let Some(reason) = &cli.change_reason {
+    tags::set_change_reason(reason)?;
+  }
+  if let Some(path) = &cli.age_identity {
+    crypto::set_identities(path)?;
+  }
+  let client_options = ssm::ClientOptions {
+    region: cli.region,
+    profile: cli.profile,
+    role_arn: cli.role_arn,
+    external_id: cli.external_id,
+    mfa_serial: cli.mfa_serial,
+    endpoint_url: cli.endpoint_url,
+    max_tps: cli.max_tps,
+    user_agent_suffix: cli.user_agent_suffix,
+  };
+  let config = ssm::sdk_config(client_options.clone()).await?;
+  let client = ssm::client(&config, &client_options);
+  anyhow::ensure!(cli.backend.is_ssm() || cli.command.uses_store(), "This command only supports the ssm and s3 backends");
+  let mut store = store::store_with(cli.backend, &config, &client, cli.root.as_deref())?;
+  if let Some(ttl) = cli.cache_ttl.filter(|_| !cli.no_cache && cli.command.uses_store()) {
+    let dir = cache::default_dir().context("Can't find a cache directory; set XDG_CACHE_HOME")?;
+    let namespace = format!("{:?}|{:?}|{}", cli.backend, cli.root, ssm::cache_namespace(&config, &client_options).await?);
+    store = Box::new(cache::CachedStore::new(store, cache::CacheOptions { dir, ttl, namespace })?);
+  }
+  let s3_client = aws_sdk_s3::Client::new(&config);
+  let s3_target = match cli.backend {
+    Backend::S3 => Some(S3Target { client: s3_client.clone(), bucket: cli.bucket.context("--bucket is required with --backend s3")? }),
+    _ => None,
+  };
 
-    client
-      .put_parameter()
-      .name(new_name)
-      .value(value)
-      .overwrite(true)
-      .r#type(param.r#type().unwrap().clone())
-      .send()
-      .await?;
+  match cli.command {
+    Command::Upload { dir, prefix, concurrency, force, include, exclude, gitignore, symlinks, include_hidden, max_file_size, preserve_mode, preserve_mtime, fail_fast, validate, parameter } => {
+      let options = UploadOptions { include, exclude, gitignore, symlinks: symlinks.into(), include_hidden, max_file_size, preserve_mode, preserve_mtime, fail_fast, validate, s3: s3_target, ..parameter.upload_options(concurrency, force, cli.dry_run) };
+      upload_dir(&client, dir, prefix, options).await?
+    },
+    Command::Sync { dir, prefix, delete, from_ssm, concurrency, include, exclude, gitignore, symlinks, include_hidden, max_file_size, preserve_mode, preserve_mtime, fail_fast, validate, parameter } => {
+      let options = UploadOptions { include, exclude, gitignore, symlinks: symlinks.into(), include_hidden, max_file_size, preserve_mode, preserve_mtime, fail_fast, validate, s3: s3_target, ..parameter.upload_options(concurrency, false, cli.dry_run) };
+      if from_ssm {
+        sync::sync_from_ssm(&client, Some(&s3_client), prefix, dir, options).await?
+      } else {
+        sync::sync_to_ssm(&client, dir, prefix, options, delete).await?
+      }
+    },
+    Command::Put { name, value, value_from_stdin, file, validate, parameter } => {
+      let source = match (value, file) {
+        (Some(value), _) => PutSource::Value(value),
+        (_, Some(file)) => PutSource::File(file),
+        _ if value_from_stdin => PutSource::Stdin,
+        _ => anyhow::bail!("One of --value, --value-from-stdin or --file is required"),
+      };
+      put::put(&client, name, source, UploadOptions { validate, s3: s3_target, ..parameter.upload_options(1, true, cli.dry_run) }).await?
+    },
+    Command::Download { prefix, dir, name, overwrite, concurrency, filters, depth } => {
+      let source = match (prefix, name) {
+        (_, Some(name)) => DownloadSource::Name(name),
+        (Some(prefix), _) => DownloadSource::Prefix(prefix),
+        _ => unreachable!("clap requires --prefix or --name"),
+      };
+      download_to_dir(&client, source, dir, DownloadOptions { overwrite, concurrency, s3: Some(s3_client), filters, max_depth: depth.into() }).await?
+    },
+    Command::Get { name, pin } => get::get(&client, name, pin.into()).await?,
+    Command::Edit { name, validate, parameter } => {
+      let (r#type, tier) = (parameter.r#type.map(Into::into), parameter.tier.map(Into::into));
+      let upload = UploadOptions { s3: s3_target, ..parameter.upload_options(1, true, cli.dry_run) };
+      edit::edit(&client, Some(&s3_client), name, edit::EditOptions { validate, r#type, tier, upload }).await?
+    },
+    Command::Env { file, stdout, systemd_unit, systemd_dir, restart, base, vars, all, format, string_list, list_delimiter, mode, allow_missing, defaults, merge, keys, pin } => {
+      let systemd = systemd_unit.map(|unit| systemd::SystemdOptions { unit, dir: systemd_dir, restart });
+      let format = if systemd.is_some() { EnvFormat::Systemd } else { format };
+      anyhow::ensure!(!stdout || format.github_file_var().is_none(), "--stdout can't be used with GitHub formats, which append to $GITHUB_ENV or $GITHUB_OUTPUT");
+      anyhow::ensure!(file.is_some() || stdout || systemd.is_some() || format.github_file_var().is_some(), "--file is required unless --stdout, --systemd-unit or a GitHub format is used");
+      let options = EnvOptions { vars, all, format, string_list, list_delimiter, mode, allow_missing, defaults, keys: keys.into(), merge, pin: pin.into(), systemd };
+      set_env(store.as_ref(), file.filter(|_| !stdout), base, options).await?
+    },
+    Command::Copy { prefix, to_prefix, r#type, kms_key_id, to_region, to_profile, to_role_arn, tags, preserve_tags, include, exclude, filters, depth, yes } => {
+      let (to_client, cross_region_or_account) = if to_region.is_some() || to_profile.is_some() || to_role_arn.is_some() {
+        let to_options = ssm::ClientOptions {
+          region: to_region.or(client_options.region),
+          profile: to_profile.or(client_options.profile),
+          role_arn: to_role_arn,
+          endpoint_url: client_options.endpoint_url.clone(),
+          max_tps: client_options.max_tps,
+          user_agent_suffix: client_options.user_agent_suffix.clone(),
+          ..Default::default()
+        };
+        let to_config = ssm::sdk_config(to_options.clone()).await?;
+        let cross_region_or_account = ssm::parameter_arn_prefix(&config).await? != ssm::parameter_arn_prefix(&to_config).await?;
+        (ssm::client(&to_config, &to_options), cross_region_or_account)
+      } else {
+        (client.clone(), false)
+      };
+      let filter = PathFilter::new(&include, &exclude)?;
+      let options = CopyOptions { r#type: r#type.map(Into::into), kms_key_id, tags, preserve_tags, filter, filters, max_depth: depth.into(), s3: s3_target, cross_region_or_account, yes, dry_run: cli.dry_run };
+      copy(&client, &to_client, prefix, to_prefix, options).await?
+    },
+    Command::Delete { prefix, filters, depth, yes } => delete::delete(&client, prefix, filters, depth.into(), yes, cli.dry_run).await?,
+    Command::Promote { from, to, all, delete, show_values, concurrency, parameter } => {
+      output::set_show_values(show_values);
+      let upload = UploadOptions { s3: s3_target, ..parameter.upload_options(concurrency, true, cli.dry_run) };
+      promote::promote(&client, from, to, promote::PromoteOptions { all, delete, upload }).await?
+    },
+    Command::Move { prefix, to_prefix, yes } => rename::rename(&client, prefix, to_prefix, s3_target, yes, cli.dry_run).await?,
+    Command::Prune { prefix } => prune::prune(&client, prefix, cli.dry_run).await?,
+    Command::Tag { prefix, add, remove } => tags::tag_prefix(&client, prefix, add, remove, cli.dry_run).await?,
+    Command::History { name } => history::history(&client, name).await?,
+    Command::Rollback { name, version } => history::rollback(&client, name, version, cli.dry_run).await?,
+    Command::Diff { prefix, to_prefix, dir, scan, show_values } => diff::diff(&client, Some(&s3_client), prefix, to_prefix, dir, scan.into(), show_values).await?,
+    Command::List { prefix, long, filters, depth } => list::list(&client, prefix, long, filters, depth.into()).await?,
+    Command::Browse { prefix } => browse::browse(&client, Some(&s3_client), prefix, cli.dry_run).await?,
+    Command::Export { prefix, format, out } => export::export(&client, Some(&s3_client), prefix, format, out).await?,
+    Command::Import { file, prefix, format, concurrency, parameter } => {
+      export::import(&client, file, prefix, format, UploadOptions { s3: s3_target, ..parameter.upload_options(concurrency, true, cli.dry_run) }).await?
+    },
+    Command::Backup { prefix, out, recipients } => backup::backup(&client, prefix, out, recipients).await?,
+    Command::Restore { input, identity, to_prefix, kms_key_id } => {
+      backup::restore(&client, input, identity, backup::RestoreOptions { to_prefix, kms_key_id, dry_run: cli.dry_run }).await?
+    },
+    Command::Apply { manifest } => apply::apply(&client, manifest, cli.dry_run).await?,
+    Command::Check { manifest, dir, prefix, scan } => match (manifest, dir, prefix) {
+      (Some(manifest), _, _) => apply::check(&client, manifest).await?,
+      (None, Some(dir), Some(prefix)) => apply::check_dir(&client, Some(&s3_client), dir, prefix, scan.into()).await?,
+      _ => unreachable!("clap requires --manifest or --dir with --prefix"),
+    },
+    Command::Stats { prefix, top } => stats::stats(&client, prefix, top).await?,
+    Command::Audit { prefix, max_age_days, strict } => audit::audit(&client, prefix, audit::AuditOptions { max_age_days, strict }).await?,
+    Command::Search { prefix, pattern, values, ignore_case } => {
+      search::search(&client, Some(&s3_client), prefix, pattern, search::SearchOptions { values, ignore_case }).await?
+    },
+    Command::Exec { base, keys, pin, command } => exec::exec(store.as_ref(), base, command, keys.into(), pin.into()).await?,
+    Command::Watch { prefix, interval, command } => {
+      watch::watch(&client, prefix, command, watch::WatchOptions { interval: Duration::from_secs(interval) }).await?
+    },
+    Command::Completions { .. } | Command::Manpages { .. } | Command::Cache { .. } => unreachable!("handled before building clients"),
+    Command::Render { template, out, mode } => render::render(&client, Some(&s3_client), template, render::RenderOptions { out, mode }).await?,
+    Command::Label { command: LabelCommand::Add { name, label, version } } => label::add(&client, name, label, version, cli.dry_run).await?,
+    Command::Label { command: LabelCommand::Remove { name, label } } => label::remove(&client, name, label, cli.dry_run).await?,
+    Command::Label { command: LabelCommand::List { name } } => label::list(&client, name).await?,
+    Command::Ecs { command: EcsCommand::PatchTaskdef { taskdef, base, container, out, register, keys } } => {
+      let options = ecs::PatchOptions { container, keys: keys.into(), out, register, dry_run: cli.dry_run };
+      ecs::patch_taskdef(&client, &config, taskdef, base, options).await?
+    },
+    Command::Terraform { command: TerraformCommand::ImportBlocks { prefix, out } } => terraform::import_blocks(&client, prefix, out).await?,
+    Command::K8s { command: K8sCommand::Secret { prefix, name, namespace, format, secret_store } } => {
+      k8s::secret(&client, Some(&s3_client), prefix, k8s::SecretOptions { name, namespace, format, secret_store }).await?
+    },
+    Command::Compose { file, namespace, show_values, runtime, secrets_as, swarm, path_template, allow_missing, compose_profile, project_name, args } => {
+      output::set_show_values(show_values);
+      let delivery = if swarm { SecretDelivery::Swarm } else { secrets_as };
+      let options = ComposeOptions { runtime, delivery, path_template, allow_missing, profiles: compose_profile, project_name };
+      let status = compose::exec_compose(store.as_ref(), &file, &namespace, args, options).await?;
+      if !status.success() {
+        std::process::exit(cancel::exit_code().or(status.code()).unwrap_or(1));
+      }
+    },
   }
 
   Ok(())
@@ -0,0 +1,79 @@
+use std::{fmt::{self, Display}, sync::{OnceLock, atomic::{AtomicBool, Ordering}}};
+
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+static FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+static QUIET: AtomicBool = AtomicBool::new(false);
+static SHOW_VALUES: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+  #[default]
+  Text,
+  Json,
+}
+
+pub fn set_format(format: OutputFormat) {
+  let _ = FORMAT.set(format);
+}
+
+pub fn is_json() -> bool {
+  FORMAT.get() == Some(&OutputFormat::Json)
+}
+
+pub fn set_quiet(quiet: bool) {
+  QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+  QUIET.load(Ordering::Relaxed)
+}
+
+pub fn set_show_values(show_values: bool) {
+  SHOW_VALUES.store(show_values, Ordering::Relaxed);
+}
+
+pub fn show_values() -> bool {
+  SHOW_VALUES.load(Ordering::Relaxed)
+}
+
+pub struct Redacted<'a>(pub &'a str);
+
+impl Display for Redacted<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if show_values() {
+      write!(f, "{}", self.0)
+    } else {
+      write!(f, "<redacted, {} bytes>", self.0.len())
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+  action: String,
+  name: String,
+  #[serde(flatten)]
+  fields: Map<String, Value>,
+}
+
+impl Event {
+  pub fn new(action: &str, name: impl Into<String>) -> Self {
+    Self { action: action.to_string(), name: name.into(), fields: Map::new() }
+  }
+
+  pub fn with(mut self, key: &str, value: impl Into<Value>) -> Self {
+    self.fields.insert(key.to_string(), value.into());
+    self
+  }
+
+  pub fn emit(self, message: impl Display) {
+    if is_json() {
+      println!("{}", serde_json::to_string(&self).expect("events serialize to JSON"));
+    } else if !is_quiet() {
+      println!("{message}");
+    }
+  }
+}
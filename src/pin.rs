@@ -0,0 +1,110 @@
+use std::fmt;
+
+use anyhow::{Context, Result, bail};
+use aws_sdk_ssm::primitives::{DateTime, DateTimeFormat};
+use aws_sdk_ssm::types::Parameter;
+use futures::{TryStreamExt, future::try_join_all};
+
+use crate::ssm::{self, Client};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pin {
+  Version(i64),
+  Label(String),
+  AsOf(DateTime),
+}
+
+impl fmt::Display for Pin {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Pin::Version(version) => write!(f, "version {version}"),
+      Pin::Label(label) => write!(f, "label {label}"),
+      Pin::AsOf(time) => write!(f, "as of {}", time.fmt(DateTimeFormat::DateTime).unwrap_or_default()),
+    }
+  }
+}
+
+pub fn parse_selector(value: &str) -> Result<Pin> {
+  match value.parse::<i64>() {
+    Ok(version) if version > 0 => Ok(Pin::Version(version)),
+    Ok(_) => bail!("Invalid version {value}; versions start at 1"),
+    Err(_) if !value.is_empty() => Ok(Pin::Label(value.to_string())),
+    Err(_) => bail!("Empty version selector"),
+  }
+}
+
+pub fn parse_as_of(value: &str) -> Result<Pin> {
+  let time = match value.parse::<i64>() {
+    Ok(seconds) => DateTime::from_secs(seconds),
+    Err(_) => DateTime::from_str(value, DateTimeFormat::DateTime).context(format!("Invalid timestamp {value}; expected RFC 3339 or epoch seconds"))?,
+  };
+  Ok(Pin::AsOf(time))
+}
+
+pub fn split_selector(name: &str) -> Result<(&str, Option<Pin>)> {
+  match name.rsplit_once(':') {
+    Some((name, selector)) => Ok((name, Some(parse_selector(selector)?))),
+    None => Ok((name, None)),
+  }
+}
+
+pub fn selector(pin: &Pin) -> Option<String> {
+  match pin {
+    Pin::Version(version) => Some(version.to_string()),
+    Pin::Label(label) => Some(label.clone()),
+    Pin::AsOf(_) => None,
+  }
+}
+
+pub async fn get_parameters(client: &Client, names: &[String], pin: Option<&Pin>) -> Result<(Vec<Parameter>, Vec<String>)> {
+  let mut selected = vec![];
+  let mut as_of = vec![];
+  for name in names {
+    let (base, own) = split_selector(name)?;
+    match own.as_ref().or(pin) {
+      Some(Pin::AsOf(time)) => as_of.push((base.to_string(), *time)),
+      Some(pin) => selected.push(format!("{base}:{}", selector(pin).unwrap_or_default())),
+      None => selected.push(base.to_string()),
+    }
+  }
+
+  let (mut parameters, invalid) = ssm::get_parameters(client, &selected).await?;
+  for parameter in &mut parameters {
+    parameter.name = parameter.name.take().map(|name| name.split(':').next().unwrap_or_default().to_string());
+  }
+  let mut invalid = invalid.into_iter().map(|name| name.split(':').next().unwrap_or_default().to_string()).collect::<Vec<_>>();
+
+  let found = try_join_all(as_of.iter().map(|(name, time)| parameter_as_of(client, name, time))).await?;
+  for ((name, _), parameter) in as_of.into_iter().zip(found) {
+    match parameter {
+      Some(parameter) => parameters.push(parameter),
+      None => invalid.push(name),
+    }
+  }
+  Ok((parameters, invalid))
+}
+
+pub async fn parameter_as_of(client: &Client, name: &str, time: &DateTime) -> Result<Option<Parameter>> {
+  let history = ssm::parameter_history(client, name).try_collect::<Vec<_>>().await;
+  let history = match history {
+    Ok(history) => history.into_iter().flatten().collect::<Vec<_>>(),
+    Err(err) if is_not_found(&err) => return Ok(None),
+    Err(err) => return Err(err).context(format!("Failed to read history of {name}")),
+  };
+
+  let entry = history.into_iter().filter(|h| h.last_modified_date().is_some_and(|modified| modified <= time)).max_by_key(|h| h.version());
+  Ok(entry.map(|h| {
+    Parameter::builder()
+      .name(name)
+      .set_type(h.r#type().cloned())
+      .set_value(h.value().map(str::to_string))
+      .version(h.version())
+      .set_last_modified_date(h.last_modified_date().cloned())
+      .set_data_type(h.data_type().map(str::to_string))
+      .build()
+  }))
+}
+
+fn is_not_found(err: &anyhow::Error) -> bool {
+  err.chain().filter_map(|cause| cause.downcast_ref::<aws_sdk_ssm::error::ErrorMetadata>()).any(|meta| meta.code() == Some("ParameterNotFound"))
+}
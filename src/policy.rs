@@ -0,0 +1,91 @@
+use std::time::SystemTime;
+
+use anyhow::{Context, Result, bail, ensure};
+use aws_sdk_ssm::primitives::{DateTime, DateTimeFormat};
+use serde_json::{Value, json};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Policy {
+  Expiration(DateTime),
+  ExpirationNotification(Interval),
+  NoChangeNotification(Interval),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+  pub amount: u32,
+  pub unit: Unit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Unit {
+  Days,
+  Hours,
+}
+
+impl Unit {
+  fn as_str(&self) -> &'static str {
+    match self {
+      Unit::Days => "Days",
+      Unit::Hours => "Hours",
+    }
+  }
+
+  fn seconds(&self) -> i64 {
+    match self {
+      Unit::Days => 24 * 60 * 60,
+      Unit::Hours => 60 * 60,
+    }
+  }
+}
+
+pub fn parse_policy(value: &str) -> Result<Policy> {
+  let (kind, rest) = value.split_once('=').context(format!("Invalid policy {value}; expected e.g. expiration=2025-12-31T00:00:00Z or no-change-notification=30d"))?;
+  match kind {
+    "expiration" => Ok(Policy::Expiration(parse_expiration(rest)?)),
+    "expiration-notification" => Ok(Policy::ExpirationNotification(parse_interval(rest)?)),
+    "no-change-notification" => Ok(Policy::NoChangeNotification(parse_interval(rest)?)),
+    _ => bail!("Unknown policy {kind}; expected expiration, expiration-notification or no-change-notification"),
+  }
+}
+
+fn parse_expiration(value: &str) -> Result<DateTime> {
+  if let Ok(interval) = parse_interval(value) {
+    let now = DateTime::from(SystemTime::now());
+    return Ok(DateTime::from_secs(now.secs() + i64::from(interval.amount) * interval.unit.seconds()));
+  }
+  let time = DateTime::from_str(value, DateTimeFormat::DateTime).context(format!("Invalid expiration {value}; expected RFC 3339 or a duration like 7d"))?;
+  ensure!(time > DateTime::from(SystemTime::now()), "Expiration {value} is in the past");
+  Ok(time)
+}
+
+fn parse_interval(value: &str) -> Result<Interval> {
+  let (amount, unit) = value.split_at(value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len()));
+  let amount = amount.parse::<u32>().context(format!("Invalid interval {value}; expected e.g. 30d or 12h"))?;
+  let unit = match unit {
+    "d" => Unit::Days,
+    "h" => Unit::Hours,
+    _ => bail!("Invalid interval unit in {value}; expected d or h"),
+  };
+  ensure!(amount > 0, "The interval in {value} must be positive");
+  Ok(Interval { amount, unit })
+}
+
+pub fn to_json(policies: &[Policy]) -> Result<Option<String>> {
+  if policies.is_empty() {
+    return Ok(None);
+  }
+
+  let policies = policies
+    .iter()
+    .map(|policy| {
+      let (r#type, attributes) = match policy {
+        Policy::Expiration(time) => ("Expiration", json!({ "Timestamp": time.fmt(DateTimeFormat::DateTime)? })),
+        Policy::ExpirationNotification(interval) => ("ExpirationNotification", json!({ "Before": interval.amount.to_string(), "Unit": interval.unit.as_str() })),
+        Policy::NoChangeNotification(interval) => ("NoChangeNotification", json!({ "After": interval.amount.to_string(), "Unit": interval.unit.as_str() })),
+      };
+      Ok(json!({ "Type": r#type, "Version": "1.0", "Attributes": attributes }))
+    })
+    .collect::<Result<Vec<Value>>>()?;
+  Ok(Some(serde_json::to_string(&policies)?))
+}
@@ -0,0 +1,111 @@
+use std::{fmt::Display, sync::atomic::{AtomicU64, Ordering}, time::Instant};
+
+use indicatif::{HumanBytes, HumanDuration, ProgressBar, ProgressStyle};
+
+use crate::output::{self, Event};
+
+const TEMPLATE: &str = "{spinner} [{bar:30}] {pos}/{len} files | {prefix} | {wide_msg}";
+
+pub struct Progress {
+  operation: &'static str,
+  bar: ProgressBar,
+  files: AtomicU64,
+  failed: AtomicU64,
+  skipped: AtomicU64,
+  chunks: AtomicU64,
+  bytes: AtomicU64,
+  api_calls: AtomicU64,
+  started: Instant,
+}
+
+impl Progress {
+  pub fn new(operation: &'static str, files: usize) -> Self {
+    let bar = if output::is_json() || output::is_quiet() { ProgressBar::hidden() } else { ProgressBar::new(files as u64) };
+    bar.set_style(ProgressStyle::with_template(TEMPLATE).expect("valid progress template").progress_chars("=> "));
+    let progress = Self {
+      operation,
+      bar,
+      files: AtomicU64::new(0),
+      failed: AtomicU64::new(0),
+      skipped: AtomicU64::new(0),
+      chunks: AtomicU64::new(0),
+      bytes: AtomicU64::new(0),
+      api_calls: AtomicU64::new(0),
+      started: Instant::now(),
+    };
+    progress.refresh();
+    progress
+  }
+
+  pub fn start(&self, name: &str) {
+    self.bar.set_message(name.to_string());
+  }
+
+  pub fn api_calls(&self, count: usize) {
+    self.api_calls.fetch_add(count as u64, Ordering::Relaxed);
+    self.refresh();
+  }
+
+  pub fn transferred(&self, chunks: usize, bytes: usize) {
+    self.chunks.fetch_add(chunks as u64, Ordering::Relaxed);
+    self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    self.refresh();
+  }
+
+  pub fn file_done(&self) {
+    self.files.fetch_add(1, Ordering::Relaxed);
+    self.bar.inc(1);
+  }
+
+  pub fn file_failed(&self) {
+    self.failed.fetch_add(1, Ordering::Relaxed);
+    self.bar.inc(1);
+  }
+
+  pub fn file_skipped(&self) {
+    self.skipped.fetch_add(1, Ordering::Relaxed);
+    self.bar.inc(1);
+  }
+
+  pub fn suspend<R>(&self, f: impl FnOnce() -> R) -> R {
+    self.bar.suspend(f)
+  }
+
+  pub fn emit(&self, event: Event, message: impl Display) {
+    self.suspend(|| event.emit(message));
+  }
+
+  pub fn finish(self) {
+    self.bar.finish_and_clear();
+
+    let files = self.files.load(Ordering::Relaxed);
+    let failed = self.failed.load(Ordering::Relaxed);
+    let skipped = self.skipped.load(Ordering::Relaxed);
+    let chunks = self.chunks.load(Ordering::Relaxed);
+    let bytes = self.bytes.load(Ordering::Relaxed);
+    let api_calls = self.api_calls.load(Ordering::Relaxed);
+    let elapsed = self.started.elapsed();
+
+    let summary = format!(
+      "{:<10} {:>6} {:>6} {:>7} {:>7} {:>10} {:>10} {:>8}\n{:<10} {:>6} {:>6} {:>7} {:>7} {:>10} {:>10} {:>8}",
+      "Operation", "Files", "Failed", "Skipped", "Chunks", "Bytes", "API calls", "Elapsed",
+      self.operation, files, failed, skipped, chunks, HumanBytes(bytes).to_string(), api_calls, HumanDuration(elapsed).to_string(),
+    );
+    Event::new("summary", self.operation)
+      .with("files", files)
+      .with("failed", failed)
+      .with("skipped", skipped)
+      .with("chunks", chunks)
+      .with("bytes", bytes)
+      .with("api_calls", api_calls)
+      .with("elapsed_ms", elapsed.as_millis() as u64)
+      .emit(summary);
+  }
+
+  fn refresh(&self) {
+    let chunks = self.chunks.load(Ordering::Relaxed);
+    let bytes = self.bytes.load(Ordering::Relaxed);
+    let api_calls = self.api_calls.load(Ordering::Relaxed);
+    self.bar.set_prefix(format!("{chunks} chunks, {}, {api_calls} API calls", HumanBytes(bytes)));
+  }
+}
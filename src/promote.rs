@@ -0,0 +1,103 @@
+use anyhow::{Result, bail};
+use futures::TryStreamExt;
+use itertools::Itertools;
+use similar::TextDiff;
+
+use crate::delete::delete_names;
+use crate::files::{fetch_stored_files, logical_name};
+use crate::output::{self, Event, Redacted};
+use crate::prompt::confirm;
+use crate::ssm::{self, Client};
+use crate::upload::{UploadOptions, prepare, put_files};
+
+#[derive(Debug, Clone, Default)]
+pub struct PromoteOptions {
+  pub all: bool,
+  pub delete: bool,
+  pub upload: UploadOptions,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Change {
+  Added,
+  Changed,
+  Removed,
+}
+
+pub async fn promote(client: &Client, from: String, to: String, options: PromoteOptions) -> Result<()> {
+  let (from, to) = (from.trim_end_matches('/'), to.trim_end_matches('/'));
+  let source = fetch_stored_files(client, from).await?.0;
+  let target = fetch_stored_files(client, to).await?.0;
+
+  let changes = source
+    .keys()
+    .chain(target.keys())
+    .unique()
+    .sorted()
+    .filter_map(|key| match (source.get(key), target.get(key)) {
+      (Some(_), None) => Some((key, Change::Added)),
+      (Some(a), Some(b)) if a != b => Some((key, Change::Changed)),
+      (None, Some(_)) if options.delete => Some((key, Change::Removed)),
+      _ => None,
+    })
+    .collect::<Vec<_>>();
+
+  if changes.is_empty() {
+    tracing::info!("{to} is already up to date with {from}");
+    return Ok(());
+  }
+
+  let (mut puts, mut removals, mut skipped) = (vec![], vec![], 0);
+  for (key, change) in changes {
+    let name = format!("{to}/{key}");
+    let (old, new) = (target.get(key).map(|v| String::from_utf8_lossy(v)), source.get(key).map(|v| String::from_utf8_lossy(v)));
+    let message = match change {
+      Change::Added => format!("+ {name} = {}", Redacted(new.as_deref().unwrap_or_default())),
+      Change::Removed => format!("- {name}"),
+      Change::Changed if output::show_values() => {
+        let diff = TextDiff::from_lines(old.as_deref().unwrap_or_default(), new.as_deref().unwrap_or_default()).unified_diff().header(&name, &name).to_string();
+        format!("~ {name}\n{}", diff.trim_end())
+      },
+      Change::Changed => format!("~ {name}: {} -> {}", Redacted(old.as_deref().unwrap_or_default()), Redacted(new.as_deref().unwrap_or_default())),
+    };
+
+    let action = match change {
+      Change::Added => "added",
+      Change::Changed => "changed",
+      Change::Removed => "removed",
+    };
+    Event::new(action, name.as_str()).emit(message);
+    if !options.all && !confirm("Apply this change?")? {
+      skipped += 1;
+      continue;
+    }
+
+    match (change, source.get(key)) {
+      (Change::Removed, _) => removals.push(name),
+      (_, Some(content)) => puts.push(prepare(&name, content.clone(), &options.upload)?),
+      (_, None) => bail!("{from}/{key} disappeared while promoting"),
+    }
+  }
+
+  let (applied, removed) = (puts.len(), removals.len());
+  put_files(client, puts, &options.upload).await?;
+  if !removals.is_empty() {
+    let names = ssm::all_parameter_metadata_by_path(client, to)
+      .try_collect::<Vec<_>>()
+      .await?
+      .into_iter()
+      .flatten()
+      .filter_map(|m| m.name)
+      .filter(|n| removals.iter().any(|r| r == logical_name(n)))
+      .collect::<Vec<_>>();
+    delete_names(client, &names, options.upload.dry_run).await?;
+  }
+
+  Event::new("promote", to)
+    .with("from", from)
+    .with("applied", applied)
+    .with("removed", removed)
+    .with("skipped", skipped)
+    .emit(format!("Promoted {from} to {to}: {applied} applied, {removed} removed, {skipped} skipped"));
+  Ok(())
+}
@@ -0,0 +1,21 @@
+use std::io::{self, BufRead, Write};
+
+use anyhow::Result;
+
+pub fn confirm(message: &str) -> Result<bool> {
+  eprint!("{message} [y/N] ");
+  io::stderr().flush()?;
+
+  let mut answer = String::new();
+  io::stdin().lock().read_line(&mut answer)?;
+  Ok(matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes"))
+}
+
+pub fn read_line(message: &str) -> Result<String> {
+  eprint!("{message}: ");
+  io::stderr().flush()?;
+
+  let mut answer = String::new();
+  io::stdin().lock().read_line(&mut answer)?;
+  Ok(answer.trim().to_string())
+}
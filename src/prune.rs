@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use aws_sdk_ssm::primitives::DateTime;
+use aws_sdk_ssm::types::ParameterMetadata;
+use futures::TryStreamExt;
+use itertools::Itertools;
+
+use crate::delete::delete_names;
+use crate::files::{MANIFEST_SUFFIX, Manifest, logical_name, split_part};
+use crate::ssm::{self, Client};
+
+pub async fn prune(client: &Client, prefix: String, dry_run: bool) -> Result<()> {
+  let metadata = ssm::all_parameter_metadata_by_path(client, &prefix)
+    .try_collect::<Vec<_>>()
+    .await?
+    .into_iter()
+    .flatten()
+    .filter(|m| m.name().is_some())
+    .collect::<Vec<_>>();
+
+  let manifest_names = metadata.iter().filter_map(|m| m.name()).filter(|n| n.ends_with(MANIFEST_SUFFIX)).map(str::to_string).collect::<Vec<_>>();
+  let (manifest_params, _) = ssm::get_parameters(client, &manifest_names).await?;
+  let manifests = manifest_params
+    .iter()
+    .filter_map(|p| {
+      let base = p.name()?.strip_suffix(MANIFEST_SUFFIX)?;
+      let manifest = serde_json::from_str::<Manifest>(p.value()?).ok()?;
+      Some((base.to_string(), manifest.parts))
+    })
+    .collect::<HashMap<_, _>>();
+
+  let mut orphans = vec![];
+  for (_, entries) in metadata.iter().into_group_map_by(|m| logical_name(m.name().unwrap_or_default()).to_string()) {
+    let variants = entries.into_iter().into_group_map_by(|m| variant_base(m.name().unwrap_or_default()).to_string());
+    let current = variants.iter().max_by_key(|(_, ms)| newest(ms)).map(|(base, _)| base.clone());
+
+    for (base, entries) in variants {
+      if Some(&base) == current.as_ref() {
+        orphans.extend(stale_entries(&base, &entries, manifests.get(&base).copied()));
+      } else {
+        orphans.extend(entries.iter().filter_map(|m| m.name().map(str::to_string)));
+      }
+    }
+  }
+
+  if orphans.is_empty() {
+    tracing::info!("No orphaned parameters under {prefix}");
+    return Ok(());
+  }
+
+  orphans.sort();
+  delete_names(client, &orphans, dry_run).await
+}
+
+fn variant_base(name: &str) -> &str {
+  split_part(name.strip_suffix(MANIFEST_SUFFIX).unwrap_or(name)).0
+}
+
+fn newest(entries: &[&ParameterMetadata]) -> Option<DateTime> {
+  entries.iter().filter_map(|m| m.last_modified_date()).max().copied()
+}
+
+fn stale_entries(base: &str, entries: &[&ParameterMetadata], manifest_parts: Option<usize>) -> Vec<String> {
+  let (plain, chunked): (Vec<_>, Vec<_>) = entries.iter().copied().partition(|m| m.name() == Some(base));
+  if chunked.is_empty() {
+    return vec![];
+  }
+
+  if !plain.is_empty() {
+    let stale = if newest(&plain) > newest(&chunked) { chunked } else { plain };
+    return stale.iter().filter_map(|m| m.name().map(str::to_string)).collect();
+  }
+
+  let indices = chunked.iter().filter_map(|m| split_part(m.name().unwrap_or_default()).1).collect::<Vec<_>>();
+  let keep = manifest_parts.unwrap_or_else(|| (0..).take_while(|i| indices.contains(i)).count());
+
+  chunked
+    .iter()
+    .filter_map(|m| m.name())
+    .filter(|name| split_part(name).1.is_some_and(|idx| idx >= keep))
+    .map(str::to_string)
+    .collect()
+}
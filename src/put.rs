@@ -0,0 +1,28 @@
+use std::{io::Read, path::PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::ssm::Client;
+use crate::upload::{UploadOptions, prepare, put_files};
+
+#[derive(Debug, Clone)]
+pub enum PutSource {
+  Value(String),
+  Stdin,
+  File(PathBuf),
+}
+
+pub async fn put(client: &Client, name: String, source: PutSource, options: UploadOptions) -> Result<()> {
+  let content = match source {
+    PutSource::Value(value) => value.into_bytes(),
+    PutSource::Stdin => {
+      let mut content = vec![];
+      std::io::stdin().read_to_end(&mut content).context("Failed to read value from stdin")?;
+      content
+    },
+    PutSource::File(path) => std::fs::read(&path).context(format!("Failed to read {}", path.display()))?,
+  };
+
+  let file = prepare(&name, content, &options)?;
+  put_files(client, vec![file], &options).await
+}
@@ -0,0 +1,80 @@
+use std::{sync::{Arc, Mutex}, time::Duration};
+
+use aws_smithy_http_client::{Builder, tls};
+use aws_smithy_runtime_api::client::http::{HttpClient, HttpConnector, HttpConnectorFuture, HttpConnectorSettings, SharedHttpClient, SharedHttpConnector};
+use aws_smithy_runtime_api::client::orchestrator::HttpRequest;
+use aws_smithy_runtime_api::client::runtime_components::RuntimeComponents;
+use tokio::time::Instant;
+
+pub fn default_http_client() -> SharedHttpClient {
+  Builder::new().tls_provider(tls::Provider::Rustls(tls::rustls_provider::CryptoMode::AwsLc)).build_https()
+}
+
+#[derive(Debug)]
+pub struct RateLimiter {
+  rate: f64,
+  state: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+  tokens: f64,
+  refilled: Instant,
+}
+
+impl RateLimiter {
+  pub fn new(max_tps: f64) -> Self {
+    Self { rate: max_tps, state: Mutex::new(Bucket { tokens: max_tps.max(1.0), refilled: Instant::now() }) }
+  }
+
+  pub async fn acquire(&self) {
+    loop {
+      let wait = {
+        let mut bucket = self.state.lock().unwrap();
+        let now = Instant::now();
+        bucket.tokens = (bucket.tokens + now.duration_since(bucket.refilled).as_secs_f64() * self.rate).min(self.rate.max(1.0));
+        bucket.refilled = now;
+        if bucket.tokens >= 1.0 {
+          bucket.tokens -= 1.0;
+          return;
+        }
+        Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate)
+      };
+      tokio::time::sleep(wait).await;
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimitedHttpClient {
+  inner: SharedHttpClient,
+  limiter: Arc<RateLimiter>,
+}
+
+impl RateLimitedHttpClient {
+  pub fn new(inner: SharedHttpClient, max_tps: f64) -> Self {
+    Self { inner, limiter: Arc::new(RateLimiter::new(max_tps)) }
+  }
+}
+
+impl HttpClient for RateLimitedHttpClient {
+  fn http_connector(&self, settings: &HttpConnectorSettings, components: &RuntimeComponents) -> SharedHttpConnector {
+    SharedHttpConnector::new(RateLimitedConnector { inner: self.inner.http_connector(settings, components), limiter: self.limiter.clone() })
+  }
+}
+
+#[derive(Debug)]
+struct RateLimitedConnector {
+  inner: SharedHttpConnector,
+  limiter: Arc<RateLimiter>,
+}
+
+impl HttpConnector for RateLimitedConnector {
+  fn call(&self, request: HttpRequest) -> HttpConnectorFuture {
+    let (inner, limiter) = (self.inner.clone(), self.limiter.clone());
+    HttpConnectorFuture::new(async move {
+      limiter.acquire().await;
+      inner.call(request).await
+    })
+  }
+}
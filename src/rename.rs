@@ -0,0 +1,31 @@
+use anyhow::{Result, bail};
+use futures::TryStreamExt;
+
+use crate::copy::{CopyOptions, copy, destination};
+use crate::delete::delete_names;
+use crate::prompt::confirm;
+use crate::s3::S3Target;
+use crate::ssm::{self, Client};
+
+pub async fn rename(client: &Client, prefix: String, to_prefix: String, s3: Option<S3Target>, yes: bool, dry_run: bool) -> Result<()> {
+  ssm::ensure_not_root(&prefix)?;
+  ssm::ensure_not_root(&to_prefix)?;
+  let params = ssm::all_parameters_by_path(client, &prefix, true).try_collect::<Vec<_>>().await?;
+  let names = params.into_iter().flatten().filter_map(|p| p.name).collect::<Vec<_>>();
+
+  for name in &names {
+    destination(name, &prefix, &to_prefix)?;
+  }
+
+  if names.is_empty() {
+    tracing::info!("Nothing to move under {prefix}");
+    return Ok(());
+  }
+
+  if !dry_run && !yes && !confirm(&format!("Move {} parameters from {prefix} to {to_prefix}?", names.len()))? {
+    bail!("Aborted");
+  }
+
+  copy(client, client, prefix, to_prefix, CopyOptions { preserve_tags: true, s3, yes: true, dry_run, ..Default::default() }).await?;
+  delete_names(client, &names, dry_run).await
+}
@@ -0,0 +1,104 @@
+use std::{collections::{BTreeSet, HashMap}, fs, io::{self, Write}, path::PathBuf};
+
+use anyhow::{Context as _, Result, bail};
+use futures::future::try_join_all;
+use handlebars::{Context, Handlebars, Helper, HelperResult, JsonValue, Output, RenderContext, RenderErrorReason, Template};
+use handlebars::template::{Parameter, TemplateElement};
+
+use crate::download::{PRIVATE_MODE, write_atomic_with_mode};
+use crate::files::fetch_file_with;
+use crate::output::Event;
+use crate::ssm::Client;
+
+#[derive(Debug, Clone, Default)]
+pub struct RenderOptions {
+  pub out: Option<PathBuf>,
+  pub mode: Option<u32>,
+}
+
+pub async fn render(client: &Client, s3: Option<&aws_sdk_s3::Client>, template: PathBuf, options: RenderOptions) -> Result<()> {
+  let source = fs::read_to_string(&template).context(format!("Failed to read {}", template.display()))?;
+
+  let names = referenced_names(&source)?;
+  let values = try_join_all(names.iter().map(|name| async move {
+    let content = fetch_file_with(client, s3, name).await?;
+    let value = String::from_utf8(content).context(format!("{name} is not valid UTF-8"))?;
+    anyhow::Ok((name.clone(), value))
+  }))
+  .await?
+  .into_iter()
+  .collect::<HashMap<_, _>>();
+
+  let rendered = registry(move |name| values.get(name).cloned())
+    .render_template(&source, &())
+    .context(format!("Failed to render {}", template.display()))?;
+
+  match options.out {
+    Some(out) => {
+      write_atomic_with_mode(&out, rendered.as_bytes(), options.mode.unwrap_or(PRIVATE_MODE)).context(format!("Failed to write {}", out.display()))?;
+      Event::new("render", out.display().to_string()).with("parameters", names.len()).emit(format!("Rendered {} with {} parameter(s)", out.display(), names.len()));
+    },
+    None => {
+      let mut stdout = io::stdout().lock();
+      stdout.write_all(rendered.as_bytes())?;
+      stdout.flush()?;
+    },
+  }
+
+  Ok(())
+}
+
+fn referenced_names(source: &str) -> Result<BTreeSet<String>> {
+  let template = Template::compile(source).context("Failed to parse template")?;
+  let mut names = BTreeSet::new();
+  collect_names(&template, &mut names)?;
+  Ok(names)
+}
+
+fn collect_names(template: &Template, names: &mut BTreeSet<String>) -> Result<()> {
+  template.elements.iter().try_for_each(|element| collect_element(element, names))
+}
+
+fn collect_element(element: &TemplateElement, names: &mut BTreeSet<String>) -> Result<()> {
+  match element {
+    TemplateElement::Expression(helper) | TemplateElement::HtmlExpression(helper) | TemplateElement::HelperBlock(helper) => {
+      if helper.name.as_name() == Some("ssm") {
+        let Some(Parameter::Literal(JsonValue::String(name))) = helper.params.first() else { bail!("The ssm helper takes the parameter name as a quoted string") };
+        names.insert(name.clone());
+      }
+      helper.params.iter().chain(helper.hash.values()).try_for_each(|param| collect_param(param, names))?;
+      helper.template.iter().chain(&helper.inverse).try_for_each(|template| collect_names(template, names))
+    },
+    TemplateElement::DecoratorExpression(decorator) | TemplateElement::DecoratorBlock(decorator) | TemplateElement::PartialExpression(decorator) | TemplateElement::PartialBlock(decorator) => {
+      decorator.params.iter().chain(decorator.hash.values()).try_for_each(|param| collect_param(param, names))?;
+      decorator.template.iter().try_for_each(|template| collect_names(template, names))
+    },
+    _ => Ok(()),
+  }
+}
+
+fn collect_param(param: &Parameter, names: &mut BTreeSet<String>) -> Result<()> {
+  match param {
+    Parameter::Subexpression(subexpression) => collect_element(subexpression.as_element(), names),
+    _ => Ok(()),
+  }
+}
+
+fn registry(lookup: impl Fn(&str) -> Option<String> + Send + Sync + 'static) -> Handlebars<'static> {
+  let mut handlebars = Handlebars::new();
+  handlebars.set_strict_mode(true);
+  handlebars.register_escape_fn(handlebars::no_escape);
+  handlebars.register_helper(
+    "ssm",
+    Box::new(move |h: &Helper, _: &Handlebars, _: &Context, _: &mut RenderContext, out: &mut dyn Output| -> HelperResult {
+      let name = h
+        .param(0)
+        .and_then(|p| p.value().as_str())
+        .ok_or(RenderErrorReason::ParamNotFoundForIndex("ssm", 0))?;
+      let value = lookup(name).ok_or_else(|| RenderErrorReason::Other(format!("Parameter {name} not found")))?;
+      out.write(&value)?;
+      Ok(())
+    }),
+  );
+  handlebars
+}
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result, bail};
+use aws_sdk_s3::{Client, primitives::ByteStream, types::ServerSideEncryption};
+use futures::future::try_join_all;
+
+use crate::crypto::{self, Encryption};
+use crate::files::S3_SUFFIX;
+use crate::ssm;
+
+const POINTER_SCHEME: &str = "s3://";
+const ENCRYPTION_METADATA: &str = "ops-encryption";
+
+#[derive(Debug, Clone)]
+pub struct S3Target {
+  pub client: Client,
+  pub bucket: String,
+}
+
+pub fn object_key(name: &str) -> String {
+  name.trim_start_matches('/').to_string()
+}
+
+pub fn pointer(bucket: &str, key: &str) -> String {
+  format!("{POINTER_SCHEME}{bucket}/{key}")
+}
+
+pub fn parse_pointer(value: &str) -> Result<(&str, &str)> {
+  value
+    .strip_prefix(POINTER_SCHEME)
+    .and_then(|rest| rest.split_once('/'))
+    .context(format!("Invalid S3 pointer {value}"))
+}
+
+pub async fn put_object(target: &S3Target, key: &str, content: &[u8], kms_key_id: Option<&str>, encryption: Option<Encryption>) -> Result<()> {
+  ssm::ensure_writable("s3:PutObject", &format!("s3://{}/{key}", target.bucket))?;
+  target
+    .client
+    .put_object()
+    .bucket(&target.bucket)
+    .key(key)
+    .body(ByteStream::from(content.to_vec()))
+    .server_side_encryption(ServerSideEncryption::AwsKms)
+    .set_ssekms_key_id(kms_key_id.map(str::to_string))
+    .set_metadata(encryption.map(|e| HashMap::from([(ENCRYPTION_METADATA.to_string(), e.as_str().to_string())])))
+    .send()
+    .await
+    .context(format!("Failed to upload s3://{}/{key}", target.bucket))?;
+  Ok(())
+}
+
+pub async fn get_object(client: &Client, pointer: &str) -> Result<(Vec<u8>, Option<Encryption>)> {
+  let (bucket, key) = parse_pointer(pointer)?;
+  let resp = client.get_object().bucket(bucket).key(key).send().await.context(format!("Failed to download {pointer}"))?;
+  let encryption = resp.metadata().and_then(|m| m.get(ENCRYPTION_METADATA)).and_then(|e| Encryption::parse(e));
+  let body = resp.body.collect().await.context(format!("Failed to read {pointer}"))?;
+  Ok((body.into_bytes().to_vec(), encryption))
+}
+
+pub async fn copy_object(target: &S3Target, pointer: &str, key: &str, kms_key_id: Option<&str>) -> Result<String> {
+  let (bucket, source_key) = parse_pointer(pointer)?;
+  ssm::ensure_writable("s3:PutObject", &format!("s3://{}/{key}", target.bucket))?;
+  target
+    .client
+    .copy_object()
+    .copy_source(format!("{bucket}/{source_key}"))
+    .bucket(&target.bucket)
+    .key(key)
+    .server_side_encryption(ServerSideEncryption::AwsKms)
+    .set_ssekms_key_id(kms_key_id.map(str::to_string))
+    .send()
+    .await
+    .context(format!("Failed to copy {pointer}"))?;
+  Ok(self::pointer(&target.bucket, key))
+}
+
+pub async fn resolve_pointers(client: Option<&Client>, files: HashMap<String, Vec<u8>>) -> Result<HashMap<String, Vec<u8>>> {
+  let resolved = try_join_all(files.into_iter().map(|(rel_path, content)| async move {
+    let Some(base) = rel_path.strip_suffix(S3_SUFFIX) else {
+      return Ok((rel_path, content));
+    };
+    let Some(client) = client else {
+      bail!("{base} is stored in S3; use --backend s3 to read it");
+    };
+
+    let pointer = String::from_utf8(content).context(format!("Invalid S3 pointer for {base}"))?;
+    let (content, encryption) = get_object(client, &pointer).await?;
+    let content = crypto::decrypt_as(encryption, content).context(format!("Failed to decrypt {base}"))?;
+    Ok((base.to_string(), content))
+  }))
+  .await?;
+
+  Ok(resolved.into_iter().collect())
+}
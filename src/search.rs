@@ -0,0 +1,56 @@
+use anyhow::{Context, Result};
+use futures::TryStreamExt;
+use itertools::Itertools;
+use regex::{Regex, RegexBuilder};
+
+use crate::files::{fetch_files, logical_name};
+use crate::output::Event;
+use crate::ssm::{self, Client};
+
+#[derive(Debug, Clone, Default)]
+pub struct SearchOptions {
+  pub values: bool,
+  pub ignore_case: bool,
+}
+
+pub async fn search(client: &Client, s3: Option<&aws_sdk_s3::Client>, prefix: String, pattern: String, options: SearchOptions) -> Result<()> {
+  let regex = RegexBuilder::new(&pattern).case_insensitive(options.ignore_case).build().context(format!("Invalid regex {pattern}"))?;
+  let prefix = prefix.trim_end_matches('/');
+
+  if !options.values {
+    let names = ssm::all_parameter_metadata_by_path(client, prefix)
+      .try_collect::<Vec<_>>()
+      .await?
+      .into_iter()
+      .flatten()
+      .filter_map(|p| p.name)
+      .map(|name| logical_name(&name).to_string())
+      .unique()
+      .sorted();
+
+    names.filter(|name| regex.is_match(name)).for_each(|name| Event::new("match", name.as_str()).with("in", "name").emit(&name));
+    return Ok(());
+  }
+
+  let files = fetch_files(client, s3, prefix).await?;
+  for (rel_path, content) in files.into_iter().sorted_by(|(a, _), (b, _)| a.cmp(b)) {
+    let name = format!("{prefix}/{rel_path}");
+    if regex.is_match(&name) {
+      Event::new("match", name.as_str()).with("in", "name").emit(&name);
+    }
+
+    let lines = matching_lines(&regex, &String::from_utf8_lossy(&content));
+    if !lines.is_empty() {
+      Event::new("match", name.as_str())
+        .with("in", "value")
+        .with("lines", lines.clone())
+        .emit(format!("{name}:{}", lines.iter().join(",")));
+    }
+  }
+
+  Ok(())
+}
+
+fn matching_lines(regex: &Regex, content: &str) -> Vec<usize> {
+  content.lines().enumerate().filter(|(_, line)| regex.is_match(line)).map(|(i, _)| i + 1).collect()
+}
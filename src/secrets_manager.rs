@@ -0,0 +1,131 @@
+use anyhow::{Context, Result, ensure};
+use async_trait::async_trait;
+use aws_sdk_secretsmanager::Client;
+use aws_sdk_secretsmanager::types::{Filter, FilterNameStringType, SecretListEntry};
+use futures::{TryStreamExt, future::try_join_all, stream::Stream};
+use serde_json::Value;
+
+use crate::pin::Pin;
+use crate::ssm;
+use crate::store::{Entry, SecretStore};
+
+const KEY_SEPARATOR: char = '#';
+
+pub struct SecretsManagerStore {
+  client: Client,
+}
+
+impl SecretsManagerStore {
+  pub fn new(client: Client) -> Self {
+    Self { client }
+  }
+
+  fn list_secrets<'a>(&'a self, prefix: &'a str) -> impl Stream<Item = Result<Vec<SecretListEntry>>> + 'a {
+    let filter = Filter::builder().key(FilterNameStringType::Name).values(prefix).build();
+    ssm::paginate(format!("secrets under {prefix}"), move |next_token| {
+      let filter = filter.clone();
+      async move {
+        let resp = self.client.list_secrets().filters(filter).set_next_token(next_token).send().await?;
+        Ok((resp.secret_list().to_vec(), resp.next_token().map(str::to_string)))
+      }
+    })
+  }
+
+  async fn secret_value(&self, secret_id: &str) -> Result<Option<Vec<u8>>> {
+    let resp = match self.client.get_secret_value().secret_id(secret_id).send().await {
+      Ok(resp) => resp,
+      Err(err) if err.as_service_error().is_some_and(|e| e.is_resource_not_found_exception()) => return Ok(None),
+      Err(err) => return Err(err).context(format!("Failed to get secret {secret_id}")),
+    };
+
+    let value = match (resp.secret_string(), resp.secret_binary()) {
+      (Some(text), _) => text.as_bytes().to_vec(),
+      (None, Some(blob)) => blob.as_ref().to_vec(),
+      (None, None) => vec![],
+    };
+    Ok(Some(value))
+  }
+}
+
+#[async_trait]
+impl SecretStore for SecretsManagerStore {
+  async fn fetch_prefix(&self, prefix: &str, pin: Option<&Pin>) -> Result<Vec<Entry>> {
+    ensure!(pin.is_none(), "Version pinning is only supported by the ssm backend");
+    let prefix = prefix.trim_end_matches('/');
+    let names = self
+      .list_secrets(prefix)
+      .try_collect::<Vec<_>>()
+      .await?
+      .into_iter()
+      .flatten()
+      .filter_map(|s| s.name)
+      .filter(|name| name.starts_with(prefix))
+      .collect::<Vec<_>>();
+
+    let values = try_join_all(names.iter().map(|name| async move { anyhow::Ok((name, self.secret_value(name).await?)) })).await?;
+
+    Ok(
+      values
+        .into_iter()
+        .filter_map(|(name, value)| Some((name.trim_start_matches(prefix).trim_start_matches('/').to_string(), value?)))
+        .flat_map(|(rel_path, value)| expand_json(&rel_path, value))
+        .map(|(name, value)| Entry { name, value, string_list: false })
+        .collect(),
+    )
+  }
+
+  async fn fetch_names(&self, names: &[String], pin: Option<&Pin>) -> Result<(Vec<Entry>, Vec<String>)> {
+    ensure!(pin.is_none(), "Version pinning is only supported by the ssm backend");
+    let values = try_join_all(names.iter().map(|name| async move {
+      let (secret_id, key) = match name.split_once(KEY_SEPARATOR) {
+        Some((secret_id, key)) => (secret_id, Some(key)),
+        None => (name.as_str(), None),
+      };
+      let value = self.secret_value(secret_id).await?;
+      let value = match key {
+        Some(key) => value.and_then(|value| json_key(&value, key)),
+        None => value,
+      };
+      anyhow::Ok((name, value))
+    }))
+    .await?;
+
+    let mut entries = vec![];
+    let mut missing = vec![];
+    for (name, value) in values {
+      match value {
+        Some(value) => entries.push(Entry { name: name.clone(), value, string_list: false }),
+        None => missing.push(name.clone()),
+      }
+    }
+    Ok((entries, missing))
+  }
+}
+
+fn expand_json(rel_path: &str, value: Vec<u8>) -> Vec<(String, Vec<u8>)> {
+  let Ok(Value::Object(object)) = serde_json::from_slice::<Value>(&value) else {
+    return vec![(rel_path.to_string(), value)];
+  };
+
+  object
+    .into_iter()
+    .map(|(key, value)| {
+      let name = if rel_path.is_empty() { key } else { format!("{rel_path}/{key}") };
+      (name, scalar_bytes(value))
+    })
+    .collect()
+}
+
+fn json_key(value: &[u8], key: &str) -> Option<Vec<u8>> {
+  match serde_json::from_slice::<Value>(value).ok()? {
+    Value::Object(mut object) => object.remove(key).map(scalar_bytes),
+    _ => None,
+  }
+}
+
+fn scalar_bytes(value: Value) -> Vec<u8> {
+  match value {
+    Value::String(text) => text.into_bytes(),
+    other => other.to_string().into_bytes(),
+  }
+}
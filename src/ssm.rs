@@ -1,28 +1,307 @@
-use anyhow::Result;
-use aws_config::BehaviorVersion;
+use std::{collections::HashSet, future::Future, sync::atomic::{AtomicBool, Ordering}, time::Duration};
+
+use anyhow::{Context, Result, bail, ensure};
+use aws_config::{AppName, BehaviorVersion, Region, SdkConfig, sts::AssumeRoleProvider};
 pub use aws_sdk_ssm::Client;
+use aws_sdk_ssm::config::{Credentials, ProvideCredentials, SharedCredentialsProvider};
+use aws_sdk_ssm::error::{ErrorMetadata, ProvideErrorMetadata, SdkError};
+use aws_sdk_ssm::types::{Parameter, ParameterHistory, ParameterMetadata, ParameterStringFilter};
 
+use futures::future::try_join_all;
 use futures::stream::{self, Stream};
 
-pub async fn client() -> Client {
-  let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
-  Client::new(&config)
+use crate::cancel;
+use crate::prompt;
+use crate::rate_limit::{self, RateLimitedHttpClient};
+
+pub const GET_BATCH_SIZE: usize = 10;
+const PATH_PAGE_SIZE: i32 = 10;
+const DESCRIBE_PAGE_SIZE: i32 = 50;
+const HISTORY_PAGE_SIZE: i32 = 50;
+const MAX_PAGES: usize = 100_000;
+const ROLE_SESSION_NAME: &str = "ops";
+const MAX_THROTTLE_RETRIES: u32 = 6;
+const THROTTLE_BASE_DELAY: Duration = Duration::from_millis(250);
+
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+pub fn set_read_only(read_only: bool) {
+  READ_ONLY.store(read_only, Ordering::Relaxed);
+}
+
+pub fn ensure_writable(action: &str, resource: &str) -> Result<()> {
+  ensure!(!READ_ONLY.load(Ordering::Relaxed), "Refusing {action} on {resource} in --read-only mode");
+  Ok(())
+}
+
+pub fn ensure_not_root(prefix: &str) -> Result<()> {
+  ensure!(!prefix.trim_matches('/').is_empty(), "Refusing to operate on the root prefix {prefix}; use a narrower --prefix");
+  Ok(())
+}
+
+pub fn explain_access_denied(err: anyhow::Error) -> anyhow::Error {
+  let action = err
+    .chain()
+    .filter_map(|cause| cause.downcast_ref::<ErrorMetadata>())
+    .filter(|meta| matches!(meta.code(), Some("AccessDeniedException" | "AccessDenied")))
+    .find_map(|meta| Some(meta.message()?.split("not authorized to perform: ").nth(1)?.split_whitespace().next()?.to_string()));
+  match action {
+    Some(action) => err.context(format!("Access denied: the caller lacks the {action} IAM permission")),
+    None => err,
+  }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ClientOptions {
+  pub region: Option<String>,
+  pub profile: Option<String>,
+  pub role_arn: Option<String>,
+  pub external_id: Option<String>,
+  pub mfa_serial: Option<String>,
+  pub endpoint_url: Option<String>,
+  pub max_tps: Option<f64>,
+  pub user_agent_suffix: Option<AppName>,
+}
+
+pub fn parse_user_agent_suffix(value: &str) -> Result<AppName> {
+  AppName::new(value.to_string()).context("The user agent suffix may only contain letters, numbers and !#$%&'*+-.^_`|~")
+}
+
+pub async fn client_with(options: ClientOptions) -> Result<Client> {
+  Ok(client(&sdk_config(options.clone()).await?, &options))
 }
 
-pub fn all_parameters_by_path(client: &Client, prefix: &str) -> impl Stream<Item = Result<Vec<aws_sdk_ssm::types::Parameter>>> {
-  stream::try_unfold((true, None), move |(first, next_token)| async move {
-    if first || next_token.is_some() {
-      let resp = client
+pub fn client(config: &SdkConfig, options: &ClientOptions) -> Client {
+  let mut builder = aws_sdk_ssm::config::Builder::from(config);
+  if let Some(endpoint_url) = &options.endpoint_url {
+    builder = builder.endpoint_url(endpoint_url);
+  }
+  if let Some(max_tps) = options.max_tps {
+    let http_client = config.http_client().unwrap_or_else(rate_limit::default_http_client);
+    builder = builder.http_client(RateLimitedHttpClient::new(http_client, max_tps));
+  }
+  Client::from_conf(builder.build())
+}
+
+pub async fn sdk_config(options: ClientOptions) -> Result<SdkConfig> {
+  let mut loader = aws_config::defaults(BehaviorVersion::latest());
+  if let Some(region) = options.region {
+    loader = loader.region(Region::new(region));
+  }
+  if let Some(profile) = options.profile {
+    loader = loader.profile_name(profile);
+  }
+  if let Some(app_name) = options.user_agent_suffix {
+    loader = loader.app_name(app_name);
+  }
+  let mut config = loader.load().await;
+
+  match (options.role_arn, options.mfa_serial) {
+    (Some(role_arn), Some(mfa_serial)) => {
+      let credentials = assume_role_with_mfa(&config, &role_arn, options.external_id, &mfa_serial).await?;
+      config = config.into_builder().credentials_provider(SharedCredentialsProvider::new(credentials)).build();
+    },
+    (Some(role_arn), None) => {
+      let mut builder = AssumeRoleProvider::builder(role_arn).session_name(ROLE_SESSION_NAME);
+      if let Some(external_id) = options.external_id {
+        builder = builder.external_id(external_id);
+      }
+      let provider = builder.configure(&config).build().await;
+      config = config.into_builder().credentials_provider(SharedCredentialsProvider::new(provider)).build();
+    },
+    (None, _) => {},
+  }
+
+  Ok(config)
+}
+
+pub async fn cache_namespace(config: &SdkConfig, options: &ClientOptions) -> Result<String> {
+  let region = config.region().map(|region| region.to_string()).unwrap_or_default();
+  let profile = options.profile.clone().or_else(|| std::env::var("AWS_PROFILE").ok()).unwrap_or_else(|| "default".to_string());
+  let provider = config.credentials_provider().context("No AWS credentials are configured")?;
+  let credentials = provider.provide_credentials().await.context("Failed to load AWS credentials")?;
+  Ok(format!("{region}|{profile}|{}|{options:?}", credentials.access_key_id()))
+}
+
+pub async fn parameter_arn_prefix(config: &SdkConfig) -> Result<String> {
+  let region = config.region().context("A region is required to build parameter ARNs")?;
+  let identity = aws_sdk_sts::Client::new(config).get_caller_identity().send().await.context("Failed to look up the caller identity")?;
+  let account = identity.account().context("The caller identity has no account")?;
+  let partition = identity.arn().and_then(|arn| arn.split(':').nth(1)).unwrap_or("aws");
+  Ok(format!("arn:{partition}:ssm:{region}:{account}:parameter"))
+}
+
+async fn assume_role_with_mfa(config: &SdkConfig, role_arn: &str, external_id: Option<String>, mfa_serial: &str) -> Result<Credentials> {
+  let token = prompt::read_line(&format!("MFA code for {mfa_serial}"))?;
+  let resp = aws_sdk_sts::Client::new(config)
+    .assume_role()
+    .role_arn(role_arn)
+    .role_session_name(ROLE_SESSION_NAME)
+    .set_external_id(external_id)
+    .serial_number(mfa_serial)
+    .token_code(token)
+    .send()
+    .await
+    .context(format!("Failed to assume {role_arn}"))?;
+
+  let credentials = resp.credentials().context(format!("No credentials returned for {role_arn}"))?;
+  let expiry = std::time::SystemTime::try_from(*credentials.expiration()).ok();
+  Ok(Credentials::new(credentials.access_key_id(), credentials.secret_access_key(), Some(credentials.session_token().to_string()), expiry, "ops-mfa"))
+}
+
+pub fn all_parameters_by_path<'a>(client: &'a Client, prefix: &'a str, recursive: bool) -> impl Stream<Item = Result<Vec<Parameter>>> + 'a {
+  paginate(format!("parameters under {prefix}"), move |next_token| async move {
+    let resp = retry_throttled(|| {
+      client
         .get_parameters_by_path()
         .with_decryption(true)
         .path(prefix)
-        .set_next_token(next_token)
-        .recursive(true)
+        .recursive(recursive)
+        .max_results(PATH_PAGE_SIZE)
+        .set_next_token(next_token.clone())
         .send()
-        .await?;
-      Ok(Some((resp.parameters().to_vec(), (false, resp.next_token().map(|s| s.to_string())))))
-    } else {
-      Ok(None)
+    })
+    .await?;
+    Ok((resp.parameters().to_vec(), resp.next_token().map(str::to_string)))
+  })
+}
+
+pub fn all_parameter_metadata_by_path<'a>(client: &'a Client, prefix: &str) -> impl Stream<Item = Result<Vec<ParameterMetadata>>> + 'a {
+  describe_parameters(client, vec![string_filter("Path", "Recursive", prefix)])
+}
+
+pub fn describe_parameters(client: &Client, filters: Vec<ParameterStringFilter>) -> impl Stream<Item = Result<Vec<ParameterMetadata>>> + '_ {
+  let what = format!("parameters matching {}", filters.iter().map(|f| format!("{} {} {}", f.key(), f.option().unwrap_or_default(), f.values().join(","))).collect::<Vec<_>>().join(" and "));
+  paginate(what, move |next_token| {
+    let filters = filters.clone();
+    async move {
+      let resp = retry_throttled(|| {
+        client
+          .describe_parameters()
+          .set_parameter_filters(Some(filters.clone()))
+          .max_results(DESCRIBE_PAGE_SIZE)
+          .set_next_token(next_token.clone())
+          .send()
+      })
+      .await?;
+      Ok((resp.parameters().to_vec(), resp.next_token().map(str::to_string)))
+    }
+  })
+}
+
+pub fn paginate<'a, T, F, Fut>(what: String, fetch: F) -> impl Stream<Item = Result<Vec<T>>> + 'a
+where
+  T: 'a,
+  F: FnMut(Option<String>) -> Fut + 'a,
+  Fut: Future<Output = Result<(Vec<T>, Option<String>)>> + 'a,
+{
+  stream::try_unfold((Pager::default(), fetch, what), |(mut pager, mut fetch, what)| async move {
+    if pager.done {
+      return Ok(None);
+    }
+
+    cancel::check()?;
+    pager.page += 1;
+    ensure!(pager.page <= MAX_PAGES, "Gave up listing {what} after {MAX_PAGES} pages");
+    let (items, next_token) = fetch(pager.next_token.take()).await.context(format!("Failed to fetch page {} of {what}", pager.page))?;
+    match next_token.filter(|token| !token.is_empty()) {
+      Some(token) => {
+        ensure!(pager.seen.insert(token.clone()), "Page {} of {what} repeated an earlier pagination token", pager.page);
+        pager.next_token = Some(token);
+      },
+      None => pager.done = true,
     }
+    Ok(Some((items, (pager, fetch, what))))
   })
 }
+
+#[derive(Default)]
+struct Pager {
+  page: usize,
+  next_token: Option<String>,
+  seen: HashSet<String>,
+  done: bool,
+}
+
+pub fn string_filter(key: &str, option: &str, value: &str) -> ParameterStringFilter {
+  ParameterStringFilter::builder().key(key).option(option).values(value).build().expect("filter key is set")
+}
+
+pub fn parse_filter(value: &str) -> Result<ParameterStringFilter> {
+  let (kind, rest) = value.split_once(':').context(format!("Invalid filter {value}; expected e.g. tag:team=payments or type:SecureString"))?;
+  let (key, values) = match kind.to_ascii_lowercase().as_str() {
+    "tag" => match rest.split_once('=') {
+      Some((tag, values)) => (format!("tag:{tag}"), values),
+      None => (format!("tag:{rest}"), ""),
+    },
+    "type" => ("Type".to_string(), canonical(rest, &["String", "StringList", "SecureString"])),
+    "tier" => ("Tier".to_string(), canonical(rest, &["Standard", "Advanced", "Intelligent-Tiering"])),
+    "kms-key" | "key-id" => ("KeyId".to_string(), rest),
+    "data-type" => ("DataType".to_string(), rest),
+    _ => bail!("Unknown filter {kind}; expected tag, type, tier, kms-key or data-type"),
+  };
+  ensure!(key != "tag:", "Invalid filter {value}; the tag key is empty");
+  ensure!(key.starts_with("tag:") || !values.is_empty(), "Invalid filter {value}; the value is empty");
+
+  let values = values.split(',').filter(|v| !v.is_empty()).map(str::to_string).collect::<Vec<_>>();
+  let option = (!values.is_empty()).then_some("Equals");
+  Ok(ParameterStringFilter::builder().key(key).set_option(option.map(str::to_string)).set_values((!values.is_empty()).then_some(values)).build()?)
+}
+
+fn canonical<'a>(value: &'a str, known: &[&'a str]) -> &'a str {
+  known.iter().find(|k| k.eq_ignore_ascii_case(value)).copied().unwrap_or(value)
+}
+
+pub async fn get_parameters(client: &Client, names: &[String]) -> Result<(Vec<Parameter>, Vec<String>)> {
+  cancel::check()?;
+  let responses = try_join_all(names.chunks(GET_BATCH_SIZE).map(|batch| {
+    client
+      .get_parameters()
+      .set_names(Some(batch.to_vec()))
+      .with_decryption(true)
+      .send()
+  }))
+  .await?;
+
+  let parameters = responses.iter().flat_map(|r| r.parameters()).cloned().collect();
+  let invalid = responses.iter().flat_map(|r| r.invalid_parameters()).cloned().collect();
+  Ok((parameters, invalid))
+}
+
+pub fn parameter_history<'a>(client: &'a Client, name: &'a str) -> impl Stream<Item = Result<Vec<ParameterHistory>>> + 'a {
+  paginate(format!("history of {name}"), move |next_token| async move {
+    let resp = retry_throttled(|| {
+      client
+        .get_parameter_history()
+        .with_decryption(true)
+        .name(name)
+        .max_results(HISTORY_PAGE_SIZE)
+        .set_next_token(next_token.clone())
+        .send()
+    })
+    .await?;
+    Ok((resp.parameters().to_vec(), resp.next_token().map(str::to_string)))
+  })
+}
+
+pub async fn retry_throttled<T, E, R, F, Fut>(mut send: F) -> Result<T, SdkError<E, R>>
+where
+  F: FnMut() -> Fut,
+  Fut: Future<Output = Result<T, SdkError<E, R>>>,
+  E: ProvideErrorMetadata,
+{
+  let mut attempt = 0;
+  loop {
+    match send().await {
+      Err(err) if attempt < MAX_THROTTLE_RETRIES && is_throttled(&err) => {
+        attempt += 1;
+        tokio::time::sleep(THROTTLE_BASE_DELAY * 2u32.pow(attempt)).await;
+      }
+      result => return result,
+    }
+  }
+}
+
+fn is_throttled<E: ProvideErrorMetadata, R>(err: &SdkError<E, R>) -> bool {
+  matches!(err.code(), Some("ThrottlingException" | "TooManyUpdates"))
+}
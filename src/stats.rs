@@ -0,0 +1,86 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use aws_sdk_ssm::types::ParameterTier;
+use futures::TryStreamExt;
+use itertools::Itertools;
+
+use crate::files::{MANIFEST_SUFFIX, S3_SUFFIX, logical_name};
+use crate::output::Event;
+use crate::ssm::{self, Client};
+
+pub const ADVANCED_MONTHLY_COST: f64 = 0.05;
+
+#[derive(Debug, Default)]
+struct Item {
+  bytes: usize,
+  parameters: usize,
+  s3: bool,
+}
+
+pub async fn stats(client: &Client, prefix: String, top: usize) -> Result<()> {
+  let params = ssm::all_parameters_by_path(client, &prefix, true).try_collect::<Vec<_>>().await?.into_iter().flatten().collect::<Vec<_>>();
+  let tiers = ssm::all_parameter_metadata_by_path(client, &prefix)
+    .try_collect::<Vec<_>>()
+    .await?
+    .into_iter()
+    .flatten()
+    .filter_map(|m| Some((m.name?, m.tier.unwrap_or(ParameterTier::Standard))))
+    .collect::<HashMap<_, _>>();
+
+  let mut by_type: BTreeMap<(String, String), usize> = BTreeMap::new();
+  let mut items: HashMap<&str, Item> = HashMap::new();
+  let mut total_bytes = 0;
+  for param in &params {
+    let name = param.name().unwrap_or_default();
+    let bytes = param.value().unwrap_or_default().len();
+    let r#type = param.r#type().map(|t| t.as_str()).unwrap_or_default().to_string();
+    let tier = tiers.get(name).map(|t| t.as_str()).unwrap_or(ParameterTier::Standard.as_str()).to_string();
+    *by_type.entry((r#type, tier)).or_default() += 1;
+    total_bytes += bytes;
+
+    let item = items.entry(logical_name(name)).or_default();
+    item.parameters += 1;
+    item.s3 |= name.ends_with(S3_SUFFIX);
+    if !name.ends_with(MANIFEST_SUFFIX) {
+      item.bytes += bytes;
+    }
+  }
+
+  for ((r#type, tier), count) in &by_type {
+    Event::new("stats-type", prefix.as_str())
+      .with("type", r#type.as_str())
+      .with("tier", tier.as_str())
+      .with("parameters", *count)
+      .emit(format!("{type}\t{tier}\t{count}"));
+  }
+
+  for (name, item) in items.iter().sorted_by(|(a_name, a), (b_name, b)| b.bytes.cmp(&a.bytes).then(a_name.cmp(b_name))).take(top) {
+    Event::new("stats-largest", *name)
+      .with("bytes", item.bytes)
+      .with("parameters", item.parameters)
+      .emit(format!("{}\t{name} ({} parameters)", item.bytes, item.parameters));
+  }
+
+  let chunked = items.values().filter(|item| item.parameters > 1).count();
+  let offloaded = items.values().filter(|item| item.s3).count();
+  let advanced = tiers.values().filter(|tier| **tier != ParameterTier::Standard).count();
+  let advanced_cost = advanced as f64 * ADVANCED_MONTHLY_COST;
+  let all_advanced_cost = params.len() as f64 * ADVANCED_MONTHLY_COST;
+
+  Event::new("stats", prefix.as_str())
+    .with("parameters", params.len())
+    .with("files", items.len())
+    .with("chunked_files", chunked)
+    .with("s3_files", offloaded)
+    .with("bytes", total_bytes)
+    .with("advanced", advanced)
+    .with("advanced_monthly_usd", advanced_cost)
+    .with("all_advanced_monthly_usd", all_advanced_cost)
+    .emit(format!(
+      "{} parameters ({} files, {chunked} chunked, {offloaded} in S3), {total_bytes} bytes under {prefix}\n{advanced} advanced parameters cost ~${advanced_cost:.2}/month; all advanced would cost ~${all_advanced_cost:.2}/month",
+      params.len(),
+      items.len()
+    ));
+  Ok(())
+}
@@ -0,0 +1,117 @@
+use std::{collections::HashSet, path::Path};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use aws_config::SdkConfig;
+use aws_sdk_ssm::types::ParameterType;
+use clap::ValueEnum;
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::files::{assemble, fetch_file_at, logical_name, physical_names};
+use crate::local::LocalStore;
+use crate::pin::{self, Pin};
+use crate::s3;
+use crate::secrets_manager::SecretsManagerStore;
+use crate::ssm::{self, Client};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum Backend {
+  #[default]
+  Ssm,
+  SecretsManager,
+  S3,
+  Local,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+  pub name: String,
+  pub value: Vec<u8>,
+  pub string_list: bool,
+}
+
+#[async_trait]
+pub trait SecretStore: Send + Sync {
+  async fn fetch_prefix(&self, prefix: &str, pin: Option<&Pin>) -> Result<Vec<Entry>>;
+
+  async fn fetch_names(&self, names: &[String], pin: Option<&Pin>) -> Result<(Vec<Entry>, Vec<String>)>;
+}
+
+impl Backend {
+  pub fn is_ssm(self) -> bool {
+    matches!(self, Backend::Ssm | Backend::S3)
+  }
+}
+
+pub fn store_with(backend: Backend, config: &SdkConfig, client: &Client, root: Option<&Path>) -> Result<Box<dyn SecretStore>> {
+  Ok(match backend {
+    Backend::Ssm | Backend::S3 => Box::new(SsmStore::new(client.clone(), aws_sdk_s3::Client::new(config))),
+    Backend::SecretsManager => Box::new(SecretsManagerStore::new(aws_sdk_secretsmanager::Client::new(config))),
+    Backend::Local => Box::new(LocalStore::new(root.context("--root is required with --backend local")?.to_path_buf())),
+  })
+}
+
+pub struct SsmStore {
+  client: Client,
+  s3: aws_sdk_s3::Client,
+}
+
+impl SsmStore {
+  pub fn new(client: Client, s3: aws_sdk_s3::Client) -> Self {
+    Self { client, s3 }
+  }
+}
+
+#[async_trait]
+impl SecretStore for SsmStore {
+  async fn fetch_prefix(&self, prefix: &str, pin: Option<&Pin>) -> Result<Vec<Entry>> {
+    let prefix = prefix.trim_end_matches('/');
+    let params = match pin {
+      Some(pin) => {
+        let names = ssm::all_parameter_metadata_by_path(&self.client, prefix).try_collect::<Vec<_>>().await?.into_iter().flatten().filter_map(|m| m.name).collect::<Vec<_>>();
+        pin::get_parameters(&self.client, &names, Some(pin)).await?.0
+      },
+      None => ssm::all_parameters_by_path(&self.client, prefix, true).try_collect::<Vec<_>>().await?.into_iter().flatten().collect(),
+    };
+
+    let rel_path = |name: &str| name.trim_start_matches(&format!("{prefix}/")).to_string();
+    let string_lists = params
+      .iter()
+      .filter(|p| p.r#type() == Some(&ParameterType::StringList))
+      .map(|p| rel_path(logical_name(p.name().unwrap_or_default())))
+      .collect::<HashSet<_>>();
+
+    let files = assemble(params.iter().map(|p| (rel_path(p.name().unwrap_or_default()), p.value().unwrap_or_default().to_string())))?;
+    let files = s3::resolve_pointers(Some(&self.s3), files).await?;
+
+    Ok(
+      files
+        .into_iter()
+        .map(|(name, value)| {
+          let string_list = string_lists.contains(&name);
+          Entry { name, value, string_list }
+        })
+        .collect(),
+    )
+  }
+
+  async fn fetch_names(&self, names: &[String], pin: Option<&Pin>) -> Result<(Vec<Entry>, Vec<String>)> {
+    let (parameters, invalid) = pin::get_parameters(&self.client, names, pin).await?;
+
+    let mut entries = parameters
+      .iter()
+      .map(|p| Entry { name: p.name().unwrap_or_default().to_string(), value: p.value().unwrap_or_default().as_bytes().to_vec(), string_list: p.r#type() == Some(&ParameterType::StringList) })
+      .collect::<Vec<_>>();
+    let mut missing = vec![];
+    for name in invalid {
+      if physical_names(&self.client, &name).await?.is_empty() {
+        missing.push(name);
+        continue;
+      }
+      let value = fetch_file_at(&self.client, Some(&self.s3), &name, pin).await?;
+      entries.push(Entry { name, value, string_list: false });
+    }
+    Ok((entries, missing))
+  }
+}
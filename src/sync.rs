@@ -0,0 +1,91 @@
+use std::{collections::HashSet, fs, path::PathBuf};
+
+use anyhow::{Context, Result};
+use futures::TryStreamExt;
+
+use crate::delete::delete_names;
+use crate::download::{ensure_no_symlink_parents, safe_join, write_atomic, write_symlink};
+use crate::files::{fetch_files_with_metadata, logical_name, to_ssm_key};
+use crate::output::Event;
+use crate::ssm::{self, Client};
+use crate::upload::{UploadOptions, local_files, parameter_name, scan_local_files, upload_dir};
+
+pub async fn sync_to_ssm(client: &Client, dir: PathBuf, prefix: String, options: UploadOptions, delete: bool) -> Result<()> {
+  let (files, skipped) = scan_local_files(&dir, &options)?;
+  let local = files.iter().map(|path| parameter_name(&prefix, path.strip_prefix(&dir).unwrap_or(path))).collect::<HashSet<_>>();
+  let kept = skipped.iter().map(|file| parameter_name(&prefix, file.path.strip_prefix(&dir).unwrap_or(&file.path))).collect::<Vec<_>>();
+  let dry_run = options.dry_run;
+
+  upload_dir(client, dir, prefix.clone(), options).await?;
+
+  if !delete {
+    return Ok(());
+  }
+
+  let stale = ssm::all_parameter_metadata_by_path(client, &prefix)
+    .try_collect::<Vec<_>>()
+    .await?
+    .into_iter()
+    .flatten()
+    .filter_map(|p| p.name)
+    .filter(|name| {
+      let logical = logical_name(name);
+      !local.contains(logical) && !kept.iter().any(|kept| logical == kept || logical.starts_with(&format!("{kept}/")))
+    })
+    .collect::<Vec<_>>();
+
+  delete_names(client, &stale, dry_run).await
+}
+
+pub async fn sync_from_ssm(client: &Client, s3: Option<&aws_sdk_s3::Client>, prefix: String, dir: PathBuf, options: UploadOptions) -> Result<()> {
+  let (remote, metadata) = fetch_files_with_metadata(client, s3, &prefix).await?;
+  let local = local_files(&dir, &options)?;
+  let (mut written, mut unchanged, mut removed) = (0, 0, 0);
+
+  for (rel_path, content) in &remote {
+    let path = safe_join(&dir, rel_path)?;
+    ensure_no_symlink_parents(&dir, rel_path)?;
+    let symlink = metadata.get(rel_path).is_some_and(|m| m.is_symlink());
+    let current = match symlink {
+      true => fs::read_link(&path).ok().map(|target| target.to_string_lossy().into_owned().into_bytes()),
+      false => fs::read(&path).ok(),
+    };
+    if current.as_ref() == Some(content) {
+      unchanged += 1;
+      continue;
+    }
+
+    if options.dry_run {
+      Event::new("would-write", rel_path.as_str()).emit(format!("Would write {}", path.display()));
+    } else if symlink {
+      write_symlink(&path, &String::from_utf8_lossy(content)).context(format!("Failed to create the symlink {}", path.display()))?;
+      Event::new("write", rel_path.as_str()).emit(format!("Linked {}", path.display()));
+    } else {
+      write_atomic(&path, content).context(format!("Failed to write {}", path.display()))?;
+      Event::new("write", rel_path.as_str()).emit(format!("Wrote {}", path.display()));
+    }
+    written += 1;
+  }
+
+  for path in local {
+    let rel_path = to_ssm_key(path.strip_prefix(&dir).unwrap_or(&path));
+    if remote.contains_key(rel_path.trim_start_matches('/')) {
+      continue;
+    }
+
+    if options.dry_run {
+      Event::new("would-remove", rel_path.trim_start_matches('/')).emit(format!("Would remove {}", path.display()));
+    } else {
+      fs::remove_file(&path).context(format!("Failed to remove {}", path.display()))?;
+      Event::new("remove", rel_path.trim_start_matches('/')).emit(format!("Removed {}", path.display()));
+    }
+    removed += 1;
+  }
+
+  Event::new("summary", prefix.as_str())
+    .with("written", written)
+    .with("unchanged", unchanged)
+    .with("removed", removed)
+    .emit(format!("{written} written, {unchanged} unchanged, {removed} removed"));
+  Ok(())
+}
@@ -0,0 +1,63 @@
+use std::{path::{Path, PathBuf}, process::Command};
+
+use anyhow::{Context, Result, ensure};
+
+use crate::download::{PRIVATE_MODE, write_atomic_with_mode};
+use crate::output::Event;
+
+pub const SYSTEMD_DIR: &str = "/etc/systemd/system";
+const ENV_FILE_NAME: &str = "ops.env";
+const DROP_IN_NAME: &str = "ops.conf";
+const DROP_IN_MODE: u32 = 0o644;
+
+#[derive(Debug, Clone)]
+pub struct SystemdOptions {
+  pub unit: String,
+  pub dir: PathBuf,
+  pub restart: bool,
+}
+
+pub fn unit_name(unit: &str) -> Result<String> {
+  ensure!(!unit.is_empty() && !unit.contains('/'), "Invalid systemd unit {unit}");
+  Ok(if unit.contains('.') { unit.to_string() } else { format!("{unit}.service") })
+}
+
+pub fn install(env: &str, vars: usize, options: &SystemdOptions) -> Result<()> {
+  let unit = unit_name(&options.unit)?;
+  let drop_in_dir = options.dir.join(format!("{unit}.d"));
+  let env_path = drop_in_dir.join(ENV_FILE_NAME);
+  let drop_in_path = drop_in_dir.join(DROP_IN_NAME);
+
+  write_atomic_with_mode(&env_path, format!("{env}\n").as_bytes(), PRIVATE_MODE).context(format!("Failed to write {}", env_path.display()))?;
+  let drop_in = format!("[Service]\nEnvironmentFile={}\n", env_path.display());
+  write_atomic_with_mode(&drop_in_path, drop_in.as_bytes(), DROP_IN_MODE).context(format!("Failed to write {}", drop_in_path.display()))?;
+  for path in [&env_path, &drop_in_path] {
+    chown_root(path);
+  }
+
+  let path = env_path.display().to_string();
+  Event::new("write", path.as_str()).with("vars", vars).with("unit", unit.as_str()).emit(format!("Wrote {vars} vars to {path} for {unit}"));
+
+  if options.restart {
+    systemctl(&["daemon-reload"])?;
+    systemctl(&["restart", &unit])?;
+    Event::new("restart", unit.as_str()).emit(format!("Restarted {unit}"));
+  }
+  Ok(())
+}
+
+fn systemctl(args: &[&str]) -> Result<()> {
+  let status = Command::new("systemctl").args(args).status().context("Failed to run systemctl")?;
+  ensure!(status.success(), "systemctl {} failed with {status}", args.join(" "));
+  Ok(())
+}
+
+#[cfg(unix)]
+fn chown_root(path: &Path) {
+  if let Err(err) = std::os::unix::fs::chown(path, Some(0), Some(0)) {
+    tracing::warn!("Couldn't make {} owned by root: {err}", path.display());
+  }
+}
+
+#[cfg(not(unix))]
+fn chown_root(_path: &Path) {}
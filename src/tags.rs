@@ -0,0 +1,112 @@
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result, ensure};
+use aws_sdk_ssm::types::{ResourceTypeForTagging, Tag};
+use futures::TryStreamExt;
+
+use crate::cancel;
+use crate::output::Event;
+use crate::ssm::{self, Client};
+
+pub const CHANGE_REASON_TAG: &str = "ops:change-reason";
+const MAX_TAG_VALUE_LEN: usize = 256;
+
+static CHANGE_REASON: OnceLock<Tag> = OnceLock::new();
+
+pub fn parse_change_reason(value: &str) -> Result<String> {
+  ensure!(!value.trim().is_empty(), "--change-reason must not be empty");
+  ensure!(value.chars().count() <= MAX_TAG_VALUE_LEN, "--change-reason must be at most {MAX_TAG_VALUE_LEN} characters");
+  ensure!(
+    value.chars().all(|c| c.is_alphanumeric() || c.is_whitespace() || "_.:/=+-@".contains(c)),
+    "--change-reason may only contain letters, numbers, spaces and _.:/=+-@"
+  );
+  Ok(value.to_string())
+}
+
+pub fn set_change_reason(reason: &str) -> Result<()> {
+  let _ = CHANGE_REASON.set(Tag::builder().key(CHANGE_REASON_TAG).value(reason).build()?);
+  Ok(())
+}
+
+pub fn with_change_reason(tags: &[Tag]) -> Vec<Tag> {
+  tags.iter().filter(|tag| CHANGE_REASON.get().is_none() || tag.key() != CHANGE_REASON_TAG).cloned().chain(CHANGE_REASON.get().cloned()).collect()
+}
+
+pub fn parse_tag(value: &str) -> Result<Tag> {
+  let (key, value) = value.split_once('=').context(format!("Tag {value} must be in key=value form"))?;
+  Ok(Tag::builder().key(key).value(value).build()?)
+}
+
+pub async fn add_tags(client: &Client, name: &str, tags: &[Tag]) -> Result<()> {
+  if tags.is_empty() {
+    return Ok(());
+  }
+
+  ssm::ensure_writable("ssm:AddTagsToResource", name)?;
+  ssm::retry_throttled(|| {
+    client
+      .add_tags_to_resource()
+      .resource_type(ResourceTypeForTagging::Parameter)
+      .resource_id(name)
+      .set_tags(Some(tags.to_vec()))
+      .send()
+  })
+  .await
+  .context(format!("Failed to tag {name}"))?;
+  Ok(())
+}
+
+pub async fn list_tags(client: &Client, name: &str) -> Result<Vec<Tag>> {
+  let resp = ssm::retry_throttled(|| {
+    client
+      .list_tags_for_resource()
+      .resource_type(ResourceTypeForTagging::Parameter)
+      .resource_id(name)
+      .send()
+  })
+  .await
+  .context(format!("Failed to list tags for {name}"))?;
+  Ok(resp.tag_list().to_vec())
+}
+
+pub async fn remove_tags(client: &Client, name: &str, keys: &[String]) -> Result<()> {
+  if keys.is_empty() {
+    return Ok(());
+  }
+
+  ssm::ensure_writable("ssm:RemoveTagsFromResource", name)?;
+  ssm::retry_throttled(|| {
+    client
+      .remove_tags_from_resource()
+      .resource_type(ResourceTypeForTagging::Parameter)
+      .resource_id(name)
+      .set_tag_keys(Some(keys.to_vec()))
+      .send()
+  })
+  .await
+  .context(format!("Failed to untag {name}"))?;
+  Ok(())
+}
+
+pub async fn tag_prefix(client: &Client, prefix: String, add: Vec<Tag>, remove: Vec<String>, dry_run: bool) -> Result<()> {
+  let names = ssm::all_parameter_metadata_by_path(client, &prefix)
+    .try_collect::<Vec<_>>()
+    .await?
+    .into_iter()
+    .flatten()
+    .filter_map(|p| p.name);
+
+  for name in names {
+    cancel::check()?;
+    if dry_run {
+      Event::new("would-tag", name.as_str()).emit(format!("Would tag {name} (+{} -{})", add.len(), remove.len()));
+      continue;
+    }
+
+    add_tags(client, &name, &add).await?;
+    remove_tags(client, &name, &remove).await?;
+    Event::new("tag", name.as_str()).emit(format!("Tagged {name}"));
+  }
+
+  Ok(())
+}
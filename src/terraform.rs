@@ -0,0 +1,115 @@
+use std::{collections::HashSet, fs, io::Write, path::PathBuf};
+
+use anyhow::{Context, Result};
+use aws_sdk_ssm::types::{ParameterMetadata, ParameterTier, ParameterType};
+use futures::TryStreamExt;
+use itertools::Itertools;
+
+use crate::copy::DEFAULT_KMS_KEY;
+use crate::files::logical_name;
+use crate::output::Event;
+use crate::ssm::{self, Client};
+use crate::tags::list_tags;
+
+pub async fn import_blocks(client: &Client, prefix: String, out: Option<PathBuf>) -> Result<()> {
+  let metadata = ssm::all_parameter_metadata_by_path(client, &prefix)
+    .try_collect::<Vec<_>>()
+    .await?
+    .into_iter()
+    .flatten()
+    .filter(|m| m.name().is_some())
+    .sorted_by(|a, b| a.name().cmp(&b.name()))
+    .collect::<Vec<_>>();
+
+  let mut addresses = HashSet::new();
+  let mut skipped = HashSet::new();
+  let mut blocks = vec![];
+  for m in &metadata {
+    let name = m.name().unwrap_or_default();
+    let logical = logical_name(name);
+    if logical != name {
+      if skipped.insert(logical) {
+        tracing::warn!("Skipping {logical}: chunked and S3-backed files are managed by ops");
+      }
+      continue;
+    }
+    let address = unique_address(&mut addresses, resource_name(name.trim_start_matches(prefix.trim_end_matches('/'))));
+    let tags = list_tags(client, name).await?.into_iter().map(|t| (t.key, t.value)).collect::<Vec<_>>();
+    blocks.push(render(&address, m, &tags));
+  }
+
+  let output = blocks.join("\n");
+  match &out {
+    Some(out) => {
+      fs::write(out, &output).context(format!("Failed to write {}", out.display()))?;
+      Event::new("write", out.display().to_string()).with("resources", blocks.len()).emit(format!("Wrote {} import blocks to {}", blocks.len(), out.display()));
+    },
+    None => {
+      let mut stdout = std::io::stdout().lock();
+      stdout.write_all(output.as_bytes())?;
+      stdout.flush()?;
+    },
+  }
+  Ok(())
+}
+
+fn resource_name(rel_path: &str) -> String {
+  let name = rel_path.trim_matches('/').chars().map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' }).collect::<String>();
+  match name.chars().next() {
+    Some(c) if c.is_ascii_alphabetic() || c == '_' => name,
+    _ => format!("_{name}"),
+  }
+}
+
+fn unique_address(seen: &mut HashSet<String>, name: String) -> String {
+  let address = (1..).map(|i| if i == 1 { name.clone() } else { format!("{name}_{i}") }).find(|candidate| !seen.contains(candidate)).expect("unbounded range");
+  seen.insert(address.clone());
+  address
+}
+
+fn render(address: &str, m: &ParameterMetadata, tags: &[(String, String)]) -> String {
+  let name = m.name().unwrap_or_default();
+  let r#type = m.r#type().cloned().unwrap_or(ParameterType::String);
+  let mut attributes = vec![("name", quote(name)), ("type", quote(r#type.as_str()))];
+  if let Some(tier) = m.tier().filter(|tier| **tier != ParameterTier::Standard) {
+    attributes.push(("tier", quote(tier.as_str())));
+  }
+  if let Some(key_id) = m.key_id().filter(|key| r#type == ParameterType::SecureString && *key != DEFAULT_KMS_KEY) {
+    attributes.push(("key_id", quote(key_id)));
+  }
+  if let Some(description) = m.description().filter(|d| !d.is_empty()) {
+    attributes.push(("description", quote(description)));
+  }
+  if let Some(data_type) = m.data_type().filter(|d| *d != "text") {
+    attributes.push(("data_type", quote(data_type)));
+  }
+  if let Some(pattern) = m.allowed_pattern() {
+    attributes.push(("allowed_pattern", quote(pattern)));
+  }
+  attributes.push(("value", quote("managed outside Terraform")));
+
+  let width = attributes.iter().map(|(key, _)| key.len()).max().unwrap_or_default();
+  let mut body = attributes.iter().map(|(key, value)| format!("  {key:<width$} = {value}")).collect::<Vec<_>>();
+  if !tags.is_empty() {
+    let tag_width = tags.iter().map(|(key, _)| quote(key).len()).max().unwrap_or_default();
+    body.push(String::new());
+    body.push("  tags = {".to_string());
+    body.extend(tags.iter().map(|(key, value)| format!("    {:<tag_width$} = {}", quote(key), quote(value))));
+    body.push("  }".to_string());
+  }
+  body.push(String::new());
+  body.push("  lifecycle {".to_string());
+  body.push("    ignore_changes = [value]".to_string());
+  body.push("  }".to_string());
+
+  format!(
+    "import {{\n  to = aws_ssm_parameter.{address}\n  id = {}\n}}\n\nresource \"aws_ssm_parameter\" \"{address}\" {{\n{}\n}}\n",
+    quote(name),
+    body.join("\n")
+  )
+}
+
+fn quote(value: &str) -> String {
+  let escaped = value.replace('\\', r"\\").replace('"', r#"\""#).replace('\n', r"\n").replace("${", "$${").replace("%{", "%%{");
+  format!("\"{escaped}\"")
+}
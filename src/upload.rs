@@ -0,0 +1,541 @@
+use std::{collections::HashMap, path::{Path, PathBuf}, time::UNIX_EPOCH};
+
+use anyhow::{Context, Result, ensure};
+use aws_sdk_ssm::types::{ParameterMetadata, ParameterTier, ParameterType, Tag};
+use clap::ValueEnum;
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use futures::{StreamExt, TryStreamExt, stream};
+use itertools::Itertools;
+use sha2::{Digest, Sha256};
+use tokio::fs as tokio_fs;
+
+use crate::files::{BASE64_SUFFIX, FileKind, FileMetadata, SymlinkPolicy, WalkOptions, logical_name, physical_names, HASH_DESCRIPTION_PREFIX, MANIFEST_SUFFIX, S3_SUFFIX, Manifest, max_chunk_size, parameter_quota, to_ssm_key, walk_files};
+use crate::cancel;
+use crate::compress::Compression;
+use crate::copy::DEFAULT_KMS_KEY;
+use crate::crypto::{self, Encryption};
+use crate::delete::DELETE_BATCH_SIZE;
+use crate::filter::PathFilter;
+use crate::output::Event;
+use crate::policy::{self, Policy};
+use crate::s3::{self, S3Target};
+use crate::progress::Progress;
+use crate::ssm::{self, Client};
+use crate::tags::{add_tags, with_change_reason};
+use crate::validate::{self, Syntax};
+
+#[derive(Debug, Clone)]
+pub struct UploadOptions {
+  pub concurrency: usize,
+  pub force: bool,
+  pub r#type: ParameterType,
+  pub kms_key_id: Option<String>,
+  pub tier: ParameterTier,
+  pub chunk_size: Option<usize>,
+  pub max_parts: Option<usize>,
+  pub tags: Vec<Tag>,
+  pub policies: Vec<Policy>,
+  pub validate: Option<Syntax>,
+  pub include: Vec<String>,
+  pub exclude: Vec<String>,
+  pub gitignore: bool,
+  pub symlinks: SymlinkPolicy,
+  pub include_hidden: bool,
+  pub max_file_size: Option<u64>,
+  pub s3: Option<S3Target>,
+  pub encrypt_with: Vec<age::x25519::Recipient>,
+  pub compress: Option<Compression>,
+  pub preserve_mode: bool,
+  pub preserve_mtime: bool,
+  pub fail_fast: bool,
+  pub dry_run: bool,
+}
+
+impl Default for UploadOptions {
+  fn default() -> Self {
+    Self {
+      concurrency: 4,
+      force: false,
+      r#type: ParameterType::SecureString,
+      kms_key_id: None,
+      tier: ParameterTier::Standard,
+      chunk_size: None,
+      max_parts: None,
+      tags: vec![],
+      policies: vec![],
+      validate: None,
+      include: vec![],
+      exclude: vec![],
+      gitignore: false,
+      symlinks: SymlinkPolicy::default(),
+      include_hidden: false,
+      max_file_size: None,
+      s3: None,
+      encrypt_with: vec![],
+      compress: None,
+      preserve_mode: false,
+      preserve_mtime: false,
+      fail_fast: false,
+      dry_run: false,
+    }
+  }
+}
+
+impl UploadOptions {
+  pub fn resolved_chunk_size(&self) -> Result<usize> {
+    let max_chunk_size = max_chunk_size(&self.tier);
+    let chunk_size = self.chunk_size.unwrap_or(max_chunk_size);
+    ensure!(chunk_size > 0 && chunk_size <= max_chunk_size, "Chunk size must be between 1 and {max_chunk_size} bytes for the {} tier", self.tier.as_str());
+    Ok(chunk_size)
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct SkippedFile {
+  pub path: PathBuf,
+  pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct PreparedFile {
+  pub description: String,
+  pub parts: Vec<(String, String)>,
+  pub object: Option<(String, Vec<u8>)>,
+  pub unchanged: bool,
+}
+
+pub fn prepare(name: &str, content: Vec<u8>, options: &UploadOptions) -> Result<PreparedFile> {
+  prepare_with_metadata(name, content, FileMetadata::default(), options)
+}
+
+pub fn prepare_with_metadata(name: &str, content: Vec<u8>, metadata: FileMetadata, options: &UploadOptions) -> Result<PreparedFile> {
+  let chunk_size = options.resolved_chunk_size()?;
+  if !metadata.is_symlink() {
+    validate::validate(name, &content, options.validate)?;
+  }
+  let content = stored_content(name, content, options)?;
+
+  let description = format!("{HASH_DESCRIPTION_PREFIX}{}{}", hex::encode(Sha256::digest(&content)), encoding_suffix(options));
+  ensure!(options.encrypt_with.is_empty() || options.r#type != ParameterType::StringList, "{name} can't be encrypted as a StringList");
+  ensure!(options.compress.is_none() || options.r#type != ParameterType::StringList, "{name} can't be compressed as a StringList");
+  ensure!(options.policies.is_empty() || options.tier != ParameterTier::Standard, "Parameter policies on {name} require the advanced or intelligent-tiering tier");
+  let encrypt = |content: Vec<u8>| match options.encrypt_with.as_slice() {
+    [] => Ok(content),
+    recipients => crypto::encrypt_armored(recipients, &content).context(format!("Failed to encrypt {name}")),
+  };
+
+  let packed = match options.compress {
+    Some(compression) => encrypt(compression.compress(&content).context(format!("Failed to compress {name}"))?)?,
+    None => encrypt(content.clone())?,
+  };
+
+  if let Some(target) = &options.s3 && packed.len() > chunk_size {
+    let key = s3::object_key(name);
+    let object = if options.compress.is_some() { encrypt(content)? } else { packed };
+    let parts = vec![(format!("{name}{S3_SUFFIX}"), s3::pointer(&target.bucket, &key))];
+    return Ok(PreparedFile { description, parts, object: Some((key, object)), unchanged: false });
+  }
+
+  let hash = hex::encode(Sha256::digest(&packed));
+  let (param_base, content) = match String::from_utf8(packed) {
+    Ok(text) => (name.to_string(), text),
+    Err(err) => (format!("{name}{BASE64_SUFFIX}"), BASE64.encode(err.into_bytes())),
+  };
+
+  let encryption = (!options.encrypt_with.is_empty()).then_some(Encryption::Age);
+  let parts = if content.len() > chunk_size || options.compress.is_some() || encryption.is_some() || !metadata.is_empty() {
+    let mut parts = split_chunks(&content, chunk_size)
+      .into_iter()
+      .enumerate()
+      .map(|(i, chunk)| (format!("{}.part{}", param_base, i), chunk.to_string()))
+      .collect::<Vec<_>>();
+    let manifest = Manifest { parts: parts.len(), sha256: hash, compression: options.compress, encryption, mode: metadata.mode, mtime: metadata.mtime, kind: metadata.kind };
+    if let Some(max_parts) = options.max_parts {
+      ensure!(parts.len() <= max_parts, "{name} would be split into {} parts, more than --max-parts {max_parts}", parts.len());
+    }
+    parts.push((format!("{param_base}{MANIFEST_SUFFIX}"), serde_json::to_string(&manifest)?));
+    parts
+  } else {
+    vec![(param_base, content)]
+  };
+
+  Ok(PreparedFile { description, parts, object: None, unchanged: false })
+}
+
+fn encoding_suffix(options: &UploadOptions) -> String {
+  let compression = options.compress.and_then(|c| c.to_possible_value()).map(|c| format!(" {}", c.get_name()));
+  let recipients = (!options.encrypt_with.is_empty()).then(|| {
+    let recipients = options.encrypt_with.iter().map(ToString::to_string).sorted().join(",");
+    format!(" age:{}", &hex::encode(Sha256::digest(recipients))[..16])
+  });
+  format!("{}{}", compression.unwrap_or_default(), recipients.unwrap_or_default())
+}
+
+fn is_unchanged(file: &PreparedFile, existing: &HashMap<String, ParameterMetadata>, options: &UploadOptions) -> bool {
+  let key_id = match options.r#type {
+    ParameterType::SecureString => Some(options.kms_key_id.as_deref().unwrap_or(DEFAULT_KMS_KEY)),
+    _ => None,
+  };
+  file.parts.iter().all(|(name, _)| {
+    existing.get(name).is_some_and(|meta| {
+      meta.description() == Some(file.description.as_str())
+        && meta.r#type() == Some(&options.r#type)
+        && meta.key_id() == key_id
+        && (options.tier != ParameterTier::Advanced || meta.tier() == Some(&ParameterTier::Advanced))
+    })
+  })
+}
+
+fn split_chunks(content: &str, chunk_size: usize) -> Vec<&str> {
+  let mut chunks = vec![];
+  let mut rest = content;
+  while !rest.is_empty() {
+    let mut end = chunk_size.min(rest.len());
+    while !rest.is_char_boundary(end) {
+      end -= 1;
+    }
+    if end == 0 {
+      end = rest.chars().next().map_or(rest.len(), char::len_utf8);
+    }
+    let (chunk, tail) = rest.split_at(end);
+    chunks.push(chunk);
+    rest = tail;
+  }
+  chunks
+}
+
+pub fn stored_content(name: &str, content: Vec<u8>, options: &UploadOptions) -> Result<Vec<u8>> {
+  match options.r#type {
+    ParameterType::StringList => to_string_list(name, content),
+    _ => Ok(content),
+  }
+}
+
+fn to_string_list(name: &str, content: Vec<u8>) -> Result<Vec<u8>> {
+  let text = String::from_utf8(content).context(format!("{name} must be UTF-8 to upload as a StringList"))?;
+  let items = text.lines().map(str::trim).filter(|l| !l.is_empty()).collect::<Vec<_>>();
+  ensure!(items.iter().all(|item| !item.contains(',')), "{name} has a line containing a comma, which a StringList item can't hold");
+  Ok(items.join(",").into_bytes())
+}
+
+pub async fn upload_dir(client: &Client, dir: PathBuf, prefix: String, options: UploadOptions) -> Result<()> {
+  options.resolved_chunk_size()?;
+
+  let existing = ssm::all_parameter_metadata_by_path(client, &prefix)
+    .try_collect::<Vec<_>>()
+    .await?
+    .into_iter()
+    .flatten()
+    .filter_map(|p| Some((p.name.clone()?, p)))
+    .collect::<HashMap<_, _>>();
+
+  let (paths, skipped) = scan_local_files(&dir, &options)?;
+  let mut files = vec![];
+  for path in paths {
+    let rel_path = path.strip_prefix(&dir)?;
+    let (content, metadata) = read_local_file(&path, &options).await?;
+
+    let param_base = parameter_name(&prefix, rel_path);
+    let mut file = prepare_with_metadata(&param_base, content, metadata, &options)?;
+
+    if !options.force && is_unchanged(&file, &existing, &options) {
+      Event::new("unchanged", param_base.as_str()).emit(format!("Unchanged {}", rel_path.display()));
+      file.unchanged = true;
+    }
+
+    files.push(file);
+  }
+
+  check_quota(client, &files, &existing, &options).await?;
+  let result = put_files(client, files, &options).await;
+  warn_skipped(&dir, &skipped);
+  result
+}
+
+fn warn_skipped(dir: &Path, skipped: &[SkippedFile]) {
+  if skipped.is_empty() {
+    return;
+  }
+
+  let list = skipped.iter().map(|file| format!("  {} ({})", file.path.strip_prefix(dir).unwrap_or(&file.path).display(), file.reason)).collect::<Vec<_>>().join("\n");
+  tracing::warn!("Skipped {} file(s):\n{list}", skipped.len());
+}
+
+async fn check_quota(client: &Client, files: &[PreparedFile], existing: &HashMap<String, ParameterMetadata>, options: &UploadOptions) -> Result<()> {
+  let created = files.iter().filter(|file| !file.unchanged).flat_map(|file| &file.parts).filter(|(name, _)| !existing.contains_key(name)).count();
+  if created == 0 {
+    return Ok(());
+  }
+
+  let quota = parameter_quota(&options.tier);
+  let filters = match options.tier {
+    ParameterTier::Standard => vec![ssm::string_filter("Tier", "Equals", ParameterTier::Standard.as_str())],
+    _ => vec![],
+  };
+  let current = ssm::describe_parameters(client, filters).try_fold(0, |count, page| async move { Ok(count + page.len()) }).await?;
+  let total = current + created;
+  tracing::debug!("Upload creates {created} parameters; {total} in the account afterwards");
+
+  ensure!(
+    total <= quota,
+    "Upload would create {created} parameters, leaving {total} in the account, over the {quota} parameter quota for the {} tier",
+    options.tier.as_str()
+  );
+  if total * 10 > quota * 8 {
+    tracing::warn!("Upload leaves {total} parameters in the account, close to the {quota} parameter quota for the {} tier", options.tier.as_str());
+  }
+  Ok(())
+}
+
+pub async fn read_local_file(path: &Path, options: &UploadOptions) -> Result<(Vec<u8>, FileMetadata)> {
+  match options.symlinks == SymlinkPolicy::Preserve && tokio_fs::symlink_metadata(path).await?.is_symlink() {
+    true => Ok((symlink_target(path).await?, FileMetadata { kind: Some(FileKind::Symlink), ..Default::default() })),
+    false => Ok((tokio_fs::read(path).await?, file_metadata(path, options).await?)),
+  }
+}
+
+async fn symlink_target(path: &Path) -> Result<Vec<u8>> {
+  let target = tokio_fs::read_link(path).await.context(format!("Failed to read the symlink {}", path.display()))?;
+  let target = target.to_str().context(format!("The target of the symlink {} is not UTF-8", path.display()))?;
+  Ok(target.as_bytes().to_vec())
+}
+
+async fn file_metadata(path: &Path, options: &UploadOptions) -> Result<FileMetadata> {
+  let metadata = tokio_fs::metadata(path).await?;
+  let mtime = metadata.modified()?.duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).ok();
+
+  Ok(FileMetadata {
+    mode: options.preserve_mode.then(|| file_mode(&metadata)).flatten(),
+    mtime: options.preserve_mtime.then_some(mtime).flatten(),
+    kind: None,
+  })
+}
+
+#[cfg(unix)]
+pub fn file_mode(metadata: &std::fs::Metadata) -> Option<u32> {
+  use std::os::unix::fs::PermissionsExt;
+
+  Some(metadata.permissions().mode() & 0o777)
+}
+
+#[cfg(not(unix))]
+pub fn file_mode(_metadata: &std::fs::Metadata) -> Option<u32> {
+  None
+}
+
+pub fn local_files(dir: &Path, options: &UploadOptions) -> Result<Vec<PathBuf>> {
+  Ok(scan_local_files(dir, options)?.0)
+}
+
+pub fn scan_local_files(dir: &Path, options: &UploadOptions) -> Result<(Vec<PathBuf>, Vec<SkippedFile>)> {
+  let filter = PathFilter::for_dir(dir, &options.include, &options.exclude)?;
+  let walk_options = WalkOptions { gitignore: options.gitignore, symlinks: options.symlinks, include_hidden: options.include_hidden };
+  let walk = walk_files(dir, &filter, walk_options)?;
+  let mut skipped = walk.hidden.into_iter().map(|path| SkippedFile { path, reason: "hidden; use --include-hidden".to_string() }).collect::<Vec<_>>();
+
+  let mut files = vec![];
+  for path in walk.files.into_iter().filter(|path| filter.is_included(path.strip_prefix(dir).unwrap_or(path))) {
+    let metadata = match options.symlinks {
+      SymlinkPolicy::Preserve => std::fs::symlink_metadata(&path)?,
+      _ => std::fs::metadata(&path)?,
+    };
+    match options.max_file_size.filter(|max| metadata.len() > *max && !metadata.is_symlink()) {
+      Some(_) if options.s3.is_some() => {
+        tracing::debug!("{} is {} bytes, routing it to S3", path.display(), metadata.len());
+        files.push(path);
+      },
+      Some(max) => skipped.push(SkippedFile { path, reason: format!("{} bytes, over --max-file-size {max}", metadata.len()) }),
+      None => files.push(path),
+    }
+  }
+  Ok((files, skipped))
+}
+
+pub async fn read_local_tree(dir: &Path, prefix: &str, options: &UploadOptions) -> Result<(HashMap<String, Vec<u8>>, Vec<String>)> {
+  let (paths, skipped) = scan_local_files(dir, options)?;
+  let mut local = HashMap::new();
+  for path in paths {
+    let rel_path = path.strip_prefix(dir)?;
+    let (content, _) = read_local_file(&path, options).await?;
+    local.insert(to_ssm_key(rel_path).trim_start_matches('/').to_string(), stored_content(&parameter_name(prefix, rel_path), content, options)?);
+  }
+  let skipped = skipped.iter().map(|file| to_ssm_key(file.path.strip_prefix(dir).unwrap_or(&file.path)).trim_start_matches('/').to_string()).collect();
+  Ok((local, skipped))
+}
+
+pub fn is_skipped(key: &str, skipped: &[String]) -> bool {
+  skipped.iter().any(|skipped| key == skipped || key.starts_with(&format!("{skipped}/")))
+}
+
+pub fn parse_size(value: &str) -> Result<u64> {
+  let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+  let (amount, unit) = value.split_at(split);
+  let amount = amount.parse::<u64>().context(format!("Invalid size {value}; expected e.g. 512K, 1M or 2G"))?;
+  let multiplier: u64 = match unit.to_ascii_uppercase().as_str() {
+    "" | "B" => 1,
+    "K" | "KB" | "KIB" => 1 << 10,
+    "M" | "MB" | "MIB" => 1 << 20,
+    "G" | "GB" | "GIB" => 1 << 30,
+    _ => anyhow::bail!("Invalid size unit in {value}; expected K, M or G"),
+  };
+  amount.checked_mul(multiplier).context(format!("Size {value} is too large"))
+}
+
+pub fn parameter_name(prefix: &str, rel_path: &Path) -> String {
+  format!("{}{}", prefix.trim_end_matches('/'), to_ssm_key(rel_path))
+}
+
+pub async fn put_files(client: &Client, files: Vec<PreparedFile>, options: &UploadOptions) -> Result<()> {
+  if options.dry_run {
+    for file in files.iter().filter(|file| !file.unchanged) {
+      if let (Some(target), Some((key, content))) = (&options.s3, &file.object) {
+        Event::new("would-upload", s3::pointer(&target.bucket, key))
+          .with("bytes", content.len())
+          .emit(format!("Would upload {} ({} bytes)", s3::pointer(&target.bucket, key), content.len()));
+      }
+      for (name, value) in &file.parts {
+        Event::new("would-put", name.as_str())
+          .with("bytes", value.len())
+          .with("type", options.r#type.as_str())
+          .emit(format!("Would put {name} ({} bytes, {})", value.len(), options.r#type.as_str()));
+      }
+    }
+    return Ok(());
+  }
+
+  let total = files.len();
+  let progress = Progress::new("upload", total);
+  let progress_ref = &progress;
+
+  let failures = stream::iter(files)
+    .map(|file| async move {
+      if file.unchanged || cancel::is_cancelled() {
+        progress_ref.file_skipped();
+        return Ok(None);
+      }
+
+      let name = file.parts.first().map(|(name, _)| logical_name(name).to_string()).unwrap_or_default();
+      match put_file(client, &file, options, progress_ref).await {
+        Ok(()) => {
+          progress_ref.file_done();
+          Ok(None)
+        },
+        Err(e) if options.fail_fast => Err(e),
+        Err(e) => {
+          progress_ref.file_failed();
+          progress_ref.emit(Event::new("failed", name.as_str()).with("error", format!("{e:#}")), format!("Failed {name}: {e:#}"));
+          Ok(Some(name))
+        },
+      }
+    })
+    .buffer_unordered(options.concurrency.max(1))
+    .try_collect::<Vec<_>>()
+    .await?
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>();
+
+  progress.finish();
+  cancel::check()?;
+  ensure!(failures.is_empty(), "{} of {total} files failed to upload: {}", failures.len(), failures.join(", "));
+  Ok(())
+}
+
+async fn put_file(client: &Client, file: &PreparedFile, options: &UploadOptions, progress: &Progress) -> Result<()> {
+  if let (Some(target), Some((key, content))) = (&options.s3, &file.object) {
+    progress.start(key);
+    let encryption = (!options.encrypt_with.is_empty()).then_some(Encryption::Age);
+    s3::put_object(target, key, content, options.kms_key_id.as_deref(), encryption).await?;
+    progress.api_calls(1);
+    progress.transferred(0, content.len());
+  }
+  let policies = policy::to_json(&options.policies)?;
+  for (name, value) in &file.parts {
+    progress.start(name);
+    ssm::ensure_writable("ssm:PutParameter", name)?;
+    let resp = ssm::retry_throttled(|| {
+      client
+        .put_parameter()
+        .name(name)
+        .value(value)
+        .description(&file.description)
+        .overwrite(true)
+        .r#type(options.r#type.clone())
+        .set_key_id(options.kms_key_id.clone())
+        .tier(options.tier.clone())
+        .set_policies(policies.clone())
+        .send()
+    })
+    .await
+    .context(format!("Failed to upload {name}"))?;
+
+    let tags = with_change_reason(&options.tags);
+    add_tags(client, name, &tags).await?;
+    progress.api_calls(if tags.is_empty() { 1 } else { 2 });
+    progress.transferred(1, value.len());
+    progress.emit(Event::new("put", name.as_str()).with("version", resp.version()), format!("Put {name} (v{})", resp.version()));
+  }
+
+  let logical = file.parts.first().map(|(name, _)| logical_name(name).to_string()).unwrap_or_default();
+  let stale = physical_names(client, &logical).await?.into_iter().filter(|name| !file.parts.iter().any(|(part, _)| part == name)).collect::<Vec<_>>();
+  progress.api_calls(1);
+  for batch in stale.chunks(DELETE_BATCH_SIZE) {
+    ssm::ensure_writable("ssm:DeleteParameters", &batch.join(", "))?;
+    let resp = ssm::retry_throttled(|| client.delete_parameters().set_names(Some(batch.to_vec())).send()).await.context(format!("Failed to remove stale parts of {logical}"))?;
+    progress.api_calls(1);
+    for name in resp.deleted_parameters() {
+      progress.emit(Event::new("delete", name.as_str()), format!("Deleted {name}"));
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::files::assemble;
+
+  fn round_trip(content: &[u8], options: &UploadOptions) -> Vec<u8> {
+    let file = prepare("config", content.to_vec(), options).unwrap();
+    for (name, value) in &file.parts {
+      assert!(value.len() <= options.resolved_chunk_size().unwrap(), "{name} is {} bytes", value.len());
+    }
+    assemble(file.parts).unwrap().remove("config").unwrap()
+  }
+
+  #[test]
+  fn split_chunks_never_splits_a_character() {
+    let content = "aé€😀".repeat(50);
+    for chunk_size in 4..=16 {
+      let chunks = split_chunks(&content, chunk_size);
+      assert!(chunks.iter().all(|c| !c.is_empty() && c.len() <= chunk_size));
+      assert_eq!(chunks.concat(), content);
+    }
+    assert_eq!(split_chunks("é😀", 1), ["é", "😀"]);
+  }
+
+  #[test]
+  fn multi_byte_content_round_trips_across_chunk_boundaries() {
+    let options = UploadOptions { chunk_size: Some(4096), ..Default::default() };
+    for padding in 0..4 {
+      let content = format!("{}{}", "x".repeat(4094 + padding), "日本語😀".repeat(2000));
+      assert_eq!(round_trip(content.as_bytes(), &options), content.as_bytes());
+    }
+  }
+
+  #[test]
+  fn parse_size_accepts_binary_units() {
+    assert_eq!(parse_size("512").unwrap(), 512);
+    assert_eq!(parse_size("4K").unwrap(), 4096);
+    assert_eq!(parse_size("1MiB").unwrap(), 1 << 20);
+    assert_eq!(parse_size("2g").unwrap(), 2 << 30);
+    assert!(parse_size("1T").is_err());
+    assert!(parse_size("M").is_err());
+  }
+
+  #[test]
+  fn binary_content_round_trips_through_base64_chunks() {
+    let content = (0..10_000u32).map(|i| (i * 7 % 256) as u8).collect::<Vec<_>>();
+    assert_eq!(round_trip(&content, &UploadOptions { chunk_size: Some(100), ..Default::default() }), content);
+  }
+}
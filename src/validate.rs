@@ -0,0 +1,39 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Syntax {
+  Yaml,
+  Json,
+  Toml,
+  None,
+}
+
+impl Syntax {
+  pub fn detect(name: &str) -> Option<Syntax> {
+    match Path::new(name).extension()?.to_str()?.to_ascii_lowercase().as_str() {
+      "yaml" | "yml" => Some(Syntax::Yaml),
+      "json" => Some(Syntax::Json),
+      "toml" => Some(Syntax::Toml),
+      _ => None,
+    }
+  }
+
+  pub fn validate(self, content: &[u8]) -> Result<()> {
+    match self {
+      Syntax::Yaml => serde_yaml::from_slice::<serde_yaml::Value>(content).map(drop).context("Invalid YAML"),
+      Syntax::Json => serde_json::from_slice::<serde_json::Value>(content).map(drop).context("Invalid JSON"),
+      Syntax::Toml => toml::from_str::<toml::Table>(std::str::from_utf8(content).context("Invalid TOML")?).map(drop).context("Invalid TOML"),
+      Syntax::None => Ok(()),
+    }
+  }
+}
+
+pub fn validate(name: &str, content: &[u8], syntax: Option<Syntax>) -> Result<()> {
+  match syntax.or_else(|| Syntax::detect(name)) {
+    Some(syntax) => syntax.validate(content).context(format!("{name} failed validation")),
+    None => Ok(()),
+  }
+}
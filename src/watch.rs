@@ -0,0 +1,89 @@
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::{Context, Result};
+use futures::TryStreamExt;
+use tokio::process::{Child, Command};
+
+use crate::cancel;
+use crate::output::Event;
+use crate::ssm::{self, Client};
+
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+  pub interval: Duration,
+}
+
+impl Default for WatchOptions {
+  fn default() -> Self {
+    Self { interval: Duration::from_secs(30) }
+  }
+}
+
+pub async fn watch(client: &Client, prefix: String, command: Vec<String>, options: WatchOptions) -> Result<()> {
+  let mut versions = snapshot(client, &prefix).await?;
+  let mut child = spawn(&command)?;
+
+  let mut ticker = tokio::time::interval(options.interval);
+  ticker.tick().await;
+  loop {
+    tokio::select! {
+      _ = ticker.tick() => {},
+      _ = cancel::cancelled() => {
+        if child.try_wait()?.is_none() {
+          child.kill().await.context("Failed to stop the command")?;
+        }
+        return cancel::check();
+      },
+    }
+    let current = match snapshot(client, &prefix).await {
+      Ok(current) => current,
+      Err(err) => {
+        tracing::warn!("Failed to poll {prefix}: {err:#}");
+        continue;
+      },
+    };
+
+    let changed = changed_names(&versions, &current);
+    if changed.is_empty() {
+      continue;
+    }
+    versions = current;
+
+    Event::new("change", prefix.as_str())
+      .with("parameters", changed.clone())
+      .emit(format!("{} parameter(s) changed under {prefix}, re-running {}", changed.len(), command.join(" ")));
+
+    if child.try_wait()?.is_none() {
+      child.kill().await.context("Failed to stop the previous command")?;
+    }
+    child = spawn(&command)?;
+  }
+}
+
+async fn snapshot(client: &Client, prefix: &str) -> Result<HashMap<String, i64>> {
+  Ok(
+    ssm::all_parameter_metadata_by_path(client, prefix)
+      .try_collect::<Vec<_>>()
+      .await?
+      .into_iter()
+      .flatten()
+      .filter_map(|p| Some((p.name?, p.version)))
+      .collect(),
+  )
+}
+
+fn changed_names(before: &HashMap<String, i64>, after: &HashMap<String, i64>) -> Vec<String> {
+  let mut changed = after
+    .iter()
+    .filter(|(name, version)| before.get(*name) != Some(version))
+    .map(|(name, _)| name.clone())
+    .chain(before.keys().filter(|name| !after.contains_key(*name)).cloned())
+    .collect::<Vec<_>>();
+  changed.sort();
+  changed
+}
+
+fn spawn(command: &[String]) -> Result<Child> {
+  let (program, args) = command.split_first().context("No command given")?;
+  Command::new(program).args(args).kill_on_drop(true).spawn().context(format!("Failed to run {program}"))
+}
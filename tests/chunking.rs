@@ -0,0 +1,174 @@
+mod support;
+
+use std::{fs, path::Path};
+
+use ops::files::read_local_files;
+use ops::store::SecretStore;
+use ops::{DownloadOptions, DownloadSource, UploadOptions, download_to_dir, upload_dir};
+use support::{FakeSsm, golden};
+
+fn write_fixtures(dir: &Path) {
+  fs::write(dir.join("small.txt"), "hello\n").unwrap();
+  let text = (0..1000).map(|i| format!("line {i:04}\n")).collect::<String>();
+  fs::write(dir.join("big.txt"), text).unwrap();
+  fs::create_dir(dir.join("certs")).unwrap();
+  fs::write(dir.join("certs/key.der"), (0..5000u32).map(|i| (i * 31 % 256) as u8).collect::<Vec<_>>()).unwrap();
+}
+
+fn render(ssm: &FakeSsm) -> String {
+  ssm
+    .parameters()
+    .iter()
+    .map(|(name, p)| {
+      let value = if name.ends_with(".manifest") { p.value.clone() } else { format!("<{} bytes>", p.value.len()) };
+      format!("{name}\t{}\t{}\t{}\t{value}\n", p.r#type, p.tier, p.description.as_deref().unwrap_or("-"))
+    })
+    .collect()
+}
+
+async fn upload_fixtures(ssm: &FakeSsm) -> tempfile::TempDir {
+  let dir = tempfile::tempdir().unwrap();
+  write_fixtures(dir.path());
+  upload_dir(&ssm.client(), dir.path().to_path_buf(), "/app".to_string(), UploadOptions { concurrency: 1, ..Default::default() }).await.unwrap();
+  dir
+}
+
+#[tokio::test]
+async fn upload_splits_large_and_binary_files_into_parts() {
+  let ssm = FakeSsm::new();
+  upload_fixtures(&ssm).await;
+
+  golden("upload_chunked.txt", &render(&ssm));
+  assert!(ssm.parameters().values().all(|p| p.value.len() <= ops::files::CHUNK_SIZE));
+}
+
+#[tokio::test]
+async fn download_reassembles_uploaded_files() {
+  let ssm = FakeSsm::new();
+  let source = upload_fixtures(&ssm).await;
+  let target = tempfile::tempdir().unwrap();
+
+  download_to_dir(&ssm.client(), DownloadSource::Prefix("/app".to_string()), target.path().to_path_buf(), DownloadOptions::default()).await.unwrap();
+
+  assert_eq!(read_local_files(target.path()).unwrap(), read_local_files(source.path()).unwrap());
+}
+
+#[tokio::test]
+async fn unchanged_files_are_not_uploaded_again() {
+  let ssm = FakeSsm::new();
+  let dir = upload_fixtures(&ssm).await;
+  ssm.clear_calls();
+
+  upload_dir(&ssm.client(), dir.path().to_path_buf(), "/app".to_string(), UploadOptions::default()).await.unwrap();
+
+  assert_eq!(ssm.count("PutParameter"), 0);
+}
+
+#[tokio::test]
+async fn reassembly_fails_when_a_part_is_missing() {
+  let ssm = FakeSsm::new();
+  upload_fixtures(&ssm).await;
+  ssm.remove("/app/big.txt.part1");
+
+  let err = ops::files::fetch_files(&ssm.client(), None, "/app").await.unwrap_err();
+
+  assert!(format!("{err:#}").contains("big.txt expected 3 parts but found [0, 2]"), "{err:#}");
+}
+
+#[tokio::test]
+async fn reassembly_fails_when_a_part_is_modified() {
+  let ssm = FakeSsm::new();
+  upload_fixtures(&ssm).await;
+  ssm.set_value("/app/big.txt.part0", "tampered");
+
+  let err = ops::files::fetch_file(&ssm.client(), "/app/big.txt").await.unwrap_err();
+
+  assert!(format!("{err:#}").contains("does not match its manifest checksum"), "{err:#}");
+}
+
+#[tokio::test]
+async fn throttled_puts_are_retried() {
+  let ssm = FakeSsm::new();
+  ssm.fail_next("PutParameter", "ThrottlingException");
+  upload_fixtures(&ssm).await;
+
+  assert_eq!(ssm.count("PutParameter"), ssm.parameters().len() + 1);
+}
+
+#[tokio::test]
+async fn s3_pointers_are_not_returned_as_values() {
+  let ssm = FakeSsm::new();
+  upload_fixtures(&ssm).await;
+  ssm.insert("/app/large.bin.s3", "s3://bucket/app/large.bin");
+
+  let err = ops::files::fetch_files(&ssm.client(), None, "/app").await.unwrap_err();
+
+  assert!(format!("{err:#}").contains("large.bin is stored in S3; use --backend s3 to read it"), "{err:#}");
+}
+
+#[tokio::test]
+async fn reuploading_removes_parts_the_new_version_no_longer_uses() {
+  let ssm = FakeSsm::new();
+  let dir = upload_fixtures(&ssm).await;
+  let upload = async || upload_dir(&ssm.client(), dir.path().to_path_buf(), "/app".to_string(), UploadOptions::default()).await.unwrap();
+
+  fs::write(dir.path().join("big.txt"), "x".repeat(5000)).unwrap();
+  upload().await;
+  assert_eq!(ops::files::fetch_file(&ssm.client(), "/app/big.txt").await.unwrap(), "x".repeat(5000).into_bytes());
+  assert!(!ssm.parameters().contains_key("/app/big.txt.part2"));
+
+  fs::write(dir.path().join("big.txt"), "small\n").unwrap();
+  upload().await;
+  assert_eq!(ops::files::fetch_file(&ssm.client(), "/app/big.txt").await.unwrap(), b"small\n");
+  assert_eq!(ssm.parameters().keys().filter(|name| name.starts_with("/app/big.txt")).collect::<Vec<_>>(), ["/app/big.txt"]);
+}
+
+#[tokio::test]
+async fn reassembly_ignores_parts_beyond_the_manifest() {
+  let ssm = FakeSsm::new();
+  let dir = upload_fixtures(&ssm).await;
+  ssm.insert("/app/big.txt.part3", "leftover");
+
+  let files = ops::files::fetch_files(&ssm.client(), None, "/app").await.unwrap();
+
+  assert_eq!(files["big.txt"], fs::read(dir.path().join("big.txt")).unwrap());
+}
+
+#[tokio::test]
+async fn switching_between_text_and_binary_leaves_one_variant() {
+  let ssm = FakeSsm::new();
+  let dir = tempfile::tempdir().unwrap();
+  let upload = async || upload_dir(&ssm.client(), dir.path().to_path_buf(), "/app".to_string(), UploadOptions::default()).await.unwrap();
+
+  fs::write(dir.path().join("k"), "text").unwrap();
+  upload().await;
+  fs::write(dir.path().join("k"), [255, 254, 0]).unwrap();
+  upload().await;
+
+  assert_eq!(ssm.parameters().keys().collect::<Vec<_>>(), ["/app/k.b64"]);
+  assert_eq!(ops::files::fetch_file(&ssm.client(), "/app/k").await.unwrap(), [255, 254, 0]);
+}
+
+#[tokio::test]
+async fn reassembly_fails_when_text_and_binary_variants_both_exist() {
+  let ssm = FakeSsm::new();
+  ssm.insert("/app/k", "text");
+  ssm.insert("/app/k.b64", "//4A");
+
+  let err = ops::files::fetch_files(&ssm.client(), None, "/app").await.unwrap_err();
+
+  assert!(format!("{err:#}").contains("k is stored both as text and as k.b64"), "{err:#}");
+}
+
+#[tokio::test]
+async fn stores_fetch_split_values_by_name() {
+  let ssm = FakeSsm::new();
+  let dir = upload_fixtures(&ssm).await;
+  let s3 = aws_sdk_s3::Client::from_conf(aws_sdk_s3::Config::builder().behavior_version(aws_sdk_s3::config::BehaviorVersion::latest()).region(aws_sdk_s3::config::Region::new("us-east-1")).build());
+  let store = ops::store::SsmStore::new(ssm.client(), s3);
+
+  let (entries, missing) = store.fetch_names(&["/app/big.txt".to_string(), "/app/nope".to_string()], None).await.unwrap();
+
+  assert_eq!(entries.iter().map(|e| (e.name.as_str(), e.value.clone())).collect::<Vec<_>>(), [("/app/big.txt", fs::read(dir.path().join("big.txt")).unwrap())]);
+  assert_eq!(missing, ["/app/nope"]);
+}
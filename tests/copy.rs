@@ -0,0 +1,40 @@
+mod support;
+
+use aws_sdk_ssm::types::ParameterType;
+use ops::copy::{CopyOptions, copy};
+use support::FakeSsm;
+
+async fn seed(ssm: &FakeSsm) {
+  ssm.client().put_parameter().name("/a/db").value("secret").r#type(ParameterType::SecureString).key_id("alias/app").send().await.unwrap();
+}
+
+#[tokio::test]
+async fn copies_keep_the_source_key_within_the_account() {
+  let ssm = FakeSsm::new();
+  seed(&ssm).await;
+
+  copy(&ssm.client(), &ssm.client(), "/a".to_string(), "/b".to_string(), CopyOptions { yes: true, ..Default::default() }).await.unwrap();
+
+  assert_eq!(ssm.parameters()["/b/db"].key_id.as_deref(), Some("alias/app"));
+}
+
+#[tokio::test]
+async fn copies_to_another_region_or_account_use_the_default_key() {
+  let (source, destination) = (FakeSsm::new(), FakeSsm::new());
+  seed(&source).await;
+
+  copy(&source.client(), &destination.client(), "/a".to_string(), "/b".to_string(), CopyOptions { cross_region_or_account: true, yes: true, ..Default::default() }).await.unwrap();
+
+  assert_eq!(destination.parameters()["/b/db"].key_id.as_deref(), Some("alias/aws/ssm"));
+}
+
+#[tokio::test]
+async fn throttled_copies_are_retried() {
+  let ssm = FakeSsm::new();
+  seed(&ssm).await;
+  ssm.fail_next("PutParameter", "ThrottlingException");
+
+  copy(&ssm.client(), &ssm.client(), "/a".to_string(), "/b".to_string(), CopyOptions { yes: true, ..Default::default() }).await.unwrap();
+
+  assert_eq!(ssm.parameters()["/b/db"].value, "secret");
+}
@@ -0,0 +1,77 @@
+#![cfg(unix)]
+
+mod support;
+
+use std::{fs, os::unix::fs::PermissionsExt, sync::Once};
+
+use age::secrecy::ExposeSecret;
+use aws_sdk_ssm::types::{ParameterTier, ParameterType, Tag};
+use ops::edit::{EditOptions, edit};
+use ops::tags::add_tags;
+use ops::{UploadOptions, crypto, upload_dir};
+use support::FakeSsm;
+
+#[tokio::test]
+async fn editing_an_encrypted_value_requires_recipients() {
+  let identity = age::x25519::Identity::generate();
+  let identity_file = tempfile::NamedTempFile::new().unwrap();
+  fs::write(identity_file.path(), identity.to_string().expose_secret()).unwrap();
+  crypto::set_identities(identity_file.path()).unwrap();
+
+  let ssm = FakeSsm::new();
+  let dir = tempfile::tempdir().unwrap();
+  fs::write(dir.path().join("db.env"), "PASSWORD=secret\n").unwrap();
+  let upload = UploadOptions { encrypt_with: vec![identity.to_public()], ..Default::default() };
+  upload_dir(&ssm.client(), dir.path().to_path_buf(), "/app".to_string(), upload).await.unwrap();
+  ssm.clear_calls();
+
+  let err = edit(&ssm.client(), None, "/app/db.env".to_string(), EditOptions::default()).await.unwrap_err();
+
+  assert!(format!("{err:#}").contains("pass --encrypt-with"), "{err:#}");
+  assert_eq!(ssm.count("PutParameter"), 0);
+}
+
+#[tokio::test]
+async fn editing_keeps_type_key_tier_and_tags() {
+  let ssm = FakeSsm::new();
+  let client = ssm.client();
+  client.put_parameter().name("/app/config.env").value("LEVEL=info\n").r#type(ParameterType::String).tier(ParameterTier::Advanced).send().await.unwrap();
+  add_tags(&client, "/app/config.env", &[Tag::builder().key("team").value("ops").build().unwrap()]).await.unwrap();
+
+  use_editor();
+
+  edit(&client, None, "/app/config.env".to_string(), EditOptions::default()).await.unwrap();
+
+  let parameter = &ssm.parameters()["/app/config.env"];
+  assert_eq!(parameter.value, "LEVEL=edited\n");
+  assert_eq!(parameter.r#type, "String");
+  assert_eq!(parameter.tier, "Advanced");
+  assert_eq!(parameter.key_id, None);
+  assert_eq!(parameter.tags.get("team").map(String::as_str), Some("ops"));
+}
+
+#[tokio::test]
+async fn editing_keeps_a_custom_kms_key() {
+  let ssm = FakeSsm::new();
+  let client = ssm.client();
+  client.put_parameter().name("/app/db.env").value("PASSWORD=a\n").r#type(ParameterType::SecureString).key_id("alias/app").send().await.unwrap();
+
+  use_editor();
+
+  edit(&client, None, "/app/db.env".to_string(), EditOptions::default()).await.unwrap();
+
+  let parameter = &ssm.parameters()["/app/db.env"];
+  assert_eq!(parameter.value, "PASSWORD=edited\n");
+  assert_eq!(parameter.r#type, "SecureString");
+  assert_eq!(parameter.key_id.as_deref(), Some("alias/app"));
+}
+
+fn use_editor() {
+  static EDITOR: Once = Once::new();
+  EDITOR.call_once(|| {
+    let editor = tempfile::Builder::new().suffix(".sh").tempfile().unwrap().into_temp_path().keep().unwrap();
+    fs::write(&editor, "#!/bin/sh\nsed -i 's/=.*/=edited/' \"$1\"\n").unwrap();
+    fs::set_permissions(&editor, fs::Permissions::from_mode(0o755)).unwrap();
+    unsafe { std::env::set_var("VISUAL", &editor) };
+  });
+}
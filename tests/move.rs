@@ -0,0 +1,28 @@
+mod support;
+
+use aws_sdk_ssm::types::Tag;
+use ops::rename::rename;
+use ops::tags::add_tags;
+use support::FakeSsm;
+
+#[tokio::test]
+async fn move_keeps_nested_names_that_repeat_the_prefix() {
+  let ssm = FakeSsm::new();
+  ssm.insert("/a/a/x", "1");
+  ssm.insert("/a/y", "2");
+
+  rename(&ssm.client(), "/a".to_string(), "/b".to_string(), None, true, false).await.unwrap();
+
+  assert_eq!(ssm.parameters().keys().collect::<Vec<_>>(), ["/b/a/x", "/b/y"]);
+}
+
+#[tokio::test]
+async fn move_keeps_tags() {
+  let ssm = FakeSsm::new();
+  ssm.insert("/a/x", "1");
+  add_tags(&ssm.client(), "/a/x", &[Tag::builder().key("team").value("ops").build().unwrap()]).await.unwrap();
+
+  rename(&ssm.client(), "/a/".to_string(), "/b".to_string(), None, true, false).await.unwrap();
+
+  assert_eq!(ssm.parameters()["/b/x"].tags.get("team").map(String::as_str), Some("ops"));
+}
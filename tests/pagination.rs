@@ -0,0 +1,62 @@
+mod support;
+
+use futures::TryStreamExt;
+use ops::files::fetch_files;
+use ops::ssm;
+use support::{FakeSsm, golden};
+
+fn seed(ssm: &FakeSsm, count: usize) {
+  for i in 0..count {
+    ssm.insert(&format!("/app/item{i:03}"), &format!("value {i}"));
+  }
+  ssm.insert("/other/item", "outside the prefix");
+}
+
+#[tokio::test]
+async fn get_parameters_by_path_follows_every_page() {
+  let ssm = FakeSsm::new();
+  seed(&ssm, 25);
+
+  let files = fetch_files(&ssm.client(), None, "/app/").await.unwrap();
+
+  assert_eq!(files.len(), 25);
+  assert_eq!(files["item024"], b"value 24");
+  let pages = ssm
+    .calls()
+    .iter()
+    .map(|call| format!("{} Path={} MaxResults={} NextToken={}\n", call.action, call.input["Path"], call.input["MaxResults"], call.input["NextToken"]))
+    .collect::<String>();
+  golden("get_parameters_by_path_pages.txt", &pages);
+}
+
+#[tokio::test]
+async fn describe_parameters_follows_every_page() {
+  let ssm = FakeSsm::new();
+  seed(&ssm, 120);
+
+  let names = ssm::all_parameter_metadata_by_path(&ssm.client(), "/app").try_collect::<Vec<_>>().await.unwrap();
+
+  assert_eq!(names.iter().map(Vec::len).collect::<Vec<_>>(), [50, 50, 20]);
+  assert_eq!(ssm.count("DescribeParameters"), 3);
+}
+
+#[tokio::test]
+async fn an_empty_prefix_is_a_single_page() {
+  let ssm = FakeSsm::new();
+  seed(&ssm, 3);
+
+  assert!(fetch_files(&ssm.client(), None, "/missing").await.unwrap().is_empty());
+  assert_eq!(ssm.count("GetParametersByPath"), 1);
+}
+
+#[tokio::test]
+async fn non_recursive_reads_stop_at_one_level() {
+  let ssm = FakeSsm::new();
+  seed(&ssm, 3);
+  ssm.insert("/app/nested/item", "one level down");
+
+  let params = ssm::all_parameters_by_path(&ssm.client(), "/app", false).try_collect::<Vec<_>>().await.unwrap();
+
+  assert_eq!(params.concat().len(), 3);
+  assert_eq!(ssm.calls()[0].input["Recursive"], false);
+}
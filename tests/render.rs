@@ -0,0 +1,22 @@
+mod support;
+
+use std::fs;
+
+use ops::render::{RenderOptions, render};
+use support::FakeSsm;
+
+#[tokio::test]
+async fn renders_parameters_inside_conditional_and_loop_blocks() {
+  let ssm = FakeSsm::new();
+  ssm.insert("/app/host", "db.internal");
+  ssm.insert("/app/password", "hunter2");
+  ssm.insert("/app/tls", "on");
+  let dir = tempfile::tempdir().unwrap();
+  let template = dir.path().join("app.conf.hbs");
+  let out = dir.path().join("app.conf");
+  fs::write(&template, "{{#if (ssm \"/app/tls\")}}host={{ssm \"/app/host\"}}{{/if}}\n{{#each [1]}}password={{ssm \"/app/password\"}}{{/each}}\n").unwrap();
+
+  render(&ssm.client(), None, template, RenderOptions { out: Some(out.clone()), mode: None }).await.unwrap();
+
+  assert_eq!(fs::read_to_string(out).unwrap(), "host=db.internal\npassword=hunter2\n");
+}
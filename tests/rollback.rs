@@ -0,0 +1,43 @@
+mod support;
+
+use std::fs;
+
+use ops::history::rollback;
+use ops::{UploadOptions, upload_dir};
+use support::FakeSsm;
+
+#[tokio::test]
+async fn rolled_back_values_can_be_uploaded_again() {
+  let ssm = FakeSsm::new();
+  let dir = tempfile::tempdir().unwrap();
+  let upload = async || upload_dir(&ssm.client(), dir.path().to_path_buf(), "/app".to_string(), UploadOptions::default()).await.unwrap();
+  fs::write(dir.path().join("app.env"), "A=1\n").unwrap();
+  upload().await;
+  fs::write(dir.path().join("app.env"), "A=2\n").unwrap();
+  upload().await;
+
+  rollback(&ssm.client(), "/app/app.env".to_string(), 1, false).await.unwrap();
+  assert_eq!(ssm.parameters()["/app/app.env"].value, "A=1\n");
+
+  ssm.clear_calls();
+  upload().await;
+  assert_eq!(ssm.count("PutParameter"), 1);
+  assert_eq!(ssm.parameters()["/app/app.env"].value, "A=2\n");
+}
+
+#[tokio::test]
+async fn rollback_resolves_binary_names_and_rejects_split_files() {
+  let ssm = FakeSsm::new();
+  let dir = tempfile::tempdir().unwrap();
+  fs::write(dir.path().join("key.der"), [255, 0]).unwrap();
+  fs::write(dir.path().join("big.txt"), "x".repeat(10_000)).unwrap();
+  upload_dir(&ssm.client(), dir.path().to_path_buf(), "/app".to_string(), UploadOptions::default()).await.unwrap();
+  fs::write(dir.path().join("key.der"), [255, 1]).unwrap();
+  upload_dir(&ssm.client(), dir.path().to_path_buf(), "/app".to_string(), UploadOptions::default()).await.unwrap();
+
+  rollback(&ssm.client(), "/app/key.der".to_string(), 1, false).await.unwrap();
+  assert_eq!(ops::files::fetch_file(&ssm.client(), "/app/key.der").await.unwrap(), [255, 0]);
+
+  let err = rollback(&ssm.client(), "/app/big.txt".to_string(), 1, false).await.unwrap_err();
+  assert!(err.to_string().contains("split across 4 parameters"), "{err:#}");
+}
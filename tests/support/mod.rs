@@ -0,0 +1,311 @@
+#![allow(dead_code)]
+
+use std::{collections::{BTreeMap, HashMap}, sync::{Arc, Mutex}};
+
+use aws_sdk_ssm::Client;
+use aws_sdk_ssm::config::{BehaviorVersion, Credentials, Region, retry::RetryConfig};
+use aws_smithy_runtime_api::client::http::{HttpClient, HttpConnector, HttpConnectorFuture, HttpConnectorSettings, SharedHttpConnector};
+use aws_smithy_runtime_api::client::orchestrator::{HttpRequest, HttpResponse};
+use aws_smithy_runtime_api::client::runtime_components::RuntimeComponents;
+use aws_smithy_runtime_api::http::StatusCode;
+use aws_smithy_types::body::SdkBody;
+use serde_json::{Value, json};
+
+#[derive(Debug, Clone)]
+pub struct StoredParameter {
+  pub value: String,
+  pub r#type: String,
+  pub description: Option<String>,
+  pub tier: String,
+  pub key_id: Option<String>,
+  pub version: i64,
+  pub tags: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+  parameters: BTreeMap<String, StoredParameter>,
+  history: BTreeMap<String, Vec<StoredParameter>>,
+  calls: Vec<Call>,
+  failures: HashMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Call {
+  pub action: String,
+  pub input: Value,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FakeSsm {
+  state: Arc<Mutex<State>>,
+}
+
+type Reply = Result<Value, (&'static str, String)>;
+
+impl FakeSsm {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn client(&self) -> Client {
+    let config = aws_sdk_ssm::Config::builder()
+      .behavior_version(BehaviorVersion::latest())
+      .region(Region::new("us-east-1"))
+      .credentials_provider(Credentials::new("test", "test", None, None, "fake-ssm"))
+      .retry_config(RetryConfig::disabled())
+      .http_client(self.clone())
+      .build();
+    Client::from_conf(config)
+  }
+
+  pub fn insert(&self, name: &str, value: &str) {
+    let mut state = self.state.lock().unwrap();
+    state.parameters.insert(name.to_string(), StoredParameter {
+      value: value.to_string(),
+      r#type: "String".to_string(),
+      description: None,
+      tier: "Standard".to_string(),
+      key_id: None,
+      version: 1,
+      tags: BTreeMap::new(),
+    });
+  }
+
+  pub fn remove(&self, name: &str) {
+    self.state.lock().unwrap().parameters.remove(name);
+  }
+
+  pub fn set_value(&self, name: &str, value: &str) {
+    let mut state = self.state.lock().unwrap();
+    let parameter = state.parameters.get_mut(name).expect("parameter exists");
+    parameter.value = value.to_string();
+    parameter.version += 1;
+  }
+
+  pub fn parameters(&self) -> BTreeMap<String, StoredParameter> {
+    self.state.lock().unwrap().parameters.clone()
+  }
+
+  pub fn calls(&self) -> Vec<Call> {
+    self.state.lock().unwrap().calls.clone()
+  }
+
+  pub fn count(&self, action: &str) -> usize {
+    self.state.lock().unwrap().calls.iter().filter(|call| call.action == action).count()
+  }
+
+  pub fn clear_calls(&self) {
+    self.state.lock().unwrap().calls.clear();
+  }
+
+  pub fn fail_next(&self, action: &str, code: &str) {
+    self.state.lock().unwrap().failures.entry(action.to_string()).or_default().push(code.to_string());
+  }
+
+  fn handle(&self, request: &HttpRequest) -> HttpResponse {
+    let action = request.headers().get("x-amz-target").and_then(|target| target.strip_prefix("AmazonSSM.")).unwrap_or_default().to_string();
+    let input = request.body().bytes().and_then(|body| serde_json::from_slice(body).ok()).unwrap_or(Value::Null);
+    let mut state = self.state.lock().unwrap();
+    state.calls.push(Call { action: action.clone(), input: input.clone() });
+
+    let injected = state.failures.get_mut(&action).and_then(|codes| (!codes.is_empty()).then(|| codes.remove(0)));
+    let reply = match injected {
+      Some(code) => Err(("", code)),
+      None => state.dispatch(&action, &input),
+    };
+    let (status, body) = match reply {
+      Ok(body) => (200, body),
+      Err(("", code)) => (400, json!({ "__type": code, "message": format!("Injected {code}") })),
+      Err((code, message)) => (400, json!({ "__type": code, "message": message })),
+    };
+
+    let mut response = HttpResponse::new(StatusCode::try_from(status).unwrap(), SdkBody::from(body.to_string()));
+    response.headers_mut().insert("content-type", "application/x-amz-json-1.1");
+    response
+  }
+}
+
+impl State {
+  fn dispatch(&mut self, action: &str, input: &Value) -> Reply {
+    match action {
+      "PutParameter" => self.put_parameter(input),
+      "GetParameter" => {
+        let name = str_field(input, "Name");
+        let parameter = self.parameters.get(name).ok_or(("ParameterNotFound", name.to_string()))?;
+        Ok(json!({ "Parameter": parameter_json(name, parameter) }))
+      },
+      "GetParameters" => {
+        let names = input["Names"].as_array().cloned().unwrap_or_default();
+        let (found, invalid): (Vec<_>, Vec<_>) = names.iter().filter_map(Value::as_str).partition(|name| self.parameters.contains_key(*name));
+        let parameters = found.iter().map(|name| parameter_json(name, &self.parameters[*name])).collect::<Vec<_>>();
+        Ok(json!({ "Parameters": parameters, "InvalidParameters": invalid }))
+      },
+      "GetParametersByPath" => {
+        let path = str_field(input, "Path");
+        let recursive = input["Recursive"].as_bool().unwrap_or(false);
+        let matching = self.parameters.iter().filter(|(name, _)| under_path(name, path, recursive)).collect::<Vec<_>>();
+        Ok(page(&matching, input, "Parameters", 10, |(name, p)| parameter_json(name, p)))
+      },
+      "DescribeParameters" => {
+        let filters = input["ParameterFilters"].as_array().cloned().unwrap_or_default();
+        let matching = self
+          .parameters
+          .iter()
+          .filter(|(name, parameter)| filters.iter().all(|filter| matches_filter(name, parameter, filter)))
+          .collect::<Vec<_>>();
+        Ok(page(&matching, input, "Parameters", 50, |(name, p)| metadata_json(name, p)))
+      },
+      "DeleteParameter" => {
+        let name = str_field(input, "Name");
+        self.parameters.remove(name).ok_or(("ParameterNotFound", name.to_string()))?;
+        Ok(json!({}))
+      },
+      "DeleteParameters" => {
+        let names = input["Names"].as_array().cloned().unwrap_or_default();
+        let (deleted, invalid): (Vec<_>, Vec<_>) = names.iter().filter_map(Value::as_str).partition(|name| self.parameters.remove(*name).is_some());
+        Ok(json!({ "DeletedParameters": deleted, "InvalidParameters": invalid }))
+      },
+      "AddTagsToResource" => {
+        let parameter = self.parameters.get_mut(str_field(input, "ResourceId")).ok_or(("InvalidResourceId", str_field(input, "ResourceId").to_string()))?;
+        for tag in input["Tags"].as_array().into_iter().flatten() {
+          parameter.tags.insert(str_field(tag, "Key").to_string(), str_field(tag, "Value").to_string());
+        }
+        Ok(json!({}))
+      },
+      "RemoveTagsFromResource" => {
+        let parameter = self.parameters.get_mut(str_field(input, "ResourceId")).ok_or(("InvalidResourceId", str_field(input, "ResourceId").to_string()))?;
+        for key in input["TagKeys"].as_array().into_iter().flatten().filter_map(Value::as_str) {
+          parameter.tags.remove(key);
+        }
+        Ok(json!({}))
+      },
+      "GetParameterHistory" => {
+        let name = str_field(input, "Name");
+        let versions = self.history.get(name).ok_or(("ParameterNotFound", name.to_string()))?;
+        let versions = versions
+          .iter()
+          .map(|p| json!({ "Name": name, "Value": p.value, "Type": p.r#type, "Version": p.version, "Description": p.description, "Tier": p.tier, "KeyId": p.key_id, "Labels": [] }))
+          .collect::<Vec<_>>();
+        Ok(json!({ "Parameters": versions }))
+      },
+      "ListTagsForResource" => {
+        let parameter = self.parameters.get(str_field(input, "ResourceId")).ok_or(("InvalidResourceId", str_field(input, "ResourceId").to_string()))?;
+        Ok(json!({ "TagList": parameter.tags.iter().map(|(key, value)| json!({ "Key": key, "Value": value })).collect::<Vec<_>>() }))
+      },
+      _ => Err(("InvalidAction", format!("The fake does not implement {action}"))),
+    }
+  }
+
+  fn put_parameter(&mut self, input: &Value) -> Reply {
+    let name = str_field(input, "Name").to_string();
+    let overwrite = input["Overwrite"].as_bool().unwrap_or(false);
+    let existing = self.parameters.get(&name);
+    if existing.is_some() && !overwrite {
+      return Err(("ParameterAlreadyExists", name));
+    }
+
+    let version = existing.map_or(1, |p| p.version + 1);
+    let tags = existing.map(|p| p.tags.clone()).unwrap_or_default();
+    let r#type = input["Type"].as_str().unwrap_or("String").to_string();
+    let default_key = (r#type == "SecureString").then_some("alias/aws/ssm");
+    let parameter = StoredParameter {
+      value: str_field(input, "Value").to_string(),
+      description: input["Description"].as_str().map(str::to_string),
+      tier: input["Tier"].as_str().unwrap_or("Standard").to_string(),
+      key_id: input["KeyId"].as_str().or(default_key).map(str::to_string),
+      r#type,
+      version,
+      tags,
+    };
+    let tier = parameter.tier.clone();
+    self.history.entry(name.clone()).or_default().push(parameter.clone());
+    self.parameters.insert(name, parameter);
+    Ok(json!({ "Version": version, "Tier": tier }))
+  }
+}
+
+impl HttpConnector for FakeSsm {
+  fn call(&self, request: HttpRequest) -> HttpConnectorFuture {
+    HttpConnectorFuture::ready(Ok(self.handle(&request)))
+  }
+}
+
+impl HttpClient for FakeSsm {
+  fn http_connector(&self, _settings: &HttpConnectorSettings, _components: &RuntimeComponents) -> SharedHttpConnector {
+    SharedHttpConnector::new(self.clone())
+  }
+}
+
+fn str_field<'a>(value: &'a Value, key: &str) -> &'a str {
+  value[key].as_str().unwrap_or_default()
+}
+
+fn under_path(name: &str, path: &str, recursive: bool) -> bool {
+  let path = path.trim_end_matches('/');
+  name.strip_prefix(&format!("{path}/")).is_some_and(|rest| recursive || !rest.contains('/'))
+}
+
+fn matches_filter(name: &str, parameter: &StoredParameter, filter: &Value) -> bool {
+  let values = filter["Values"].as_array().into_iter().flatten().filter_map(Value::as_str).collect::<Vec<_>>();
+  let option = filter["Option"].as_str().unwrap_or("Equals");
+  match str_field(filter, "Key") {
+    "Path" => values.iter().any(|path| under_path(name, path, option == "Recursive")),
+    "Name" if option == "BeginsWith" => values.iter().any(|value| name.starts_with(value)),
+    "Name" => values.contains(&name),
+    "Type" => values.contains(&parameter.r#type.as_str()),
+    "Tier" => values.contains(&parameter.tier.as_str()),
+    key => match key.strip_prefix("tag:") {
+      Some(tag) => parameter.tags.get(tag).is_some_and(|value| values.is_empty() || values.contains(&value.as_str())),
+      None => false,
+    },
+  }
+}
+
+fn page<T>(items: &[T], input: &Value, key: &str, default_size: usize, render: impl Fn(&T) -> Value) -> Value {
+  let size = input["MaxResults"].as_u64().map_or(default_size, |size| size as usize);
+  let start = input["NextToken"].as_str().and_then(|token| token.strip_prefix("page-")).and_then(|offset| offset.parse().ok()).unwrap_or(0);
+  let end = (start + size).min(items.len());
+  let mut body = json!({ key: items[start.min(end)..end].iter().map(render).collect::<Vec<_>>() });
+  if end < items.len() {
+    body["NextToken"] = json!(format!("page-{end}"));
+  }
+  body
+}
+
+fn parameter_json(name: &str, parameter: &StoredParameter) -> Value {
+  json!({
+    "Name": name,
+    "Type": parameter.r#type,
+    "Value": parameter.value,
+    "Version": parameter.version,
+    "DataType": "text",
+  })
+}
+
+fn metadata_json(name: &str, parameter: &StoredParameter) -> Value {
+  let mut metadata = json!({
+    "Name": name,
+    "Type": parameter.r#type,
+    "Tier": parameter.tier,
+    "Version": parameter.version,
+    "DataType": "text",
+  });
+  if let Some(description) = &parameter.description {
+    metadata["Description"] = json!(description);
+  }
+  if let Some(key_id) = &parameter.key_id {
+    metadata["KeyId"] = json!(key_id);
+  }
+  metadata
+}
+
+pub fn golden(name: &str, actual: &str) {
+  let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(name);
+  if std::env::var_os("UPDATE_GOLDEN").is_some() {
+    std::fs::write(&path, actual).unwrap();
+    return;
+  }
+  let expected = std::fs::read_to_string(&path).unwrap_or_else(|_| panic!("Missing {}; run with UPDATE_GOLDEN=1 to create it", path.display()));
+  assert_eq!(actual, expected, "{} is out of date; run with UPDATE_GOLDEN=1 to update it", path.display());
+}
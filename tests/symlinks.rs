@@ -0,0 +1,119 @@
+#![cfg(unix)]
+
+mod support;
+
+use std::{fs, os::unix::fs::symlink, path::Path};
+
+use ops::apply::check_dir;
+use ops::files::SymlinkPolicy;
+use ops::{DownloadOptions, DownloadSource, UploadOptions, download_to_dir, upload_dir};
+use support::FakeSsm;
+
+fn write_fixtures(dir: &Path) {
+  fs::create_dir(dir.join("conf")).unwrap();
+  fs::write(dir.join("conf/app.yaml"), "port: 80\n").unwrap();
+  symlink("app.yaml", dir.join("conf/current.yaml")).unwrap();
+  symlink("conf", dir.join("linked")).unwrap();
+  symlink("..", dir.join("conf/loop")).unwrap();
+}
+
+async fn upload(ssm: &FakeSsm, symlinks: SymlinkPolicy) -> Vec<String> {
+  let dir = tempfile::tempdir().unwrap();
+  write_fixtures(dir.path());
+  upload_dir(&ssm.client(), dir.path().to_path_buf(), "/app".to_string(), UploadOptions { symlinks, ..Default::default() }).await.unwrap();
+  ssm.parameters().into_keys().collect()
+}
+
+#[tokio::test]
+async fn symlinks_are_stored_and_recreated_by_default() {
+  let ssm = FakeSsm::new();
+  upload(&ssm, SymlinkPolicy::Preserve).await;
+  assert!(ssm.parameters()["/app/conf/current.yaml.manifest"].value.contains(r#""kind":"symlink""#));
+
+  let target = tempfile::tempdir().unwrap();
+  download_to_dir(&ssm.client(), DownloadSource::Prefix("/app".to_string()), target.path().to_path_buf(), DownloadOptions::default()).await.unwrap();
+
+  assert_eq!(fs::read_link(target.path().join("conf/current.yaml")).unwrap(), Path::new("app.yaml"));
+  assert_eq!(fs::read_link(target.path().join("linked")).unwrap(), Path::new("conf"));
+  assert_eq!(fs::read_to_string(target.path().join("linked/app.yaml")).unwrap(), "port: 80\n");
+}
+
+#[tokio::test]
+async fn skipped_symlinks_are_not_uploaded() {
+  let ssm = FakeSsm::new();
+
+  assert_eq!(upload(&ssm, SymlinkPolicy::Skip).await, ["/app/conf/app.yaml"]);
+}
+
+#[tokio::test]
+async fn followed_symlinks_upload_their_targets_and_stop_at_cycles() {
+  let ssm = FakeSsm::new();
+
+  let names = upload(&ssm, SymlinkPolicy::Follow).await;
+
+  assert_eq!(names, ["/app/conf/app.yaml", "/app/conf/current.yaml", "/app/linked/app.yaml", "/app/linked/current.yaml"]);
+}
+
+#[tokio::test]
+async fn download_refuses_to_write_through_a_symlinked_directory() {
+  let ssm = FakeSsm::new();
+  ssm.insert("/app/linked/secret", "value");
+  let target = tempfile::tempdir().unwrap();
+  let outside = tempfile::tempdir().unwrap();
+  symlink(outside.path(), target.path().join("linked")).unwrap();
+
+  let err = download_to_dir(&ssm.client(), DownloadSource::Prefix("/app".to_string()), target.path().to_path_buf(), DownloadOptions::default()).await.unwrap_err();
+
+  assert!(format!("{err:#}").contains("through the symlink"), "{err:#}");
+  assert!(!outside.path().join("secret").exists());
+}
+
+#[tokio::test]
+async fn check_dir_compares_symlink_targets() {
+  let ssm = FakeSsm::new();
+  let dir = tempfile::tempdir().unwrap();
+  write_fixtures(dir.path());
+  upload_dir(&ssm.client(), dir.path().to_path_buf(), "/app".to_string(), UploadOptions::default()).await.unwrap();
+
+  check_dir(&ssm.client(), None, dir.path().to_path_buf(), "/app".to_string(), UploadOptions::default()).await.unwrap();
+}
+
+#[tokio::test]
+async fn download_refuses_symlinks_that_point_outside_the_output_dir() {
+  for target in ["../outside", "/etc/passwd", "conf/../../outside"] {
+    let ssm = FakeSsm::new();
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("conf")).unwrap();
+    symlink(target, dir.path().join("escape")).unwrap();
+    upload_dir(&ssm.client(), dir.path().to_path_buf(), "/app".to_string(), UploadOptions::default()).await.unwrap();
+    let out = tempfile::tempdir().unwrap();
+
+    let err = download_to_dir(&ssm.client(), DownloadSource::Prefix("/app".to_string()), out.path().to_path_buf(), DownloadOptions::default()).await.unwrap_err();
+
+    assert!(format!("{err:#}").contains("points outside"), "{err:#}");
+    assert!(out.path().join("escape").symlink_metadata().is_err());
+  }
+}
+
+#[tokio::test]
+async fn download_writes_files_before_the_symlinks_that_could_redirect_them() {
+  let ssm = FakeSsm::new();
+  upload(&ssm, SymlinkPolicy::Preserve).await;
+  ssm.insert("/app/linked/secret", "value");
+  let target = tempfile::tempdir().unwrap();
+
+  download_to_dir(&ssm.client(), DownloadSource::Prefix("/app".to_string()), target.path().to_path_buf(), DownloadOptions::default()).await.unwrap_err();
+
+  assert!(!target.path().join("conf/secret").exists());
+}
+
+#[tokio::test]
+async fn downloading_a_single_symlink_recreates_it() {
+  let ssm = FakeSsm::new();
+  upload(&ssm, SymlinkPolicy::Preserve).await;
+  let target = tempfile::tempdir().unwrap();
+
+  download_to_dir(&ssm.client(), DownloadSource::Name("/app/conf/current.yaml".to_string()), target.path().to_path_buf(), DownloadOptions::default()).await.unwrap();
+
+  assert_eq!(fs::read_link(target.path().join("current.yaml")).unwrap(), Path::new("app.yaml"));
+}
@@ -0,0 +1,134 @@
+mod support;
+
+use std::{fs, path::Path};
+
+use aws_sdk_ssm::types::ParameterType;
+use ops::apply::check_dir;
+use ops::sync::sync_to_ssm;
+use ops::{UploadOptions, upload_dir};
+use support::FakeSsm;
+
+fn write_fixtures(dir: &Path) {
+  fs::write(dir.join("app.env"), "A=1\n").unwrap();
+  fs::write(dir.join(".env.local"), "B=2\n").unwrap();
+  fs::create_dir(dir.join(".cache")).unwrap();
+  fs::write(dir.join(".cache/state"), "x").unwrap();
+  fs::write(dir.join("debug.log"), "log line\n".repeat(1000)).unwrap();
+}
+
+async fn upload(options: UploadOptions) -> Vec<String> {
+  let ssm = FakeSsm::new();
+  let dir = tempfile::tempdir().unwrap();
+  write_fixtures(dir.path());
+
+  upload_dir(&ssm.client(), dir.path().to_path_buf(), "/app".to_string(), options).await.unwrap();
+  ssm.parameters().into_keys().filter(|name| !name.contains(".part")).collect()
+}
+
+#[tokio::test]
+async fn hidden_files_are_skipped_by_default() {
+  assert_eq!(upload(UploadOptions::default()).await, ["/app/app.env", "/app/debug.log.manifest"]);
+}
+
+#[tokio::test]
+async fn hidden_files_are_uploaded_with_include_hidden() {
+  let names = upload(UploadOptions { include_hidden: true, ..Default::default() }).await;
+
+  assert_eq!(names, ["/app/.cache/state", "/app/.env.local", "/app/app.env", "/app/debug.log.manifest"]);
+}
+
+#[tokio::test]
+async fn files_over_the_size_limit_are_skipped() {
+  assert_eq!(upload(UploadOptions { max_file_size: Some(1024), ..Default::default() }).await, ["/app/app.env"]);
+}
+
+#[tokio::test]
+async fn sync_delete_keeps_parameters_for_skipped_files() {
+  let ssm = FakeSsm::new();
+  let dir = tempfile::tempdir().unwrap();
+  write_fixtures(dir.path());
+  upload_dir(&ssm.client(), dir.path().to_path_buf(), "/app".to_string(), UploadOptions { include_hidden: true, ..Default::default() }).await.unwrap();
+  fs::remove_file(dir.path().join("app.env")).unwrap();
+
+  let options = UploadOptions { max_file_size: Some(1024), ..Default::default() };
+  sync_to_ssm(&ssm.client(), dir.path().to_path_buf(), "/app".to_string(), options, true).await.unwrap();
+
+  let names = ssm.parameters().into_keys().filter(|name| !name.contains(".part")).collect::<Vec<_>>();
+  assert_eq!(names, ["/app/.cache/state", "/app/.env.local", "/app/debug.log.manifest"]);
+}
+
+#[tokio::test]
+async fn changing_type_key_or_encryption_uploads_unchanged_files_again() {
+  let ssm = FakeSsm::new();
+  let dir = tempfile::tempdir().unwrap();
+  fs::write(dir.path().join("app.env"), "A=1\n").unwrap();
+  let upload = async |options: UploadOptions| {
+    ssm.clear_calls();
+    upload_dir(&ssm.client(), dir.path().to_path_buf(), "/app".to_string(), options).await.unwrap();
+    ssm.count("PutParameter")
+  };
+
+  assert_eq!(upload(UploadOptions { r#type: ParameterType::String, ..Default::default() }).await, 1);
+  assert_eq!(upload(UploadOptions { r#type: ParameterType::String, ..Default::default() }).await, 0);
+  assert_eq!(upload(UploadOptions::default()).await, 1);
+  assert_eq!(upload(UploadOptions::default()).await, 0);
+  assert_eq!(upload(UploadOptions { kms_key_id: Some("alias/app".to_string()), ..Default::default() }).await, 1);
+  let recipient = age::x25519::Identity::generate().to_public();
+  assert_eq!(upload(UploadOptions { kms_key_id: Some("alias/app".to_string()), encrypt_with: vec![recipient.clone()], ..Default::default() }).await, 2);
+  assert_eq!(upload(UploadOptions { kms_key_id: Some("alias/app".to_string()), encrypt_with: vec![recipient], ..Default::default() }).await, 0);
+  assert_eq!(ssm.parameters()["/app/app.env.part0"].key_id.as_deref(), Some("alias/app"));
+  assert!(!ssm.parameters().contains_key("/app/app.env"));
+}
+
+#[tokio::test]
+async fn check_dir_ignores_the_files_upload_skips() {
+  let ssm = FakeSsm::new();
+  let dir = tempfile::tempdir().unwrap();
+  write_fixtures(dir.path());
+  upload_dir(&ssm.client(), dir.path().to_path_buf(), "/app".to_string(), UploadOptions::default()).await.unwrap();
+  ssm.insert("/app/.env.local", "B=3\n");
+
+  check_dir(&ssm.client(), None, dir.path().to_path_buf(), "/app".to_string(), UploadOptions::default()).await.unwrap();
+}
+
+#[tokio::test]
+async fn check_dir_compares_string_lists_as_stored() {
+  let ssm = FakeSsm::new();
+  let dir = tempfile::tempdir().unwrap();
+  fs::write(dir.path().join("hosts"), "a.example.com\nb.example.com\n").unwrap();
+  let options = || UploadOptions { r#type: ParameterType::StringList, ..Default::default() };
+  upload_dir(&ssm.client(), dir.path().to_path_buf(), "/app".to_string(), options()).await.unwrap();
+
+  check_dir(&ssm.client(), None, dir.path().to_path_buf(), "/app".to_string(), options()).await.unwrap();
+  ssm.set_value("/app/hosts", "a.example.com");
+  check_dir(&ssm.client(), None, dir.path().to_path_buf(), "/app".to_string(), options()).await.unwrap_err();
+}
+
+#[tokio::test]
+async fn age_files_uploaded_without_encryption_are_returned_as_stored() {
+  let ssm = FakeSsm::new();
+  let dir = tempfile::tempdir().unwrap();
+  let recipient = age::x25519::Identity::generate().to_public();
+  let armored = ops::crypto::encrypt_armored(&[recipient], b"secret").unwrap();
+  fs::write(dir.path().join("secret.age"), &armored).unwrap();
+
+  upload_dir(&ssm.client(), dir.path().to_path_buf(), "/app".to_string(), UploadOptions::default()).await.unwrap();
+
+  assert_eq!(ops::files::fetch_file(&ssm.client(), "/app/secret.age").await.unwrap(), armored);
+}
+
+#[tokio::test]
+async fn uploads_count_standard_parameters_across_the_account() {
+  let ssm = FakeSsm::new();
+  for i in 0..9_999 {
+    ssm.insert(&format!("/other/{i}"), "x");
+  }
+  let dir = tempfile::tempdir().unwrap();
+  fs::write(dir.path().join("a.env"), "A=1\n").unwrap();
+  fs::write(dir.path().join("b.env"), "B=1\n").unwrap();
+
+  let err = upload_dir(&ssm.client(), dir.path().to_path_buf(), "/app".to_string(), UploadOptions::default()).await.unwrap_err();
+
+  assert!(err.to_string().contains("leaving 10001 in the account, over the 10000 parameter quota"), "{err:#}");
+  assert_eq!(ssm.count("PutParameter"), 0);
+}